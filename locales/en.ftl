@@ -0,0 +1,305 @@
+# Messages yt-sync shows, in Fluent syntax (https://projectfluent.org). To translate
+# them, copy this file to <language>.ftl, such as de.ftl, translate the text after each =, and add
+# the file to LOCALES in src/i18n.rs.
+
+## Syncing
+
+playlist-start = Downloading playlist: { $playlist }
+video-downloaded = Downloaded "{ $file }"
+video-failed = Failed to download "{ $file }"
+throttled = YouTube is throttling requests, pausing for { $cooldown }s and then waiting { $delay }s between downloads
+synced = { $count ->
+    [one] { $count } new song successfully synced to { $location }
+   *[other] { $count } new songs successfully synced to { $location }
+}
+deferred = { $count ->
+    [one] { $count } new video was left for the next run
+   *[other] { $count } new videos were left for the next run
+}
+resumed = { $count ->
+    [one] { $count } download interrupted in an earlier run was resumed
+   *[other] { $count } downloads interrupted in an earlier run were resumed
+}
+removed = { $count ->
+    [one] { $count } older video was removed to keep only the newest
+   *[other] { $count } older videos were removed to keep only the newest
+}
+pruned = { $count ->
+    [one] { $count } video no longer in the playlist was pruned
+   *[other] { $count } videos no longer in the playlist were pruned
+}
+would-prune = Would prune "{ $file }", as it is no longer in the playlist
+would-remove = Would remove "{ $file }", as it is no longer among the newest
+would-mirror = Would copy "{ $file }" to { $mirror }
+would-unmirror = Would delete "{ $file }" from { $mirror }
+skipped-unavailable = { $count ->
+    [one] { $count } unavailable video was skipped, run `yt-sync retry-unavailable` to try it again
+   *[other] { $count } unavailable videos were skipped, run `yt-sync retry-unavailable` to try them again
+}
+timings = Took { $listing } listing, { $scanning } scanning, { $downloading } downloading, { $post_processing } post-processing and { $writing_playlist } writing the playlist
+failed-with-log = Failed to download { $video } ({ $reason }), yt-dlp output saved to { $log }
+failed = Failed to download { $video } ({ $reason })
+summary-playlist = Playlist
+summary-new = New
+summary-skipped = Skipped
+summary-failed = Failed
+summary-pruned = Pruned
+summary-downloaded = Downloaded
+summary-on-disk = On disk
+summary-total = Total
+summary-error = not synced
+
+## Details and problems while syncing
+
+skipping-blacklisted = Skipping blacklisted video { $video }
+skipping-archived = Skipping video { $video }, as it is in the download archive
+removed-older = Removed "{ $file }", as it is no longer among the newest
+pruned-file = Pruned "{ $file }", as it is no longer in the playlist
+renamed-retitled = Renamed "{ $file }" to "{ $new_file }", as its video was retitled
+rename-exists = Could not rename "{ $file }" after the new title of its video, as { $new_file } already exists
+low-on-space = Only { $available } free at { $location }, less than the minimum of { $minimum }, so no more videos are downloaded to it
+organize-failed = Could not organize "{ $file }": { $error }
+organize-exists = Could not organize "{ $file }", as { $destination } already exists
+file-missing = yt-dlp finished downloading { $video }, but its file isn't in { $location }
+sidecar-failed = Failed to write the sidecar of { $path }: { $error }
+chapters-failed = Failed to find the chapters of { $path }: { $error }
+permissions-failed = Failed to change the permissions of { $path }: { $error }
+mtime-failed = Failed to set the modification time of { $path }: { $error }
+hook-failed = { $error } after downloading { $video }
+files-state-failed = Failed to save the state of files: { $error }
+downloads-state-failed = Failed to save the state of downloads: { $error }
+trying-fallback-format = Could not download { $video } in its usual format, trying "{ $format }"
+retrying = Downloading { $video } failed ({ $reason }), trying again in { $delay } ({ $attempt } of { $retries })
+video-needs-sign-in = yt-dlp needs a signed-in account to download { $video } in playlist { $playlist }, so { $advice }
+playlist-needs-sign-in = yt-dlp needs a signed-in account to list playlist { $playlist }, so { $advice }
+cookies-missing = set cookies_file or cookies_from_browser on its item or in the config to give yt-dlp cookies for YouTube
+cookies-rejected = the cookies yt-dlp was given may have expired, or be for an account that can't see it
+listing-fallback = Failed to list playlist { $playlist } with yt-dlp, falling back to { $fallback }
+mirrored = Mirrored to { $mirror }, copying { $copied ->
+    [one] { $copied } file
+   *[other] { $copied } files
+} and deleting { $deleted }
+mirror-failed = Failed to mirror { $playlist } to { $mirror }: { $error }
+out-of-budget = Downloaded { $size } this run, the remaining videos will be synced next run
+shared-location-pruned = it can't be pruned, as its location { $location } is shared with another playlist
+manifest-lists = Manifest lists { $videos }
+playlist-contains = Playlist contains: { $files }
+yt-dlp-unrunnable = Failed to execute yt-dlp: { $error }
+yt-dlp-failed = yt-dlp failed with error code { $code } and output: { $output }
+fallback-empty = { $fallback } returned no videos for playlist { $playlist }
+hook-unrunnable = Hook "{ $hook }" could not run: { $error }
+hook-status = Hook "{ $hook }" failed with { $status }
+manifest-invalid = { $path } is invalid: { $error }
+ffprobe-not-found = ffprobe was not found
+ffprobe-failed = ffprobe failed with { $status }: { $error }
+playlist-file-no-parent = Cannot save a playlist for { $location }, as it has no parent directory to save it in
+out-of-time = Reached the maximum runtime
+out-of-runtime = Reached the maximum runtime, the remaining videos will be synced next run
+
+## Errors ending a sync
+
+invalid-item = Invalid item for playlist { $playlist }: { $reason }
+listing-failed = Failed to list playlist { $playlist }: { $reason }
+sync-cancelled = Sync cancelled
+stopped-low-on-space = Stopped downloading to { $locations }, as it ran low on free space
+playlists-not-synced = { $count ->
+    [one] { $count } playlist could not be synced:
+   *[other] { $count } playlists could not be synced:
+}
+videos-failed = { $count ->
+    [one] { $count } video failed to download:
+   *[other] { $count } videos failed to download:
+}
+video-failed-in-playlist = { $video } in playlist { $playlist } ({ $reason })
+video-failed-in-playlist-with-log = { $video } in playlist { $playlist } ({ $reason }), yt-dlp output saved to { $log }
+hooks-failed = { $count ->
+    [one] { $count } hook failed:
+   *[other] { $count } hooks failed:
+}
+listing-stopped = listing stopped unexpectedly
+yt-dlp-not-found = yt-dlp was not found at { $path }. Install it from https://github.com/yt-dlp/yt-dlp, or set yt_dlp_path in the config or pass --yt-dlp to say where it is
+yt-dlp-unrunnable-at = Could not run yt-dlp at { $path }: { $error }
+yt-dlp-version-failed = { $path } --version failed with { $status }: { $error }
+yt-dlp-too-old = yt-dlp { $version } is older than { $minimum }, the oldest version yt-sync works with. Run yt-sync update-ytdlp, or update it the way it was installed
+
+## Commands
+
+error = Error: { $error }
+created-config = Created config at { $path }
+loaded-config = Loaded config at { $path }
+add-playlists = Add the playlists to sync to it, or run yt-sync add, then run yt-sync again
+config-valid = { $count ->
+    [one] The config is valid, with { $count } item to sync
+   *[other] The config is valid, with { $count } items to sync
+}
+item-added = Added { $item } to the config, syncing to { $location }
+item-removed = Removed { $item } from the config
+item-syncs-to = { $item }: { $format } to { $locations }
+blacklisted = Added { $videos } to the blacklist
+unblacklisted = Removed { $videos } from the blacklist
+exported = Exported the config and state to { $path }
+imported = Imported the config and state from { $path }. Check that the locations in the config exist on this machine before syncing.
+retrying-unavailable = { $count ->
+    [one] { $count } unavailable video will be attempted again next sync
+   *[other] { $count } unavailable videos will be attempted again next sync
+}
+status-up-to-date = { $playlist }: up to date
+status-new-videos = { $count ->
+    [one] { $playlist }: { $count } new video
+   *[other] { $playlist }: { $count } new videos
+}
+status-not-listed = { $playlist }: could not be listed: { $error }
+status-cancelled = Status cancelled
+everything-up-to-date = Everything is up to date
+verify-failed = Failed to verify { $location }: { $error }
+verify-cancelled = Verification cancelled
+unfinished-download = left over from an unfinished download
+empty-file = empty file
+ffmpeg-not-found = ffmpeg was not found, which --deep needs
+ffmpeg-failed = ffmpeg failed with { $status }
+verified = Checked { $checked ->
+    [one] { $checked } file
+   *[other] { $checked } files
+}, found { $broken ->
+    [one] { $broken } problem
+   *[other] { $broken } problems
+}
+files-failed-verification = { $count ->
+    [one] { $count } file failed verification
+   *[other] { $count } files failed verification
+}
+no-duplicates = No duplicates found
+duplicate-video = { $count ->
+    [one] Video { $video } was downloaded { $count } time:
+   *[other] Video { $video } was downloaded { $count } times:
+}
+identical-files = These files are identical:
+ffprobe-missing = ffprobe was not found, so uploaders and durations are left out
+tags-unreadable = Could not read the tags of { $path }: { $error }
+linked-duplicates = { $count ->
+    [one] Replaced { $count } copy with a symlink
+   *[other] Replaced { $count } copies with symlinks
+}
+unreadable = Could not read { $path }: { $error }
+unwritable = Could not write { $path }: { $error }
+already-exists = { $path } already exists, pass --force to replace it
+config-unreadable = Could not read the config { $path }: { $error }
+config-uneditable = Could not edit the config { $path }: { $error }
+config-unwritable = Could not write the config { $path }: { $error }
+blacklist-not-a-list = The blacklist in the config is not a list
+items-not-tables = The items in the config are not a list of tables
+item-exists = There already is an item named { $item }
+no-such-item = There is no item named { $item }
+several-items-named = Several items are named { $item }, so give them names to tell them apart
+not-a-bundle = { $path } is not a yt-sync bundle: { $error }
+bundle-too-new = { $path } was exported by a newer version of yt-sync, update to import it
+bundle-has-config-dir = The bundle has a config directory, pass --config-dir to import it to
+bundle-invalid-file-name = The bundle has an invalid config file name "{ $name }"
+read-failed = Failed to read { $path }: { $error }
+no-urls = No URLs were found in { $path }
+using-yt-dlp = Using yt-dlp { $version } at { $path }
+daemon-with-playlists = The daemon syncs the playlists in the config file, so it can't be given --playlist-id or --from-file
+daemon-with-dry-run = The daemon never stops, so it can't be given --dry-run or --json
+sync-options-twice = Options were given both before and after the command; give them all after it, as in "yt-sync sync --prune --dry-run"
+usage-failed = Failed to add up disk usage: { $error }
+usage-by-playlist = By playlist
+usage-by-uploader = By uploader
+manifests-failed = Failed to read the manifests: { $error }
+duplicates-failed = Failed to look for duplicates: { $error }
+linking-failed = Failed to link duplicates: { $error }
+files-unit = { $count ->
+    [one] file
+   *[other] files
+}
+unknown-uploader = Unknown
+stats-location = Location
+stats-videos = Videos
+stats-chapters = Chapters
+stats-size = Size
+stats-last-download = Last download
+no-config-dir = Could not find your config directory, pass a config path with --config
+no-current-dir = Could not read the current directory ({ $error }), pass --location
+current-dir-not-utf8 = The current directory "{ $path }" is not valid UTF-8, pass --location
+config-dir-uncreatable = Could not create the config directory { $path }: { $error }
+config-dir-unreadable = Could not read the config directory { $path }: { $error }
+config-unparsable = Failed to parse the config { $path }: { $error }
+config-invalid = The config { $path } is invalid:
+    { $problems }
+main-only-settings = { $path } sets { $settings }, which only the main config can
+invalid-setting = Invalid { $name } in the config: { $error }
+unknown-unit = unknown unit "{ $unit }" in "{ $value }"
+expected-number = expected a number before "{ $unit }" in "{ $value }"
+
+## Running as a daemon
+
+config-changed = Config changed: { $change }
+daemon-stopped = Daemon stopped
+sync-failed = Sync failed: { $error }
+config-change-invalid = Ignoring the changed config, as it is invalid: { $error }
+change-added-playlist = added playlist { $playlist } at { $location }
+change-removed-playlist = removed playlist { $playlist } at { $location }
+change-item-field = changed { $setting } of playlist { $playlist } from { $from } to { $to }
+change-item-setting = changed { $setting } of playlist { $playlist }
+change-item-blacklist = changed the blacklist of playlist { $playlist }
+change-item-mirrors = changed the mirrors of playlist { $playlist }
+change-item-pruning = changed pruning of playlist { $playlist }
+change-item-format = changed the download format of playlist { $playlist }
+change-item-kind = changed the kind of playlist { $playlist }
+change-item-renamed = renamed playlist { $playlist } to { $name }
+change-item-permissions = changed permissions of playlist { $playlist }
+change-item-pinned = changed the pinned videos of playlist { $playlist }
+change-item-cookies = changed the cookies of playlist { $playlist }
+change-item-settings = changed the settings of playlist { $playlist }
+change-item-entries = changed the entries for playlist { $playlist }
+change-fallback = changed the fallback instance
+change-blacklist = changed the blacklist
+change-cookies = changed the cookies
+change-hooks = changed the hooks
+change-setting = changed { $setting }
+change-settings = changed the settings
+
+## Setting up
+
+setup-intro = No config was found, so let's set up the first playlist to sync.
+setup-found-yt-dlp = Found yt-dlp { $version }
+setup-missing-yt-dlp = yt-dlp was not found. yt-sync downloads with it, so install it from https://github.com/yt-dlp/yt-dlp before syncing.
+setup-found-ffmpeg = Found { $version }
+setup-missing-ffmpeg = ffmpeg was not found. yt-dlp needs it to convert audio and merge video, so install it from https://ffmpeg.org before syncing.
+setup-playlist = Playlist URL or ID
+setup-not-a-playlist = That URL has no playlist in it, copy the URL of the playlist page
+setup-format = Download audio or video
+setup-audio-or-video = Answer audio or video
+setup-location = Save it to
+setup-m3u = Also write an .m3u playlist file
+setup-cancelled = Setup was cancelled
+## Checking the config
+
+problem-on-line = line { $line }: { $problem }
+item-problem = playlist { $playlist }: { $problem }
+id-empty = id is empty
+no-location = it has no location
+setting-invalid = { $setting } is invalid: { $error }
+audio-format-unknown = audio_format "{ $value }" is not one of { $choices }
+container-unknown = container "{ $value }" is not one of { $choices }
+video-quality-invalid = video_quality "{ $value }" is not a height such as "1080p"
+split-chapters-with-organize = split_chapters can't be used with organize, as the chapters of a video are kept next to it
+browser-unknown = cookies_from_browser "{ $value }" is not one of { $choices }
+chmod-invalid = chmod "{ $value }" is not an octal mode such as "664"
+chown-unknown-user = chown has an unknown user "{ $user }"
+chown-unknown-group = chown has an unknown group "{ $group }"
+organize-outside-location = The organize template "{ $template }" must be a relative path to a file inside the location
+organize-unclosed = Unclosed {"{"} in the organize template "{ $template }"
+organize-unknown-field = Unknown field { $field } in the organize template "{ $template }", which can use { $fields }
+
+## Updating
+
+latest-version = yt-sync { $version } is the latest version
+update-available = yt-sync { $version } is available, run yt-sync self-update to install it (this is { $current })
+downloading-update = Downloading yt-sync { $version }
+checksum-mismatch = The download of { $file } doesn't match its checksum, so it was not installed
+updated = Updated yt-sync from { $current } to { $version }
+release-check-failed = Could not check GitHub for releases: { $error }
+release-missing-asset = Release { $release } has no { $file }
+checksum-missing = { $checksums } has no checksum for { $file }
+replace-failed = Could not replace { $path }: { $error }
+yt-dlp-update-failed = yt-dlp could not update itself ({ $status }). If it was installed with pip or a package manager, update it with that instead
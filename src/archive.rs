@@ -0,0 +1,40 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use crate::storage::Storage;
+
+// Read the IDs of the YouTube videos recorded in a yt-dlp download archive, which has a line such
+// as "youtube dQw4w9WgXcQ" for each video downloaded. An archive that doesn't exist yet is empty.
+pub fn read(storage: &dyn Storage, path: &Path) -> io::Result<HashSet<String>> {
+    let content = match storage.read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(String::from_utf8_lossy(&content)
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("youtube "))
+        .map(|video_id| video_id.trim().to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn reads_youtube_videos_from_archives() {
+        let storage = MemoryStorage::default();
+        let path = Path::new("/music/archive.txt");
+        assert!(read(&storage, path).unwrap().is_empty());
+
+        storage
+            .write(path, b"youtube a\nvimeo 123\nyoutube b\r\n\n")
+            .unwrap();
+        let archived = read(&storage, path).unwrap();
+        assert_eq!(archived.len(), 2);
+        assert!(archived.contains("a") && archived.contains("b"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::Path;
+
+use toml_edit::{Array, DocumentMut, Item};
+
+use crate::i18n::tr;
+
+// Add video IDs to the blacklist at the top of a config, or remove them from it, keeping the rest
+// of the file as it was written, comments included.
+fn edit_document(
+    content: &str,
+    video_ids: &[String],
+    add: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document: DocumentMut = content.parse()?;
+    let blacklist = document
+        .entry("blacklist")
+        .or_insert(Item::Value(Array::new().into()))
+        .as_array_mut()
+        .ok_or_else(|| tr!("blacklist-not-a-list"))?;
+    for video_id in video_ids {
+        let position = blacklist
+            .iter()
+            .position(|value| value.as_str() == Some(video_id));
+        match (add, position) {
+            (true, None) => blacklist.push(video_id.as_str()),
+            (false, Some(position)) => {
+                let removed = blacklist.remove(position);
+                // The next ID takes the place of the removed one, spacing included.
+                if let Some(next) = blacklist.get_mut(position) {
+                    *next.decor_mut() = removed.decor().clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(document.to_string())
+}
+
+// Change the blacklist of the config file in place.
+pub fn edit(
+    config_path: &Path,
+    video_ids: &[String],
+    add: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(config_path).map_err(|e| {
+        tr!(
+            "config-unreadable",
+            path = config_path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    let content = edit_document(&content, video_ids, add).map_err(|e| {
+        tr!(
+            "config-uneditable",
+            path = config_path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    fs::write(config_path, content).map_err(|e| {
+        tr!(
+            "config-unwritable",
+            path = config_path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    let videos = video_ids.join(", ");
+    match add {
+        true => println!("{}", tr!("blacklisted", videos = videos)),
+        false => println!("{}", tr!("unblacklisted", videos = videos)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_the_blacklist_keeping_comments() {
+        let config = "# My playlists\n[[items]]\nid = \"PL1\" # favourites\n";
+        let ids = ["a".to_string(), "b".to_string()];
+        let added = edit_document(config, &ids, true).unwrap();
+        assert_eq!(
+            added,
+            "blacklist = [\"a\", \"b\"]\n# My playlists\n[[items]]\nid = \"PL1\" # favourites\n"
+        );
+        let removed = edit_document(&added, &ids[..1], false).unwrap();
+        assert!(removed.starts_with("blacklist = [\"b\"]\n"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir_files;
+use crate::i18n::tr;
+use crate::state::State;
+use crate::storage::Storage;
+
+// Bumped whenever the layout of bundles changes, so bundles newer than this version are refused.
+const VERSION: u32 = 1;
+
+// Everything needed to carry on syncing a library on another machine, apart from the media
+// itself: the config, any config directory, and the state with its history of unavailable
+// videos and organized files.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Bundle {
+    version: u32,
+    // The contents of the config file, if there is one.
+    config: Option<String>,
+    // The contents of the TOML files in the config directory, by file name.
+    #[serde(default)]
+    config_dir: BTreeMap<String, String>,
+    state: State,
+}
+
+// Write the config and state to a single file.
+pub fn export(
+    path: &Path,
+    config_path: &Path,
+    config_dir: Option<&Path>,
+    state: State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = match config_path.exists() {
+        true => Some(fs::read_to_string(config_path)?),
+        false => None,
+    };
+    let mut config_dir_contents = BTreeMap::new();
+    if let Some(config_dir) = config_dir {
+        for file in config_dir_files(config_dir)? {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            config_dir_contents.insert(name.into_owned(), fs::read_to_string(&file)?);
+        }
+    }
+    let bundle = Bundle {
+        version: VERSION,
+        config,
+        config_dir: config_dir_contents,
+        state,
+    };
+    fs::write(path, serde_json::to_vec_pretty(&bundle)?).map_err(|e| {
+        tr!(
+            "unwritable",
+            path = path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    println!("{}", tr!("exported", path = path.display().to_string()));
+    Ok(())
+}
+
+// Restore the config and state from a bundle. Existing ones are only replaced with force, so
+// importing by mistake doesn't throw away a library's history.
+pub fn import(
+    path: &Path,
+    config_path: &Path,
+    config_dir: Option<&Path>,
+    storage: &dyn Storage,
+    data_dir: &Path,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let display = path.display().to_string();
+    let content = fs::read(path)
+        .map_err(|e| tr!("unreadable", path = display.as_str(), error = e.to_string()))?;
+    let bundle: Bundle = serde_json::from_slice(&content).map_err(|e| {
+        tr!(
+            "not-a-bundle",
+            path = display.as_str(),
+            error = e.to_string()
+        )
+    })?;
+    if bundle.version > VERSION {
+        return Err(tr!("bundle-too-new", path = display.as_str()).into());
+    }
+    let config_dir = match (config_dir, bundle.config_dir.is_empty()) {
+        (_, true) => None,
+        (Some(config_dir), false) => Some(config_dir),
+        (None, false) => return Err(tr!("bundle-has-config-dir").into()),
+    };
+
+    if !force {
+        let state_path = State::path(data_dir);
+        let existing = if bundle.config.is_some() && config_path.exists() {
+            Some(config_path)
+        } else if storage.size(&state_path).is_ok() {
+            Some(state_path.as_path())
+        } else {
+            None
+        };
+        if let Some(existing) = existing {
+            return Err(tr!("already-exists", path = existing.display().to_string()).into());
+        }
+    }
+
+    if let Some(config) = &bundle.config {
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(config_path, config)?;
+    }
+    if let Some(config_dir) = config_dir {
+        fs::create_dir_all(config_dir)?;
+        for (name, content) in &bundle.config_dir {
+            // Only plain file names, so a crafted bundle can't write outside the directory.
+            let file_name = Path::new(name).file_name().unwrap_or_default();
+            if file_name != name.as_str() {
+                return Err(tr!("bundle-invalid-file-name", name = name.as_str()).into());
+            }
+            fs::write(config_dir.join(file_name), content)?;
+        }
+    }
+    bundle.state.save(storage, data_dir)?;
+    println!("{}", tr!("imported", path = display));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn round_trips_config_and_state() {
+        let dir = std::env::temp_dir().join(format!("yt-sync-bundle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (config_path, bundle_path) = (dir.join("config.toml"), dir.join("bundle.json"));
+        fs::write(&config_path, "items = []\n").unwrap();
+        let mut state = State::default();
+        state.unavailable.insert("a".to_string(), 3);
+        export(&bundle_path, &config_path, None, state).unwrap();
+
+        let storage = MemoryStorage::default();
+        let data_dir = Path::new("/data");
+        let imported_config = dir.join("imported/config.toml");
+        import(
+            &bundle_path,
+            &imported_config,
+            None,
+            &storage,
+            data_dir,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&imported_config).unwrap(),
+            "items = []\n"
+        );
+        assert!(State::load(&storage, data_dir).unwrap().is_unavailable("a"));
+        assert!(import(
+            &bundle_path,
+            &imported_config,
+            None,
+            &storage,
+            data_dir,
+            false
+        )
+        .is_err());
+        import(
+            &bundle_path,
+            &imported_config,
+            None,
+            &storage,
+            data_dir,
+            true,
+        )
+        .unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
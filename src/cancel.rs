@@ -0,0 +1,94 @@
+use std::io;
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::i18n::tr;
+
+// A handle for aborting a running sync. Clones share the same state, so one can be handed to
+// another thread (or a signal handler) to cancel the sync from there.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    children: Arc<Mutex<Vec<Arc<Mutex<Child>>>>>,
+}
+
+impl CancelToken {
+    // Cancel the sync, killing any yt-dlp processes it is running.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for child in self.children.lock().unwrap().iter() {
+            let _ = child.lock().unwrap().kill();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Sleep for a while, waking up early if the sync is cancelled.
+    pub fn sleep(&self, duration: Duration) {
+        let wake_at = Instant::now() + duration;
+        while !self.is_cancelled() {
+            let remaining = wake_at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(200)));
+        }
+    }
+
+    // Spawn a command whose process is killed if the sync is cancelled.
+    pub fn spawn(&self, command: &mut Command) -> io::Result<Process> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                tr!("sync-cancelled"),
+            ));
+        }
+        let child = Arc::new(Mutex::new(command.spawn()?));
+        self.children.lock().unwrap().push(Arc::clone(&child));
+        Ok(Process {
+            child,
+            token: self.clone(),
+        })
+    }
+}
+
+// A child process registered with a CancelToken.
+pub struct Process {
+    child: Arc<Mutex<Child>>,
+    token: CancelToken,
+}
+
+impl Process {
+    pub fn take_stdout(&self) -> Option<ChildStdout> {
+        self.child.lock().unwrap().stdout.take()
+    }
+
+    pub fn take_stderr(&self) -> Option<ChildStderr> {
+        self.child.lock().unwrap().stderr.take()
+    }
+
+    pub fn kill(&self) {
+        let _ = self.child.lock().unwrap().kill();
+    }
+
+    // Wait for the process to exit. The lock is only held briefly, so cancelling can still kill it.
+    pub fn wait(self) -> io::Result<ExitStatus> {
+        let status = loop {
+            if let Some(status) = self.child.lock().unwrap().try_wait()? {
+                break status;
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        self.token
+            .children
+            .lock()
+            .unwrap()
+            .retain(|child| !Arc::ptr_eq(child, &self.child));
+        Ok(status)
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use clap::ValueEnum;
+use log::warn;
+use serde::Serialize;
+
+use crate::i18n::tr;
+use crate::playlist::civil_date;
+use crate::probe::{self, MediaInfo};
+use crate::state::State;
+use crate::storage::Storage;
+
+// The extensions of the files yt-dlp writes next to downloads, such as thumbnails and subtitles,
+// which are never the download itself. Any other extension can be, as yt-dlp can be asked for
+// all sorts of formats.
+const SIDE_EXTENSIONS: [&str; 13] = [
+    "jpg",
+    "jpeg",
+    "png",
+    "webp",
+    "json",
+    "description",
+    "vtt",
+    "srt",
+    "ass",
+    "lrc",
+    "part",
+    "ytdl",
+    "temp",
+];
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+pub enum CatalogFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+// A downloaded file, as listed in the catalog.
+#[derive(Serialize, Debug)]
+pub struct Entry {
+    pub video_id: String,
+    pub title: String,
+    pub uploader: Option<String>,
+    // In seconds.
+    pub duration: Option<f64>,
+    // In bytes.
+    pub size: u64,
+    pub path: String,
+    // When the file was last written, as YYYY-MM-DD.
+    pub downloaded: Option<String>,
+}
+
+// Lists the downloaded files of locations. Titles, uploaders and durations come from the tags
+// yt-dlp embedded, read with ffprobe; without ffprobe the title comes from the file name and the
+// rest is left out.
+#[derive(Default)]
+pub struct Catalog {
+    pub entries: Vec<Entry>,
+    seen: HashSet<String>,
+    without_ffprobe: bool,
+}
+
+impl Catalog {
+    // Add the files of a location.
+    pub fn add_location(
+        &mut self,
+        storage: &dyn Storage,
+        location: &str,
+        state: &State,
+    ) -> io::Result<()> {
+        for (video_id, title, path) in location_files(storage, location, state)? {
+            let path = Path::new(location).join(path);
+            // Locations synced by several items are only listed once.
+            if !self.seen.insert(path.to_string_lossy().into_owned()) {
+                continue;
+            }
+            let Ok(size) = storage.size(&path) else {
+                continue;
+            };
+            let downloaded = storage
+                .modified(&path)
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|elapsed| {
+                    let (year, month, day) = civil_date(elapsed.as_secs() as i64);
+                    format!("{:04}-{:02}-{:02}", year, month, day)
+                });
+            let mut info = self.probe(&path);
+            self.entries.push(Entry {
+                video_id,
+                title: info.tags.remove("title").unwrap_or(title),
+                uploader: info.tags.remove("artist"),
+                duration: info.duration,
+                size,
+                path: path.to_string_lossy().into_owned(),
+                downloaded,
+            });
+        }
+        Ok(())
+    }
+
+    // Read a file's tags, warning once and carrying on without them if ffprobe isn't installed.
+    fn probe(&mut self, path: &Path) -> MediaInfo {
+        let empty = || MediaInfo {
+            tags: Default::default(),
+            duration: None,
+        };
+        if self.without_ffprobe {
+            return empty();
+        }
+        match probe::probe(path) {
+            Ok(info) => info,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                warn!("{}", tr!("ffprobe-missing"));
+                self.without_ffprobe = true;
+                empty()
+            }
+            Err(e) => {
+                warn!(
+                    "{}",
+                    tr!(
+                        "tags-unreadable",
+                        path = path.display().to_string(),
+                        error = e.to_string()
+                    )
+                );
+                empty()
+            }
+        }
+    }
+
+    pub fn write(&self, format: CatalogFormat, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            CatalogFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, &self.entries)?;
+                writeln!(out)
+            }
+            CatalogFormat::Csv => {
+                writeln!(out, "video_id,title,uploader,duration,size,path,downloaded")?;
+                for entry in &self.entries {
+                    let fields = [
+                        entry.video_id.clone(),
+                        entry.title.clone(),
+                        entry.uploader.clone().unwrap_or_default(),
+                        entry
+                            .duration
+                            .map_or(String::new(), |d| format!("{:.0}", d)),
+                        entry.size.to_string(),
+                        entry.path.clone(),
+                        entry.downloaded.clone().unwrap_or_default(),
+                    ];
+                    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                    writeln!(out, "{}", fields.join(","))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// The downloaded files of a location as their video ID, title and path inside the location, in
+// order of path: those yt-dlp named "<title> [<video ID>].<ext>", and those moved elsewhere inside
+// it by an organize template.
+pub fn location_files(
+    storage: &dyn Storage,
+    location: &str,
+    state: &State,
+) -> io::Result<Vec<(String, String, String)>> {
+    let mut files = Vec::new();
+    for file_name in storage.scan(Path::new(location))? {
+        if let Some((title, video_id)) = parse_file_name(&file_name) {
+            files.push((video_id.to_string(), title.to_string(), file_name));
+        }
+    }
+    for (video_id, organized) in &state.organized {
+        if organized.location == location {
+            let title = Path::new(&organized.path)
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            files.push((video_id.clone(), title, organized.path.clone()));
+        }
+    }
+    files.sort_by(|(_, _, a), (_, _, b)| a.cmp(b));
+    Ok(files)
+}
+
+// Split a file name yt-dlp gave a download into its title and video ID.
+fn parse_file_name(file_name: &str) -> Option<(&str, &str)> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    if SIDE_EXTENSIONS.contains(&extension) || extension.contains(' ') {
+        return None;
+    }
+    let (title, video_id) = stem.strip_suffix(']')?.rsplit_once(" [")?;
+    Some((title, video_id))
+}
+
+// Quote a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_csv() {
+        assert_eq!(
+            parse_file_name("One, Two [a].opus"),
+            Some(("One, Two", "a"))
+        );
+        assert_eq!(parse_file_name("One [a].opus.part"), None);
+        assert_eq!(parse_file_name("One [a].avi"), Some(("One", "a")));
+        assert_eq!(parse_file_name("One [a].webp"), None);
+        assert_eq!(civil_date(1_709_164_800), (2024, 2, 29));
+
+        let catalog = Catalog {
+            entries: vec![Entry {
+                video_id: "a".to_string(),
+                title: "One, \"Two\"".to_string(),
+                uploader: None,
+                duration: Some(61.4),
+                size: 1024,
+                path: "/music/mix/One, Two [a].opus".to_string(),
+                downloaded: Some("2024-02-29".to_string()),
+            }],
+            ..Catalog::default()
+        };
+        let mut out = Vec::new();
+        catalog.write(CatalogFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "video_id,title,uploader,duration,size,path,downloaded\n\
+             a,\"One, \"\"Two\"\"\",,61,1024,\"/music/mix/One, Two [a].opus\",2024-02-29\n"
+        );
+    }
+}
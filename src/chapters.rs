@@ -0,0 +1,83 @@
+use std::io;
+use std::path::Path;
+
+use crate::manifest::Chapter;
+use crate::storage::Storage;
+
+// What yt-dlp names the file of each chapter when splitting a video: the name of the full video,
+// followed by the number and title of the chapter.
+pub const TEMPLATE: &str =
+    "chapter:%(title)s [%(id)s] - %(section_number)03d %(section_title)s.%(ext)s";
+
+// The chapters yt-dlp split a downloaded file into, in order, found next to it by their names. A
+// video without chapters has none.
+pub fn find(storage: &dyn Storage, location: &str, file_name: &str) -> io::Result<Vec<Chapter>> {
+    let path = Path::new(file_name);
+    let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{} - ", stem.to_string_lossy());
+    let suffix = format!(".{}", extension.to_string_lossy());
+    let mut names: Vec<String> = storage
+        .scan(Path::new(location))?
+        .into_iter()
+        .filter(|name| {
+            name.len() > prefix.len() + suffix.len()
+                && name.starts_with(&prefix)
+                && name.ends_with(&suffix)
+        })
+        .collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let numbered = &name[prefix.len()..name.len() - suffix.len()];
+            let title = numbered
+                .split_once(' ')
+                .map_or(numbered, |(_, title)| title);
+            Chapter {
+                title: title.to_string(),
+                path: name.clone(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn finds_chapters_next_to_their_video() {
+        let storage = MemoryStorage::default();
+        for name in [
+            "Album [a].opus",
+            "Album [a] - 002 Second Song.opus",
+            "Album [a] - 001 First Song.opus",
+            "Album [a] - 001 First Song.opus.part",
+            "Album [b] - 001 Other.opus",
+        ] {
+            storage
+                .write(&Path::new("/music/albums").join(name), b"")
+                .unwrap();
+        }
+        let chapters = find(&storage, "/music/albums", "Album [a].opus").unwrap();
+        assert_eq!(
+            chapters,
+            [
+                Chapter {
+                    title: "First Song".to_string(),
+                    path: "Album [a] - 001 First Song.opus".to_string(),
+                },
+                Chapter {
+                    title: "Second Song".to_string(),
+                    path: "Album [a] - 002 Second Song.opus".to_string(),
+                },
+            ]
+        );
+        assert!(find(&storage, "/music/albums", "Single [c].opus")
+            .unwrap()
+            .is_empty());
+    }
+}
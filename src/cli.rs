@@ -0,0 +1,895 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{ArgAction, Parser, Subcommand};
+use log::{debug, LevelFilter};
+
+use crate::config::{
+    get_default_config_path, get_default_data_dir, get_default_location, load_config,
+    parse_duration, parse_size, write_default_config, write_example_config, Config, Format, Item,
+    Kind, Mtime, Order,
+};
+use crate::downloader::DEFAULT_RETRIES;
+use crate::fallback::{Fallback, FallbackApi};
+use crate::i18n::tr;
+use crate::manifest;
+use crate::observer::{ConsoleObserver, JsonObserver, Observer};
+use crate::playlist::parse_url_list;
+use crate::runner::YtDlp;
+use crate::state::State;
+use crate::storage::{LocalStorage, Storage};
+use crate::sync::{sync_all, SyncOptions};
+use crate::throttle::Throttle;
+use crate::{
+    blacklist, bundle, catalog, daemon, du, dupes, items, logging, m3u, preflight, setup, stats,
+    status, update, verify,
+};
+
+// Command line arguments for the program.
+#[derive(Parser, Debug)]
+#[command(
+    name = "yt-sync",
+    about = "Sync YouTube playlists to your local storage"
+)]
+struct Args {
+    /// The config file. Defaults to config.toml in the yt-sync directory of the platform's config
+    /// directory, such as ~/.config/yt-sync/config.toml on Linux.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// A directory of further TOML files, such as ~/.config/yt-sync/conf.d, each adding items.
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+    /// Log more detail: -v for debug, including what yt-dlp prints, and -vv for trace.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Only log errors, without progress bars.
+    #[arg(short, long, action, conflicts_with = "verbose")]
+    quiet: bool,
+    /// The yt-dlp program to run, overriding yt_dlp_path in the config.
+    #[arg(long = "yt-dlp")]
+    yt_dlp: Option<PathBuf>,
+    #[command(flatten)]
+    sync: SyncArgs,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+// The options of a sync, given before any command or after sync, daemon or status.
+#[derive(clap::Args, Debug, Clone, PartialEq)]
+struct SyncArgs {
+    /// A playlist ID, a channel's @handle or ID, a video ID or a URL.
+    #[arg(short, long)]
+    playlist_id: Option<String>,
+    /// What the playlist ID is, when it can't be told from its shape.
+    #[arg(long, value_enum)]
+    kind: Option<Kind>,
+    /// Sync the playlist or video URLs (or playlist IDs) in this file, one per line, instead of the
+    /// playlists in the config. "-" reads them from stdin.
+    #[arg(long, conflicts_with = "playlist_id")]
+    from_file: Option<PathBuf>,
+    /// Where to download the playlist given with --playlist-id. Defaults to the current directory.
+    #[arg(short, long)]
+    location: Option<String>,
+    /// Whether to keep only the audio of videos, or the video too.
+    #[arg(short, long, value_enum, default_value_t)]
+    format: Format,
+    /// The audio format extracted with --format audio, such as "mp3".
+    #[arg(long)]
+    audio_format: Option<String>,
+    /// The tallest video downloaded with --format video, such as "1080p".
+    #[arg(long)]
+    video_quality: Option<String>,
+    /// What video and audio are merged into with --format video, such as "mp4".
+    #[arg(long)]
+    container: Option<String>,
+    /// A yt-dlp download archive to record downloads in. Videos it lists are never downloaded.
+    #[arg(long)]
+    download_archive: Option<String>,
+    /// Save a playlist file next to the location, given as -s true.
+    #[arg(short, long, action = ArgAction::Set, default_value_t = false)]
+    save_playlist: bool,
+    /// Which missing videos to download first. Videos are ordered by upload date where yt-dlp lists
+    /// one, and otherwise by playlist position, taking later entries as newer.
+    #[arg(long, value_enum, default_value_t = Order::Playlist)]
+    order: Order,
+    /// Set the modification times of downloaded files to when they were downloaded or uploaded.
+    #[arg(long, value_enum, default_value_t = Mtime::Downloaded)]
+    mtime: Mtime,
+    /// Write a .yt-sync.json file next to each downloaded file, saying where it came from.
+    #[arg(long)]
+    sidecar: bool,
+    /// Split videos into a file for each of their chapters, listed in the m3u file in its place.
+    #[arg(long)]
+    split_chapters: bool,
+    /// Tag downloads with their playlist position as the track number and the playlist as album.
+    #[arg(long)]
+    track_numbers: bool,
+    /// Move downloaded files into directories inside the location by their tags, such as
+    /// "{artist}/{album}/{title}.{ext}".
+    #[arg(long)]
+    organize: Option<String>,
+    /// Keep symlinks to the downloaded files in by-uploader/ and by-year/ in this directory.
+    #[arg(long)]
+    views: Option<String>,
+    /// Only keep this many of the newest videos, removing the files of older ones.
+    #[arg(long)]
+    keep_last: Option<usize>,
+    /// Run this command after each video is downloaded, with the file in YT_SYNC_FILE.
+    #[arg(long)]
+    post_command: Option<String>,
+    /// Sign in to YouTube with the cookies in this cookies.txt file, for private playlists.
+    #[arg(long)]
+    cookies_file: Option<String>,
+    /// Sign in to YouTube with the cookies of this browser, such as "firefox".
+    #[arg(long)]
+    cookies_from_browser: Option<String>,
+    /// Remove the files of videos that are no longer in their playlist, from every playlist synced.
+    #[arg(long, action)]
+    prune: bool,
+    /// Move pruned files to this directory instead of deleting them.
+    #[arg(long)]
+    trash: Option<PathBuf>,
+    /// Only list the files that would be pruned, without removing or downloading anything.
+    #[arg(long, action)]
+    dry_run: bool,
+    /// Invidious or Piped instance to list playlists through when yt-dlp cannot.
+    #[arg(long)]
+    fallback_url: Option<String>,
+    /// Which API the instance given with --fallback-url speaks.
+    #[arg(long, value_enum, default_value_t = FallbackApi::Invidious)]
+    fallback_api: FallbackApi,
+    /// Download all new videos of a playlist with a single yt-dlp process.
+    #[arg(short, long, action)]
+    batch: bool,
+    /// How many playlists to list at the same time.
+    #[arg(long, default_value_t = 4)]
+    list_jobs: usize,
+    /// Stop starting new downloads after this long, such as "90m" or "2h".
+    #[arg(long, value_parser = parse_duration)]
+    max_runtime: Option<Duration>,
+    /// Stop downloading to a location once it has less free space than this, such as "5GB".
+    #[arg(long, value_parser = parse_size)]
+    min_free_space: Option<u64>,
+    /// Stop starting downloads once this run has downloaded this much, such as "10GB".
+    #[arg(long, value_parser = parse_size)]
+    max_bytes: Option<u64>,
+    /// Keep lines added to m3u files by hand, such as comments or tracks from elsewhere.
+    #[arg(long, action)]
+    preserve_unknown_lines: bool,
+    /// Which separator to write the paths in m3u files with, for players that only understand one.
+    #[arg(long, value_enum, default_value_t)]
+    playlist_separator: m3u::Separator,
+    /// Which character set to write m3u files in.
+    #[arg(long, value_enum, default_value_t)]
+    playlist_charset: m3u::Charset,
+    /// Start m3u files written in UTF-8 with a byte order mark.
+    #[arg(long, action)]
+    playlist_bom: bool,
+    /// End the lines of m3u files with CRLF, as on Windows.
+    #[arg(long, action)]
+    playlist_crlf: bool,
+    /// Which kind of playlist file to save: m3u, m3u8, xspf or pls.
+    #[arg(long, value_enum, default_value_t)]
+    playlist_format: m3u::PlaylistFormat,
+    /// List files in playlist files by their paths relative to the playlist file, so the music
+    /// directory can be moved or mounted elsewhere.
+    #[arg(long, action)]
+    playlist_relative: bool,
+    /// Write a JSON summary of the sync to standard output instead of progress, for scripts and
+    /// monitoring. Errors still go to standard error.
+    #[arg(long, action)]
+    json: bool,
+    /// Report how long each phase of syncing took for every playlist.
+    #[arg(long, action)]
+    timings: bool,
+    /// Post-process downloads (extracting audio, embedding thumbnails and metadata) on this many
+    /// threads of their own, so downloads carry on meanwhile. Off by default.
+    #[arg(long, default_value_t = 0)]
+    post_jobs: usize,
+    /// Download up to this many videos of a playlist at once. How many actually run adapts to how
+    /// fast downloads go, backing off when they fail or YouTube throttles them.
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+    /// How many times to try a download again after it failed for a reason that may go away.
+    #[arg(long, default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+    /// How long to wait before trying a download again, doubling with each retry, such as "10s".
+    #[arg(long, value_parser = parse_duration, default_value = "10s")]
+    retry_delay: Duration,
+    /// Wait at least this long between requests to YouTube, such as "5s", to avoid being throttled.
+    #[arg(long, value_parser = parse_duration)]
+    sleep_between: Option<Duration>,
+}
+
+impl SyncArgs {
+    // The options as they are when none are given.
+    fn unset() -> SyncArgs {
+        Args::parse_from(["yt-sync"]).sync
+    }
+}
+
+impl Args {
+    // The sync options given after a sync, daemon or status command, or else those given before
+    // it. Giving some in both places is a mistake, as one lot would be ignored.
+    fn sync_args(&self) -> Result<SyncArgs, String> {
+        let after = match &self.command {
+            Some(
+                Commands::Sync { sync, .. }
+                | Commands::Daemon { sync, .. }
+                | Commands::Status { sync },
+            ) => sync,
+            _ => return Ok(self.sync.clone()),
+        };
+        let unset = SyncArgs::unset();
+        match (self.sync != unset, *after != unset) {
+            (true, true) => Err(tr!("sync-options-twice")),
+            (true, false) => Ok(self.sync.clone()),
+            (false, _) => Ok(after.clone()),
+        }
+    }
+}
+
+// What to do, which is syncing when no command is given.
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Sync the playlists, or only the item with this name. Items are named by their ID unless
+    /// they have a name.
+    Sync {
+        name: Option<String>,
+        #[command(flatten)]
+        sync: SyncArgs,
+    },
+    /// Add a playlist to the config file.
+    Add {
+        playlist_id: String,
+        location: String,
+        /// What to call the item instead of its playlist ID.
+        #[arg(long)]
+        name: Option<String>,
+        /// Whether to keep only the audio of videos, or the video too.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+        /// Save an m3u file of the playlist next to the location.
+        #[arg(long, action)]
+        save_playlist: bool,
+    },
+    /// Remove the item with this name from the config file. Its downloaded files are kept.
+    Remove { name: String },
+    /// List the items in the config.
+    List,
+    /// Show which items have new videos that the next sync would download.
+    Status {
+        #[command(flatten)]
+        sync: SyncArgs,
+    },
+    /// Check the config for mistakes, such as settings with invalid values, without syncing.
+    CheckConfig,
+    /// Try downloading videos again that were skipped for being unavailable in several runs.
+    RetryUnavailable,
+    /// Keep running, syncing every so often and picking up changes to the config file as they are
+    /// saved.
+    Daemon {
+        /// How long to wait between syncs.
+        #[arg(long, value_parser = parse_duration, default_value = "6h")]
+        interval: Duration,
+        #[command(flatten)]
+        sync: SyncArgs,
+    },
+    /// Check the synced files for downloads that never finished.
+    Verify {
+        /// Also decode every file with ffmpeg to find truncated or corrupt ones. This is slow.
+        #[arg(long, action)]
+        deep: bool,
+    },
+    /// Print every downloaded file with its video ID, title, uploader, duration, size, path and
+    /// download date, for spreadsheets and other tools.
+    ExportCatalog {
+        #[arg(long, value_enum, default_value_t)]
+        format: catalog::CatalogFormat,
+    },
+    /// Show how much disk space each playlist and uploader takes up.
+    Du,
+    /// Show how many videos and chapters each location holds, how much space they take up and
+    /// when the last was downloaded, going by the manifests without going online.
+    Stats,
+    /// List the files in the locations of the playlists that yt-sync didn't create.
+    Orphans,
+    /// Report videos downloaded to several locations and files that are identical.
+    Dupes {
+        /// Replace each copy with a symlink to the first one found.
+        #[arg(long, action)]
+        link: bool,
+    },
+    /// Save the config and state, without any media, to a single file for moving a library to
+    /// another machine.
+    ExportState { path: PathBuf },
+    /// Restore the config and state saved by export-state.
+    ImportState {
+        path: PathBuf,
+        /// Replace the config and state if there already are some.
+        #[arg(long, action)]
+        force: bool,
+    },
+    /// Change the video IDs that are never downloaded, kept in the config file.
+    Blacklist {
+        #[command(subcommand)]
+        action: BlacklistAction,
+    },
+    /// Have yt-dlp update itself to its latest release.
+    UpdateYtdlp,
+    /// Update yt-sync to the latest release on GitHub.
+    SelfUpdate {
+        /// Only say whether there is a newer release.
+        #[arg(long, action)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BlacklistAction {
+    /// Never download these videos.
+    Add {
+        #[arg(required = true)]
+        video_ids: Vec<String>,
+    },
+    /// Download these videos again when they are in a synced playlist.
+    Remove {
+        #[arg(required = true)]
+        video_ids: Vec<String>,
+    },
+}
+
+// Forget which videos were found unavailable, so the next sync attempts them again.
+fn retry_unavailable(
+    mut state: State,
+    storage: &dyn Storage,
+    data_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let skipped = state
+        .unavailable
+        .keys()
+        .filter(|video_id| state.is_unavailable(video_id))
+        .count();
+    state.unavailable.clear();
+    state.save(storage, data_dir)?;
+    println!("{}", tr!("retrying-unavailable", count = skipped));
+    Ok(())
+}
+
+// Print each item with where it syncs to.
+fn list_items(playlists: &[Item]) {
+    for playlist in playlists {
+        let name = match &playlist.name {
+            Some(name) => format!("{} ({})", name, playlist.id),
+            None => playlist.id.clone(),
+        };
+        let mut locations = vec![playlist.location.as_str()];
+        locations.extend(playlist.locations.iter().map(String::as_str));
+        println!(
+            "{}",
+            tr!(
+                "item-syncs-to",
+                item = name,
+                format = playlist.format.name(),
+                locations = locations.join(" and ")
+            )
+        );
+    }
+}
+
+// Print whether each playlist is up to date, listing the videos of those that aren't with verbose.
+fn report_status(
+    playlists: &[Item],
+    list_jobs: usize,
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out_of_date = 0;
+    for (playlist, missing) in playlists
+        .iter()
+        .zip(status::missing(playlists, list_jobs, options))
+    {
+        match missing {
+            Ok(missing) if missing.is_empty() => {
+                println!("{}", tr!("status-up-to-date", playlist = playlist.name()))
+            }
+            Ok(missing) => {
+                out_of_date += 1;
+                println!(
+                    "{}",
+                    tr!(
+                        "status-new-videos",
+                        playlist = playlist.name(),
+                        count = missing.len()
+                    )
+                );
+                if options.verbose {
+                    for video_id in missing {
+                        println!("  {}", video_id);
+                    }
+                }
+            }
+            Err(e) => {
+                out_of_date += 1;
+                println!(
+                    "{}",
+                    tr!(
+                        "status-not-listed",
+                        playlist = playlist.name(),
+                        error = e.to_string()
+                    )
+                );
+            }
+        }
+    }
+    if options.cancel.is_cancelled() {
+        return Err(tr!("status-cancelled").into());
+    }
+    if out_of_date == 0 {
+        println!("{}", tr!("everything-up-to-date"));
+    }
+    Ok(())
+}
+
+// Verify the files of every playlist, listing any that look broken.
+fn verify_playlists(
+    playlists: &[Item],
+    deep: bool,
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut checked, mut broken) = (0, 0);
+    for playlist in playlists {
+        let location = Path::new(&playlist.location);
+        let managed = {
+            let state = options.state.lock().unwrap();
+            manifest::managed(&*options.storage, &playlist.location, &state)
+        };
+        let result = managed.and_then(|managed| {
+            let storage = &*options.storage;
+            verify::verify_location(storage, location, &managed, deep, &options.cancel)
+        });
+        let (location_checked, problems) = match result {
+            Ok(result) => result,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                let location = location.display().to_string();
+                return Err(
+                    tr!("verify-failed", location = location, error = e.to_string()).into(),
+                );
+            }
+        };
+        for problem in &problems {
+            println!("{}: {}", problem.path.display(), problem.reason);
+        }
+        checked += location_checked;
+        broken += problems.len();
+    }
+    if options.cancel.is_cancelled() {
+        return Err(tr!("verify-cancelled").into());
+    }
+    println!("{}", tr!("verified", checked = checked, broken = broken));
+    if broken > 0 {
+        return Err(tr!("files-failed-verification", count = broken).into());
+    }
+    Ok(())
+}
+
+// Print the catalog of the files downloaded to the locations of the playlists.
+fn export_catalog(
+    playlists: &[Item],
+    format: catalog::CatalogFormat,
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = options.state.lock().unwrap();
+    let mut catalog = catalog::Catalog::default();
+    for playlist in playlists {
+        match catalog.add_location(&*options.storage, &playlist.location, &state) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                let location = playlist.location.as_str();
+                return Err(tr!("read-failed", path = location, error = e.to_string()).into());
+            }
+        }
+    }
+    catalog.write(format, &mut io::stdout().lock())?;
+    Ok(())
+}
+
+// Print the disk space taken up by each playlist and uploader.
+fn report_usage(
+    playlists: &[Item],
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let playlists: Vec<(&str, &str)> = playlists
+        .iter()
+        .map(|playlist| (playlist.id.as_str(), playlist.location.as_str()))
+        .collect();
+    let state = options.state.lock().unwrap();
+    let (by_playlist, by_uploader) = du::usage(&*options.storage, &playlists, &state)
+        .map_err(|e| tr!("usage-failed", error = e.to_string()))?;
+    let mut out = io::stdout().lock();
+    du::write(&mut out, &tr!("usage-by-playlist"), &by_playlist)?;
+    writeln!(out)?;
+    du::write(&mut out, &tr!("usage-by-uploader"), &by_uploader)?;
+    Ok(())
+}
+
+// Print what the locations of the playlists hold, from their manifests.
+fn report_stats(
+    playlists: &[Item],
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let playlists: Vec<(&str, &str)> = playlists
+        .iter()
+        .map(|playlist| (playlist.id.as_str(), playlist.location.as_str()))
+        .collect();
+    let state = options.state.lock().unwrap();
+    let libraries = stats::library(&*options.storage, &playlists, &state)
+        .map_err(|e| tr!("manifests-failed", error = e.to_string()))?;
+    stats::write(&mut io::stdout().lock(), &libraries)?;
+    Ok(())
+}
+
+// Print the files in the locations of the playlists, mirrors included, that yt-sync didn't create.
+fn report_orphans(
+    playlists: &[Item],
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = options.state.lock().unwrap();
+    let mut seen = HashSet::new();
+    let locations = playlists
+        .iter()
+        .flat_map(|playlist| iter::once(&playlist.location).chain(&playlist.locations));
+    for location in locations.filter(|location| seen.insert(*location)) {
+        let orphans = match manifest::orphans(&*options.storage, location, &state) {
+            Ok(orphans) => orphans,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(tr!(
+                    "read-failed",
+                    path = location.as_str(),
+                    error = e.to_string()
+                )
+                .into())
+            }
+        };
+        for orphan in orphans {
+            println!("{}", Path::new(location).join(orphan).display());
+        }
+    }
+    Ok(())
+}
+
+// Print the files duplicated across the locations of the playlists, and with link replace the
+// copies with symlinks.
+fn report_duplicates(
+    playlists: &[Item],
+    link: bool,
+    options: &SyncOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let locations: Vec<&str> = playlists
+        .iter()
+        .map(|playlist| playlist.location.as_str())
+        .collect();
+    let state = options.state.lock().unwrap();
+    let groups = dupes::find(&*options.storage, &locations, &state)
+        .map_err(|e| tr!("duplicates-failed", error = e.to_string()))?;
+    if groups.is_empty() {
+        println!("{}", tr!("no-duplicates"));
+        return Ok(());
+    }
+    for group in &groups {
+        match &group.video_id {
+            Some(video_id) => println!(
+                "{}",
+                tr!(
+                    "duplicate-video",
+                    video = video_id.as_str(),
+                    count = group.paths.len()
+                )
+            ),
+            None => println!("{}", tr!("identical-files")),
+        }
+        for path in &group.paths {
+            println!("  {}", path.display());
+        }
+    }
+    if link {
+        let linked = dupes::link(&*options.storage, &groups)
+            .map_err(|e| tr!("linking-failed", error = e.to_string()))?;
+        println!("{}", tr!("linked-duplicates", count = linked));
+    }
+    Ok(())
+}
+
+// Describe an error that ended the program, in the user's language.
+pub fn describe_error(e: &dyn std::error::Error) -> String {
+    tr!("error", error = e.to_string())
+}
+
+// Parse the command line arguments and run the program.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let sync = args.sync_args()?;
+    let bars = logging::init(match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    });
+    if let Some(Commands::SelfUpdate { check }) = args.command {
+        return update::self_update(check);
+    }
+    let (storage, data_dir) = (Arc::new(LocalStorage), get_default_data_dir());
+    let state = State::load(&*storage, &data_dir).map_err(|e| {
+        let path = State::path(&data_dir).display().to_string();
+        tr!("unreadable", path = path, error = e.to_string())
+    })?;
+
+    if let Some(Commands::RetryUnavailable) = args.command {
+        return retry_unavailable(state, &*storage, &data_dir);
+    }
+
+    let config_path = match args.config {
+        Some(config_path) => config_path,
+        None => get_default_config_path()?,
+    };
+    match args.command {
+        Some(Commands::CheckConfig) => {
+            let config = load_config(&config_path, args.config_dir.as_deref())?;
+            println!("{}", tr!("config-valid", count = config.items.len()));
+            return Ok(());
+        }
+        Some(Commands::ExportState { path }) => {
+            return bundle::export(&path, &config_path, args.config_dir.as_deref(), state)
+        }
+        Some(Commands::Blacklist { action }) => {
+            return match action {
+                BlacklistAction::Add { video_ids } => {
+                    blacklist::edit(&config_path, &video_ids, true)
+                }
+                BlacklistAction::Remove { video_ids } => {
+                    blacklist::edit(&config_path, &video_ids, false)
+                }
+            }
+        }
+        Some(Commands::Add {
+            playlist_id,
+            location,
+            name,
+            format,
+            save_playlist,
+        }) => {
+            let new_item = items::NewItem {
+                id: &playlist_id,
+                name: name.as_deref(),
+                location: &location,
+                format,
+                save_playlist,
+            };
+            return items::add(&config_path, &new_item);
+        }
+        Some(Commands::Remove { name }) => return items::remove(&config_path, &name),
+        Some(Commands::UpdateYtdlp) => {
+            let configured = match config_path.exists() {
+                true => load_config(&config_path, args.config_dir.as_deref())?.yt_dlp_path,
+                false => None,
+            };
+            let yt_dlp = YtDlp::new(args.yt_dlp.or(configured.map(PathBuf::from)));
+            return preflight::update(&yt_dlp.program);
+        }
+        Some(Commands::ImportState { path, force }) => {
+            let config_dir = args.config_dir.as_deref();
+            return bundle::import(&path, &config_path, config_dir, &*storage, &data_dir, force);
+        }
+        _ => {}
+    }
+    let config = if config_path.exists() || args.config_dir.is_some() {
+        load_config(&config_path, args.config_dir.as_deref())?
+    } else if sync.playlist_id.is_some() || sync.from_file.is_some() {
+        // Playlists given on the command line don't need a config.
+        Config::default()
+    } else if io::stdin().is_terminal() {
+        let config = setup::run(&mut io::stdin().lock())?;
+        write_default_config(&config_path, &config)?;
+        config
+    } else {
+        // Without a terminal to ask on, write a config without playlists for them to be added to.
+        write_example_config(&config_path)?;
+        println!("{}", tr!("add-playlists"));
+        return Ok(());
+    };
+
+    let verbose = args.verbose > 0;
+    let (console, json) = (ConsoleObserver::new(bars), JsonObserver::new(sync.dry_run));
+    let observer: &dyn Observer = match sync.json {
+        true => &json,
+        false => &console,
+    };
+    let cli_fallback = sync.fallback_url.map(|url| Fallback {
+        api: sync.fallback_api,
+        url,
+    });
+    // Commands that run yt-dlp check it first, so a missing or outdated one is reported once
+    // rather than for every video.
+    let yt_dlp = YtDlp::new(
+        args.yt_dlp
+            .clone()
+            .or(config.yt_dlp_path.as_ref().map(PathBuf::from)),
+    );
+    if matches!(
+        args.command,
+        None | Some(Commands::Sync { .. } | Commands::Daemon { .. } | Commands::Status { .. })
+    ) {
+        let version = preflight::check(&yt_dlp.program)?;
+        let program = yt_dlp.program.display().to_string();
+        debug!("{}", tr!("using-yt-dlp", version = version, path = program));
+    }
+    let options = SyncOptions::new(&config, Arc::new(yt_dlp), storage, data_dir, state)?;
+    let mut options = SyncOptions {
+        fallback: cli_fallback.clone().or(options.fallback.clone()),
+        min_free_space: sync.min_free_space.or(options.min_free_space),
+        max_bytes: sync.max_bytes.or(options.max_bytes),
+        batch: sync.batch,
+        verbose,
+        deadline: sync
+            .max_runtime
+            .map(|max_runtime| Instant::now() + max_runtime),
+        preserve_unknown_lines: sync.preserve_unknown_lines,
+        playlist_separator: sync.playlist_separator,
+        playlist_encoding: m3u::Encoding {
+            charset: sync.playlist_charset,
+            bom: sync.playlist_bom,
+            crlf: sync.playlist_crlf,
+        },
+        playlist_format: sync.playlist_format,
+        playlist_relative: sync.playlist_relative,
+        timings: sync.timings,
+        post_jobs: sync.post_jobs,
+        jobs: sync.jobs,
+        retries: sync.retries,
+        retry_delay: sync.retry_delay,
+        throttle: Arc::new(Throttle::with_min_delay(
+            sync.sleep_between.unwrap_or_default(),
+        )),
+        prune: sync.prune,
+        trash: sync.trash,
+        dry_run: sync.dry_run,
+        ..options
+    };
+
+    // Stop cleanly on Ctrl-C, keeping the m3u file of the playlist being synced up to date.
+    let cancel = options.cancel.clone();
+    ctrlc::set_handler(move || cancel.cancel())?;
+
+    if let Some(Commands::Daemon { interval, .. }) = args.command {
+        if sync.playlist_id.is_some() || sync.from_file.is_some() {
+            return Err(tr!("daemon-with-playlists").into());
+        }
+        if options.dry_run || sync.json {
+            return Err(tr!("daemon-with-dry-run").into());
+        }
+        let daemon = daemon::Daemon {
+            config_path,
+            config_dir: args.config_dir,
+            cli_fallback,
+            cli_min_free_space: sync.min_free_space,
+            cli_max_bytes: sync.max_bytes,
+            cli_yt_dlp: args.yt_dlp,
+            list_jobs: sync.list_jobs,
+            max_runtime: sync.max_runtime,
+            interval,
+        };
+        return daemon.run(config, &mut options, observer);
+    }
+
+    let ids = match (sync.playlist_id, &sync.from_file) {
+        (Some(playlist_id), _) => vec![playlist_id],
+        (None, Some(path)) if path.as_os_str() == "-" => {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            parse_url_list(&content)
+        }
+        (None, Some(path)) => parse_url_list(&fs::read_to_string(path).map_err(|e| {
+            let path = path.display().to_string();
+            tr!("unreadable", path = path, error = e.to_string())
+        })?),
+        (None, None) => Vec::new(),
+    };
+    let mut playlists = match (ids.is_empty(), &sync.from_file) {
+        (true, Some(path)) => return Err(tr!("no-urls", path = path.display().to_string()).into()),
+        (true, None) => config.items,
+        (false, _) => {
+            let location = match sync.location {
+                Some(location) => location,
+                None => get_default_location()?,
+            };
+            ids.into_iter()
+                .map(|id| Item {
+                    id,
+                    kind: sync.kind,
+                    location: location.clone(),
+                    format: sync.format,
+                    audio_format: sync.audio_format.clone(),
+                    video_quality: sync.video_quality.clone(),
+                    container: sync.container.clone(),
+                    download_archive: sync.download_archive.clone(),
+                    save_playlist: sync.save_playlist,
+                    order: sync.order,
+                    organize: sync.organize.clone(),
+                    views: sync.views.clone(),
+                    keep_last: sync.keep_last,
+                    post_command: sync.post_command.clone(),
+                    mtime: sync.mtime,
+                    sidecar: sync.sidecar,
+                    split_chapters: sync.split_chapters,
+                    track_numbers: sync.track_numbers,
+                    cookies_file: sync.cookies_file.clone(),
+                    cookies_from_browser: sync.cookies_from_browser.clone(),
+                    ..Default::default()
+                })
+                .collect()
+        }
+    };
+
+    if let Some(Commands::Sync {
+        name: Some(name), ..
+    }) = &args.command
+    {
+        playlists.retain(|playlist| playlist.name() == name);
+        if playlists.is_empty() {
+            return Err(tr!("no-such-item", item = name.as_str()).into());
+        }
+    }
+
+    match args.command {
+        Some(Commands::List) => {
+            list_items(&playlists);
+            return Ok(());
+        }
+        Some(Commands::Status { .. }) => {
+            return report_status(&playlists, sync.list_jobs, &options)
+        }
+        Some(Commands::Verify { deep }) => return verify_playlists(&playlists, deep, &options),
+        Some(Commands::ExportCatalog { format }) => {
+            return export_catalog(&playlists, format, &options)
+        }
+        Some(Commands::Dupes { link }) => return report_duplicates(&playlists, link, &options),
+        Some(Commands::Du) => return report_usage(&playlists, &options),
+        Some(Commands::Stats) => return report_stats(&playlists, &options),
+        Some(Commands::Orphans) => return report_orphans(&playlists, &options),
+        _ => {}
+    }
+
+    let result = sync_all(&playlists, sync.list_jobs, &options, observer);
+    if sync.json {
+        println!("{}", json.summary(result.as_ref().err()));
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn takes_sync_options_before_or_after_the_command() {
+        Args::command().debug_assert();
+        for args in [
+            ["yt-sync", "sync", "--prune", "--dry-run"],
+            ["yt-sync", "--prune", "--dry-run", "sync"],
+            ["yt-sync", "status", "--prune", "--dry-run"],
+        ] {
+            let sync = Args::parse_from(args).sync_args().unwrap();
+            assert!(sync.prune && sync.dry_run);
+        }
+        let split = Args::parse_from(["yt-sync", "--prune", "sync", "--dry-run"]);
+        assert!(split.sync_args().is_err());
+    }
+}
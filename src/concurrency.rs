@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use crate::failure::FailureKind;
+
+// Decides how many downloads run at once, between one and a maximum. It starts at one, and after
+// each round of successful downloads adds another if that made downloads go faster than the
+// round before, or takes one away if it made them slower. Failures that suggest YouTube or the
+// network is struggling halve it, and being throttled drops it back to one.
+pub struct Concurrency {
+    max: usize,
+    limit: usize,
+    round_start: Instant,
+    round_done: usize,
+    // Downloads finished per second in the last complete round.
+    last_rate: Option<f64>,
+}
+
+impl Concurrency {
+    pub fn new(max: usize) -> Self {
+        Concurrency {
+            max: max.max(1),
+            limit: 1,
+            round_start: Instant::now(),
+            round_done: 0,
+            last_rate: None,
+        }
+    }
+
+    // How many downloads may run at once right now.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // Adjust to how a download went.
+    pub fn finished(&mut self, result: Result<(), FailureKind>) {
+        self.finished_at(result, Instant::now());
+    }
+
+    fn finished_at(&mut self, result: Result<(), FailureKind>, now: Instant) {
+        match result {
+            Ok(()) => {
+                self.round_done += 1;
+                // A round lasts long enough for every download running at this limit to finish
+                // about twice, to even out how long individual videos take.
+                if self.round_done < self.limit * 2 {
+                    return;
+                }
+                let elapsed = now
+                    .duration_since(self.round_start)
+                    .max(Duration::from_millis(1));
+                let rate = self.round_done as f64 / elapsed.as_secs_f64();
+                match self.last_rate {
+                    Some(last_rate) if rate < last_rate * 0.9 => {
+                        self.limit = (self.limit - 1).max(1)
+                    }
+                    Some(last_rate) if rate < last_rate * 1.1 => {}
+                    _ => self.limit = (self.limit + 1).min(self.max),
+                }
+                self.last_rate = Some(rate);
+            }
+            Err(FailureKind::Throttled) => {
+                self.limit = 1;
+                self.last_rate = None;
+            }
+            Err(kind) if kind.is_transient() => {
+                self.limit = (self.limit / 2).max(1);
+                self.last_rate = None;
+            }
+            // Videos that can't be downloaded at all say nothing about how busy things are.
+            Err(_) => return,
+        }
+        self.round_start = now;
+        self.round_done = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_with_throughput_and_backs_off_on_errors() {
+        let mut concurrency = Concurrency::new(3);
+        let start = concurrency.round_start;
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Two downloads in 10s at one at a time, then four in 10s at two at a time.
+        concurrency.finished_at(Ok(()), at(5));
+        concurrency.finished_at(Ok(()), at(10));
+        assert_eq!(concurrency.limit(), 2);
+        for secs in [13, 15, 18, 20] {
+            concurrency.finished_at(Ok(()), at(secs));
+        }
+        assert_eq!(concurrency.limit(), 3);
+        // Three at a time made downloads slower, so it goes back to two.
+        for secs in 21..=26 {
+            concurrency.finished_at(Ok(()), at(secs * 2));
+        }
+        assert_eq!(concurrency.limit(), 2);
+
+        concurrency.finished_at(Err(FailureKind::Unavailable), at(60));
+        assert_eq!(concurrency.limit(), 2);
+        concurrency.finished_at(Err(FailureKind::Network), at(61));
+        assert_eq!(concurrency.limit(), 1);
+        concurrency.finished_at(Err(FailureKind::Throttled), at(62));
+        assert_eq!(concurrency.limit(), 1);
+    }
+}
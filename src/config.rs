@@ -0,0 +1,628 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::info;
+use serde::de::{self, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::fallback::Fallback;
+use crate::hooks::Hooks;
+use crate::i18n::tr;
+use crate::validate;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback>,
+    // Say when there is a newer release of yt-sync, checking at most once a day. Off by default.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub check_for_updates: bool,
+    // Video IDs that are never downloaded, whichever playlist they are in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blacklist: Vec<String>,
+    // Stop downloading to a location once it has less free space than this, such as "5GB".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_free_space: Option<String>,
+    // Stop starting downloads once a run has downloaded this much, such as "10GB".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes_per_run: Option<String>,
+    // A cookies.txt file yt-dlp signs in to YouTube with, for private and members-only playlists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookies_file: Option<String>,
+    // A browser yt-dlp reads the cookies it signs in with from, such as "firefox".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookies_from_browser: Option<String>,
+    // The yt-dlp program to run, when it isn't "yt-dlp" on the PATH.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yt_dlp_path: Option<String>,
+    // Commands run after each download and after each sync.
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Item {
+    // A playlist ID, a channel's @handle or ID, a video ID or a URL.
+    pub id: String,
+    // What the id is, which is worked out from its shape when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Kind>,
+    // What the item is called by the sync and remove commands. Defaults to its ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Without it, the first of locations is where the playlist is downloaded to.
+    #[serde(default)]
+    pub location: String,
+    // Further locations kept identical to the first, each with its own m3u file, such as a USB
+    // drive. Videos are only downloaded once and copied over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<String>,
+    pub format: Format,
+    // The audio format extracted with format = "audio", such as "mp3". Defaults to "opus".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_format: Option<String>,
+    // The tallest video downloaded with format = "video", such as "1080p". Defaults to the best.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_quality: Option<String>,
+    // What video and audio are merged into with format = "video", such as "mp4". Defaults to "mkv".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    // Further arguments given to yt-dlp, such as ["--sponsorblock-remove", "all"].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub yt_dlp_args: Vec<String>,
+    // A yt-dlp download archive that downloads are recorded in, and whose videos are never
+    // downloaded again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_archive: Option<String>,
+    // Save a playlist file listing the videos next to the location.
+    #[serde(deserialize_with = "flag")]
+    pub save_playlist: bool,
+    #[serde(default)]
+    pub order: Order,
+    // Where downloaded files are moved inside the location, such as
+    // "{artist}/{album}/{title}.{ext}".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organize: Option<String>,
+    // A directory to keep symlinks to the downloaded files in, grouped by uploader and by year.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub views: Option<String>,
+    // Video IDs that are never downloaded from this playlist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blacklist: Vec<String>,
+    // Video IDs whose files are kept, and stay in the m3u file, after they leave the playlist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned: Vec<String>,
+    // Only keep this many of the newest videos, removing the files of older ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+    // A command run after each video of the playlist is downloaded, such as to tag it, with the
+    // file in YT_SYNC_FILE.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_command: Option<String>,
+    // Overrides the min_free_space of the config for this playlist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_free_space: Option<String>,
+    // The permission bits given to downloaded files, in octal such as "664".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chmod: Option<String>,
+    // The user and group given to downloaded files, such as "jellyfin:media" or ":media".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chown: Option<String>,
+    #[serde(default)]
+    pub mtime: Mtime,
+    // Write a .yt-sync.json file next to each downloaded file, saying where it came from.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sidecar: bool,
+    // Have yt-dlp split videos into a file for each of their chapters, which the m3u file lists in
+    // place of the full video, such as for albums uploaded as one video.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub split_chapters: bool,
+    // Tag downloads with their position in the playlist as the track number, and the name of the
+    // item as the album, so music players sort them in playlist order.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub track_numbers: bool,
+    // yt-dlp format selections to try in turn when a video isn't available in the usual format,
+    // or converting it fails. Defaults to "best".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_fallbacks: Option<Vec<String>>,
+    // Remove the files of videos that are no longer in the playlist.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub prune: bool,
+    // A directory to move pruned files to instead of deleting them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash: Option<String>,
+    // Override the cookies of the config for this playlist. Setting either replaces both.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookies_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookies_from_browser: Option<String>,
+}
+
+impl Item {
+    // The name of the item, or its ID when it has none.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind.unwrap_or_else(|| Kind::of(&self.id))
+    }
+}
+
+// What an item syncs.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Kind {
+    Playlist,
+    /// The uploads of a channel, given by its @handle or its ID.
+    Channel,
+    Video,
+    /// Anything yt-dlp can list, given by its URL.
+    Url,
+}
+
+impl Kind {
+    // Work out what an ID is from its shape: URLs have a scheme, handles start with @, channel
+    // IDs are "UC" and 22 more characters, and video IDs are 11 characters. Anything else is taken
+    // as a playlist ID.
+    pub fn of(id: &str) -> Kind {
+        let id_characters = |id: &str| {
+            id.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if id.contains("://") {
+            Kind::Url
+        } else if id.starts_with('@')
+            || (id.len() == 24 && id.starts_with("UC") && id_characters(id))
+        {
+            Kind::Channel
+        } else if id.len() == 11 && id_characters(id) {
+            Kind::Video
+        } else {
+            Kind::Playlist
+        }
+    }
+}
+
+// What the modification times of downloaded files are set to.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Mtime {
+    /// When the file was downloaded, so files sort by when they were added.
+    #[default]
+    Downloaded,
+    /// When the video was uploaded, so files sort by when they were published.
+    Uploaded,
+}
+
+// Whether only the audio of videos is kept, or the video too.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Audio,
+    Video,
+}
+
+impl Format {
+    // What the manifest records downloads in this format under.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Audio => "audio",
+            Format::Video => "video",
+        }
+    }
+}
+
+// Read a flag, which configs written by older versions give as the string "true" or "false".
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        String(String),
+    }
+    match Flag::deserialize(deserializer)? {
+        Flag::Bool(flag) => Ok(flag),
+        Flag::String(flag) => match flag.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(de::Error::invalid_value(
+                Unexpected::Str(&flag),
+                &"true or false",
+            )),
+        },
+    }
+}
+
+// Which missing videos of a playlist are downloaded first.
+#[derive(ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Order {
+    NewestFirst,
+    OldestFirst,
+    /// As they come in the playlist, starting while it is still being listed.
+    #[default]
+    Playlist,
+}
+
+// Parse a size such as "500MB", "5GB" or "1.5GiB". A bare number is bytes.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("{:?} is not a size", value))?;
+    let unit_bytes: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000_u64.pow(2),
+        "gb" => 1000_u64.pow(3),
+        "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(tr!("unknown-unit", unit = unit, value = value)),
+    };
+    Ok((number * unit_bytes as f64) as u64)
+}
+
+// Parse a size setting of the config, such as min_free_space.
+pub fn parse_size_setting(name: &str, value: &Option<String>) -> Result<Option<u64>, String> {
+    value
+        .as_deref()
+        .map(parse_size)
+        .transpose()
+        .map_err(|e| tr!("invalid-setting", name = name, error = e))
+}
+
+// Format a number of bytes for people, such as "4.2 GB".
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < units.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+// Parse a duration such as "90s", "45m", "2h" or "1h30m". A bare number is seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let mut total_secs = 0u64;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit_secs = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(tr!("unknown-unit", unit = c.to_string(), value = value)),
+        };
+        let amount: u64 = number
+            .parse()
+            .map_err(|_| tr!("expected-number", unit = c.to_string(), value = value))?;
+        total_secs += amount * unit_secs;
+        number.clear();
+    }
+    if !number.is_empty() {
+        total_secs += number
+            .parse::<u64>()
+            .map_err(|e| format!("{} in {:?}", e, value))?;
+    } else if total_secs == 0 {
+        return Err(format!("{:?} is not a duration", value));
+    }
+    Ok(Duration::from_secs(total_secs))
+}
+
+// Get the default configuration path for the program, in the config directory of the platform:
+// ~/.config on Linux, ~/Library/Application Support on macOS and %APPDATA% on Windows. A config
+// left at ~/.config/yt-sync by earlier versions is still used on macOS and Windows.
+pub fn get_default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let config_dir = dirs::config_dir().ok_or_else(|| tr!("no-config-dir"))?;
+    let config_path = config_dir.join("yt-sync").join("config.toml");
+    if !config_path.exists() {
+        if let Some(home_dir) = dirs::home_dir() {
+            let legacy_path = home_dir.join(".config").join("yt-sync").join("config.toml");
+            if legacy_path.exists() {
+                return Ok(legacy_path);
+            }
+        }
+    }
+    Ok(config_path)
+}
+
+// Get the current directory as the default location of a playlist given on the command line.
+pub fn get_default_location() -> Result<String, Box<dyn std::error::Error>> {
+    let current_dir =
+        env::current_dir().map_err(|e| tr!("no-current-dir", error = e.to_string()))?;
+    current_dir.into_os_string().into_string().map_err(|dir| {
+        tr!(
+            "current-dir-not-utf8",
+            path = dir.to_string_lossy().into_owned()
+        )
+        .into()
+    })
+}
+
+// An example configuration, for tests.
+#[cfg(test)]
+pub fn create_default_config() -> Config {
+    Config {
+        items: vec![
+            Item {
+                id: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+                location: "/home/user/Downloads/file_output".to_string(),
+                save_playlist: true,
+                ..Default::default()
+            },
+            Item {
+                id: "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb".to_string(),
+                location: "/home/user/Downloads/file_output2".to_string(),
+                format: Format::Video,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+// Write the default configuration to a file.
+pub fn write_default_config(
+    path: &Path,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_config_file(path, &toml::to_string(config)?)
+}
+
+// The config written the first time yt-sync runs without a terminal to ask for a playlist on. It
+// has no items, so nothing is synced until some are added.
+const EXAMPLE_CONFIG: &str = r#"# Add an [[items]] section like this one for each playlist to sync, or run
+# yt-sync add <playlist-id> <location>. The README lists every setting.
+#
+# [[items]]
+# id = "PLxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+# location = "/home/user/Music/playlist"
+# format = "audio"
+# save_playlist = false
+"#;
+
+// Write a config with no items, explaining how to add them.
+pub fn write_example_config(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    write_config_file(path, EXAMPLE_CONFIG)
+}
+
+fn write_config_file(path: &Path, toml_string: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            let path = parent.display().to_string();
+            tr!("config-dir-uncreatable", path = path, error = e.to_string())
+        })?;
+    }
+    File::create(path)
+        .and_then(|file| BufWriter::new(file).write_all(toml_string.as_bytes()))
+        .map_err(|e| {
+            let path = path.display().to_string();
+            tr!("config-unwritable", path = path, error = e.to_string())
+        })?;
+    println!(
+        "{}",
+        tr!("created-config", path = path.display().to_string())
+    );
+    Ok(())
+}
+
+// Read a configuration from a file.
+fn read_config(path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|file| BufReader::new(file).read_to_string(&mut content))
+        .map_err(|e| {
+            let path = path.display().to_string();
+            tr!("config-unreadable", path = path, error = e.to_string())
+        })?;
+    // Logged to stderr, so it stays out of output meant for other programs, like export-catalog.
+    info!(
+        "{}",
+        tr!("loaded-config", path = path.display().to_string())
+    );
+    let mut config: Config = toml::from_str(&content).map_err(|e| {
+        let path = path.display().to_string();
+        tr!("config-unparsable", path = path, error = e.to_string())
+    })?;
+    let problems = validate::check(&config, &content);
+    if !problems.is_empty() {
+        let path = path.display().to_string();
+        let problems = format!("  {}", problems.join("\n  "));
+        return Err(tr!("config-invalid", path = path, problems = problems).into());
+    }
+    for item in &mut config.items {
+        if item.location.is_empty() {
+            item.location = item.locations.remove(0);
+        }
+    }
+    Ok(config)
+}
+
+// Read the config along with the TOML files in the config directory, if there is one, whose items
+// are added after its own in the order of their file names. Without a config file, the config
+// directory alone is used.
+pub fn load_config(
+    config_path: &Path,
+    config_dir: Option<&Path>,
+) -> Result<Config, Box<dyn std::error::Error>> {
+    let Some(config_dir) = config_dir else {
+        return read_config(config_path);
+    };
+    let mut config = match config_path.exists() {
+        true => read_config(config_path)?,
+        false => Config::default(),
+    };
+    for path in config_dir_files(config_dir)? {
+        let part = read_config(&path)?;
+        let settings = main_only_settings(&part);
+        if !settings.is_empty() {
+            let (path, settings) = (path.display().to_string(), settings.join(", "));
+            return Err(tr!("main-only-settings", path = path, settings = settings).into());
+        }
+        config.blacklist.extend(part.blacklist);
+        config.items.extend(part.items);
+    }
+    Ok(config)
+}
+
+// The settings a config sets that apply to the whole run rather than to its items, which files in
+// the config directory can't set.
+fn main_only_settings(config: &Config) -> Vec<&'static str> {
+    [
+        ("fallback", config.fallback.is_some()),
+        ("check_for_updates", config.check_for_updates),
+        ("hooks", !config.hooks.is_empty()),
+        ("min_free_space", config.min_free_space.is_some()),
+        ("max_bytes_per_run", config.max_bytes_per_run.is_some()),
+        ("cookies_file", config.cookies_file.is_some()),
+        (
+            "cookies_from_browser",
+            config.cookies_from_browser.is_some(),
+        ),
+        ("yt_dlp_path", config.yt_dlp_path.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(setting, _)| setting)
+    .collect()
+}
+
+// List the TOML files in the config directory, in order of file name.
+pub fn config_dir_files(config_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(config_dir).map_err(|e| {
+        let path = config_dir.display().to_string();
+        tr!("config-dir-unreadable", path = path, error = e.to_string())
+    })? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Get the directory yt-sync keeps its own data in.
+pub fn get_default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("yt-sync")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("5GB"), Ok(5_000_000_000));
+        assert_eq!(parse_size("1.5 GiB"), Ok(1_610_612_736));
+        assert!(parse_size("5 gigs").is_err());
+        assert!(parse_size("GB").is_err());
+        assert_eq!(format_size(4_200_000_000), "4.2 GB");
+    }
+
+    #[test]
+    fn parses_typed_fields() {
+        let item = |fields: &str| {
+            toml::from_str::<Config>(&format!("[[items]]\nid = \"PL\"\n{}", fields))
+                .map(|mut config| config.items.remove(0))
+        };
+        let parsed = item("format = \"video\"\nsave_playlist = true\n").unwrap();
+        assert_eq!((parsed.format, parsed.save_playlist), (Format::Video, true));
+        // Older versions wrote save_playlist as a string.
+        assert!(
+            !item("format = \"audio\"\nsave_playlist = \"false\"\n")
+                .unwrap()
+                .save_playlist
+        );
+
+        let error = item("format = \"audo\"\nsave_playlist = true\n").unwrap_err();
+        assert!(error.to_string().contains("line 3"), "{}", error);
+        assert!(item("format = \"audio\"\nsave_playlist = \"yes\"\n").is_err());
+        assert!(item("format = \"audio\"\nsave_playlist = true\nsave_playlst = true\n").is_err());
+    }
+
+    #[test]
+    fn example_config_has_no_items() {
+        let config: Config = toml::from_str(EXAMPLE_CONFIG).unwrap();
+        assert!(config.items.is_empty());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+
+    #[test]
+    fn only_the_main_config_sets_settings_for_the_whole_run() {
+        let dir = env::temp_dir().join(format!("yt-sync-conf.d-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let item = r#"
+[[items]]
+id = "PL"
+location = "/music"
+format = "audio"
+save_playlist = false
+"#;
+        fs::write(dir.join("a.toml"), item).unwrap();
+        let main = dir.join("config.toml");
+        let config = load_config(&main, Some(&dir)).unwrap();
+        assert_eq!(config.items.len(), 1);
+
+        let limited = format!("min_free_space = \"5GB\"\n{}", item);
+        fs::write(dir.join("b.toml"), limited).unwrap();
+        let error = load_config(&main, Some(&dir)).unwrap_err().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(error.ends_with("b.toml sets min_free_space, which only the main config can"));
+
+        let part = Config {
+            cookies_from_browser: Some("firefox".to_string()),
+            yt_dlp_path: Some("/opt/yt-dlp".to_string()),
+            ..config
+        };
+        assert_eq!(
+            main_only_settings(&part),
+            ["cookies_from_browser", "yt_dlp_path"]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::config::{Config, Item};
+use crate::i18n::tr;
+
+// The browsers yt-dlp can read cookies from.
+const BROWSERS: [&str; 9] = [
+    "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi", "whale",
+];
+
+// Where yt-dlp gets the cookies it signs in to YouTube with, for private, liked and members-only
+// playlists, from cookies_file and cookies_from_browser.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cookies {
+    // A cookies.txt file in the Netscape format.
+    pub file: Option<String>,
+    // A browser to read them from, as yt-dlp takes it, such as "firefox" or "chrome:Profile 1".
+    pub from_browser: Option<String>,
+}
+
+impl Cookies {
+    // The cookies of the config, used by playlists that don't set their own.
+    pub fn of(config: &Config) -> Cookies {
+        Cookies {
+            file: config.cookies_file.clone(),
+            from_browser: config.cookies_from_browser.clone(),
+        }
+    }
+
+    // The cookies of an item, which replace these when it sets either.
+    pub fn for_item(&self, item: &Item) -> Cookies {
+        let own = Cookies {
+            file: item.cookies_file.clone(),
+            from_browser: item.cookies_from_browser.clone(),
+        };
+        match own.is_empty() {
+            true => self.clone(),
+            false => own,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Cookies::default()
+    }
+
+    // The arguments giving yt-dlp these cookies.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(file) = &self.file {
+            args.extend(["--cookies".to_string(), file.clone()]);
+        }
+        if let Some(browser) = &self.from_browser {
+            args.extend(["--cookies-from-browser".to_string(), browser.clone()]);
+        }
+        args
+    }
+
+    // What to do when yt-dlp needed a signed-in account, which depends on whether it had cookies.
+    pub fn advice(&self) -> String {
+        match self.is_empty() {
+            true => tr!("cookies-missing"),
+            false => tr!("cookies-rejected"),
+        }
+    }
+}
+
+// Check a cookies_from_browser setting names a browser yt-dlp can read cookies from. It may be
+// followed by a keyring, profile and container, as in "chrome+gnomekeyring:Profile 1".
+pub fn check_browser(from_browser: &str) -> Result<(), String> {
+    let browser = from_browser
+        .split(['+', ':'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match BROWSERS.contains(&browser.as_str()) {
+        true => Ok(()),
+        false => Err(tr!(
+            "browser-unknown",
+            value = from_browser,
+            choices = BROWSERS.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::create_default_config;
+
+    #[test]
+    fn items_override_the_cookies_of_the_config() {
+        let mut config = create_default_config();
+        config.cookies_file = Some("/home/user/cookies.txt".to_string());
+        let cookies = Cookies::of(&config);
+        let mut item = config.items.remove(0);
+        assert_eq!(
+            cookies.for_item(&item).args(),
+            ["--cookies", "/home/user/cookies.txt"]
+        );
+
+        item.cookies_from_browser = Some("firefox".to_string());
+        assert_eq!(
+            cookies.for_item(&item).args(),
+            ["--cookies-from-browser", "firefox"]
+        );
+        assert!(Cookies::default().args().is_empty());
+
+        assert!(check_browser("chrome+gnomekeyring:Profile 1").is_ok());
+        assert!(check_browser("Firefox").is_ok());
+        assert!(check_browser("netscape").is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use log::info;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::cancel::CancelToken;
+use crate::config::{load_config, parse_size_setting, Config, Item};
+use crate::cookies::Cookies;
+use crate::fallback::Fallback;
+use crate::i18n::tr;
+use crate::observer::Observer;
+use crate::preflight;
+use crate::runner::YtDlp;
+use crate::sync::{sync_all, SyncOptions};
+
+// Runs syncs on an interval until cancelled, reloading the config whenever it is saved.
+pub struct Daemon {
+    pub config_path: PathBuf,
+    pub config_dir: Option<PathBuf>,
+    // A fallback given on the command line, which takes precedence over the config's.
+    pub cli_fallback: Option<Fallback>,
+    // Likewise for the minimum free space and the download budget, in bytes.
+    pub cli_min_free_space: Option<u64>,
+    pub cli_max_bytes: Option<u64>,
+    // And for the yt-dlp program.
+    pub cli_yt_dlp: Option<PathBuf>,
+    pub list_jobs: usize,
+    pub max_runtime: Option<Duration>,
+    pub interval: Duration,
+}
+
+impl Daemon {
+    pub fn run(
+        &self,
+        mut config: Config,
+        options: &mut SyncOptions,
+        observer: &dyn Observer,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let watcher = ConfigWatcher::new(&self.config_path, self.config_dir.as_deref())?;
+        loop {
+            options.deadline = self
+                .max_runtime
+                .map(|max_runtime| Instant::now() + max_runtime);
+            options.downloaded_bytes.store(0, Ordering::Relaxed);
+            if let Err(e) = sync_all(&config.items, self.list_jobs, options, observer) {
+                if options.cancel.is_cancelled() {
+                    break;
+                }
+                observer.on_error(&tr!("sync-failed", error = e.to_string()));
+            }
+
+            // Sleep until the next sync, or until the config changes, which starts one straight
+            // away so new playlists don't wait for the interval.
+            let next_sync = Instant::now() + self.interval;
+            while watcher.wait_for_change(next_sync, &options.cancel) {
+                let loaded = load_config(&self.config_path, self.config_dir.as_deref()).and_then(
+                    |new_config| {
+                        let min_free_space =
+                            parse_size_setting("min_free_space", &new_config.min_free_space)?;
+                        let max_bytes =
+                            parse_size_setting("max_bytes_per_run", &new_config.max_bytes_per_run)?;
+                        Ok((new_config, min_free_space, max_bytes))
+                    },
+                );
+                let (new_config, min_free_space, max_bytes) = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        observer.on_error(&tr!("config-change-invalid", error = e.to_string()));
+                        continue;
+                    }
+                };
+                if new_config == config {
+                    continue;
+                }
+                for change in describe_changes(&config, &new_config) {
+                    info!("{}", tr!("config-changed", change = change));
+                }
+                let old_yt_dlp_path = config.yt_dlp_path.clone();
+                config = new_config;
+                options.fallback = self.cli_fallback.clone().or(config.fallback.clone());
+                options.check_for_updates = config.check_for_updates;
+                options.blacklist = config.blacklist.iter().cloned().collect();
+                options.hooks = config.hooks.clone();
+                if self.cli_yt_dlp.is_none() && old_yt_dlp_path != config.yt_dlp_path {
+                    let yt_dlp = YtDlp::new(config.yt_dlp_path.as_ref().map(PathBuf::from));
+                    if let Err(e) = preflight::check(&yt_dlp.program) {
+                        observer.on_error(&e);
+                    }
+                    options.runner = Arc::new(yt_dlp);
+                }
+                options.cookies = Cookies::of(&config);
+                options.min_free_space = self.cli_min_free_space.or(min_free_space);
+                options.max_bytes = self.cli_max_bytes.or(max_bytes);
+                break;
+            }
+            if options.cancel.is_cancelled() {
+                break;
+            }
+        }
+        info!("{}", tr!("daemon-stopped"));
+        Ok(())
+    }
+}
+
+// Watches the config file and config directory for changes. Editors often save by replacing the
+// file instead of writing to it, so the directory it is in is watched rather than the file itself.
+struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    file_name: PathBuf,
+    config_dir: Option<PathBuf>,
+}
+
+impl ConfigWatcher {
+    fn new(config_path: &Path, config_dir: Option<&Path>) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let dir = match config_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        if let Some(config_dir) = config_dir {
+            watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
+        }
+        Ok(ConfigWatcher {
+            _watcher: watcher,
+            events,
+            file_name: config_path.file_name().unwrap_or_default().into(),
+            config_dir: config_dir.map(Path::to_path_buf),
+        })
+    }
+
+    // Wait until the config file changes, returning false if the deadline passes or the daemon is
+    // cancelled first. A save often comes as several events, so they are gathered up for a moment.
+    fn wait_for_change(&self, deadline: Instant, cancel: &CancelToken) -> bool {
+        while !cancel.is_cancelled() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            match self
+                .events
+                .recv_timeout(remaining.min(Duration::from_millis(200)))
+            {
+                Ok(Ok(event)) if self.is_config_change(&event) => {
+                    cancel.sleep(Duration::from_millis(300));
+                    while self.events.try_recv().is_ok() {}
+                    return !cancel.is_cancelled();
+                }
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+        false
+    }
+
+    fn is_config_change(&self, event: &Event) -> bool {
+        matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && event.paths.iter().any(|path| {
+            path.file_name() == Some(self.file_name.as_os_str())
+                || self.config_dir.as_ref().is_some_and(|config_dir| {
+                    path.starts_with(config_dir)
+                        && path
+                            .extension()
+                            .is_some_and(|extension| extension == "toml")
+                })
+        })
+    }
+}
+
+// Describe how the playlists and settings differ between two configs, one line per change. Changes
+// to settings without a line of their own are described as changed settings, so there is always
+// a line when the configs differ.
+fn describe_changes(old: &Config, new: &Config) -> Vec<String> {
+    let by_id = |config: &Config| {
+        let mut items: BTreeMap<String, Vec<Item>> = BTreeMap::new();
+        for item in &config.items {
+            items.entry(item.id.clone()).or_default().push(item.clone());
+        }
+        items
+    };
+    let (old_items, new_items) = (by_id(old), by_id(new));
+
+    let mut changes = Vec::new();
+    for (id, items) in &old_items {
+        if !new_items.contains_key(id) {
+            for item in items {
+                changes.push(tr!(
+                    "change-removed-playlist",
+                    playlist = id.as_str(),
+                    location = item.location.as_str()
+                ));
+            }
+        }
+    }
+    for (id, items) in &new_items {
+        match old_items.get(id) {
+            None => {
+                for item in items {
+                    changes.push(tr!(
+                        "change-added-playlist",
+                        playlist = id.as_str(),
+                        location = item.location.as_str()
+                    ));
+                }
+            }
+            Some(old_items) if old_items == items => {}
+            Some(old_items) if old_items.len() == 1 && items.len() == 1 => {
+                let (old_item, item) = (&old_items[0], &items[0]);
+                let described = changes.len();
+                let (old_format, new_format) = (
+                    old_item.format.name().to_string(),
+                    item.format.name().to_string(),
+                );
+                let (old_save_playlist, new_save_playlist) = (
+                    old_item.save_playlist.to_string(),
+                    item.save_playlist.to_string(),
+                );
+                let fields = [
+                    ("location", &old_item.location, &item.location),
+                    ("format", &old_format, &new_format),
+                    ("save_playlist", &old_save_playlist, &new_save_playlist),
+                ];
+                let order = |item: &Item| {
+                    item.order
+                        .to_possible_value()
+                        .map_or(String::new(), |value| value.get_name().to_string())
+                };
+                let (old_order, new_order) = (order(old_item), order(item));
+                let organize = |item: &Item| item.organize.clone().unwrap_or("none".to_string());
+                let (old_organize, new_organize) = (organize(old_item), organize(item));
+                for (field, old_value, value) in fields.into_iter().chain([
+                    ("order", &old_order, &new_order),
+                    ("organize", &old_organize, &new_organize),
+                ]) {
+                    if old_value != value {
+                        changes.push(tr!(
+                            "change-item-field",
+                            setting = field,
+                            playlist = id.as_str(),
+                            from = old_value.as_str(),
+                            to = value.as_str()
+                        ));
+                    }
+                }
+                if old_item.blacklist != item.blacklist {
+                    changes.push(tr!("change-item-blacklist", playlist = id.as_str()));
+                }
+                if old_item.min_free_space != item.min_free_space {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "min_free_space",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.locations != item.locations {
+                    changes.push(tr!("change-item-mirrors", playlist = id.as_str()));
+                }
+                if old_item.format_fallbacks != item.format_fallbacks {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "format_fallbacks",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.prune != item.prune || old_item.trash != item.trash {
+                    changes.push(tr!("change-item-pruning", playlist = id.as_str()));
+                }
+                let media = |item: &Item| {
+                    (
+                        item.audio_format.clone(),
+                        item.video_quality.clone(),
+                        item.container.clone(),
+                        item.yt_dlp_args.clone(),
+                        item.download_archive.clone(),
+                    )
+                };
+                if media(old_item) != media(item) {
+                    changes.push(tr!("change-item-format", playlist = id.as_str()));
+                }
+                if old_item.kind != item.kind {
+                    changes.push(tr!("change-item-kind", playlist = id.as_str()));
+                }
+                if old_item.name != item.name {
+                    changes.push(tr!(
+                        "change-item-renamed",
+                        playlist = id.as_str(),
+                        name = item.name()
+                    ));
+                }
+                if old_item.sidecar != item.sidecar {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "sidecar",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.split_chapters != item.split_chapters {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "split_chapters",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.track_numbers != item.track_numbers {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "track_numbers",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.mtime != item.mtime {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "mtime",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.chmod != item.chmod || old_item.chown != item.chown {
+                    changes.push(tr!("change-item-permissions", playlist = id.as_str()));
+                }
+                if old_item.keep_last != item.keep_last {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "keep_last",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.pinned != item.pinned {
+                    changes.push(tr!("change-item-pinned", playlist = id.as_str()));
+                }
+                if old_item.cookies_file != item.cookies_file
+                    || old_item.cookies_from_browser != item.cookies_from_browser
+                {
+                    changes.push(tr!("change-item-cookies", playlist = id.as_str()));
+                }
+                if old_item.post_command != item.post_command {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "post_command",
+                        playlist = id.as_str()
+                    ));
+                }
+                if old_item.views != item.views {
+                    changes.push(tr!(
+                        "change-item-setting",
+                        setting = "views",
+                        playlist = id.as_str()
+                    ));
+                }
+                if changes.len() == described {
+                    changes.push(tr!("change-item-settings", playlist = id.as_str()));
+                }
+            }
+            Some(_) => changes.push(tr!("change-item-entries", playlist = id.as_str())),
+        }
+    }
+
+    if old.fallback != new.fallback {
+        changes.push(tr!("change-fallback"));
+    }
+    if old.blacklist != new.blacklist {
+        changes.push(tr!("change-blacklist"));
+    }
+    if old.min_free_space != new.min_free_space {
+        changes.push(tr!("change-setting", setting = "min_free_space"));
+    }
+    if old.max_bytes_per_run != new.max_bytes_per_run {
+        changes.push(tr!("change-setting", setting = "max_bytes_per_run"));
+    }
+    if old.cookies_file != new.cookies_file || old.cookies_from_browser != new.cookies_from_browser
+    {
+        changes.push(tr!("change-cookies"));
+    }
+    if old.yt_dlp_path != new.yt_dlp_path {
+        changes.push(tr!("change-setting", setting = "yt_dlp_path"));
+    }
+    if old.hooks != new.hooks {
+        changes.push(tr!("change-hooks"));
+    }
+    if old.check_for_updates != new.check_for_updates {
+        changes.push(tr!("change-setting", setting = "check_for_updates"));
+    }
+    if changes.is_empty() && old != new {
+        changes.push(tr!("change-settings"));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Format;
+
+    fn item(id: &str, location: &str, format: Format) -> Item {
+        Item {
+            id: id.to_string(),
+            location: location.to_string(),
+            format,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn describes_config_changes() {
+        let old = Config {
+            items: vec![
+                item("PL1", "/music/one", Format::Audio),
+                item("PL2", "/music/two", Format::Audio),
+            ],
+            ..Default::default()
+        };
+        let new = Config {
+            items: vec![
+                item("PL1", "/music/one", Format::Video),
+                item("PL3", "/music/three", Format::Audio),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            describe_changes(&old, &new),
+            [
+                "removed playlist PL2 at /music/two",
+                "changed format of playlist PL1 from audio to video",
+                "added playlist PL3 at /music/three",
+            ]
+        );
+        assert!(describe_changes(&new, &new).is_empty());
+
+        let mut viewed = item("PL1", "/music/one", Format::Video);
+        viewed.views = Some("/music/views".to_string());
+        let newer = Config {
+            items: vec![viewed, item("PL3", "/music/three", Format::Audio)],
+            check_for_updates: true,
+            ..new.clone()
+        };
+        assert_eq!(
+            describe_changes(&new, &newer),
+            ["changed views of playlist PL1", "changed check_for_updates"]
+        );
+    }
+}
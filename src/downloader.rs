@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{log_enabled, Level};
+
+use crate::chapters;
+use crate::failure::{find_batch_error, Failure, FailureKind};
+use crate::i18n::tr;
+use crate::media::MediaFormat;
+use crate::observer::Observer;
+use crate::sync::{SyncOptions, Timings};
+
+// What yt-dlp is asked to print when it starts post-processing a video, with --timings.
+const POST_PROCESS_MARKER: &str = "yt-sync:post-process";
+
+// Splits the time yt-dlp spends on videos into downloading and post-processing, going by the
+// marker it prints when post-processing starts and the video IDs it prints when one is done.
+struct PhaseClock<'a> {
+    timings: &'a mut Timings,
+    phase_start: Instant,
+    post_processing: bool,
+}
+
+impl<'a> PhaseClock<'a> {
+    fn start(timings: &'a mut Timings) -> Self {
+        PhaseClock {
+            timings,
+            phase_start: Instant::now(),
+            post_processing: false,
+        }
+    }
+
+    // Attribute the time since the last switch to the current phase, and switch to another.
+    fn switch(&mut self, post_processing: bool) {
+        let elapsed = self.phase_start.elapsed();
+        if self.post_processing {
+            self.timings.post_processing += elapsed;
+        } else {
+            self.timings.downloading += elapsed;
+        }
+        self.phase_start = Instant::now();
+        self.post_processing = post_processing;
+    }
+
+    fn on_line(&mut self, line: &str) {
+        self.switch(line.starts_with(POST_PROCESS_MARKER));
+    }
+
+    fn finish(mut self) {
+        self.switch(false);
+    }
+}
+
+// Create the list of yt-dlp arguments shared by single and batch downloads.
+// When post-processing separately, yt-dlp only downloads, and saves the details of each video for
+// post_process_args to pick up.
+fn download_args(path: &str, format: &MediaFormat, options: &SyncOptions) -> Vec<String> {
+    // --continue is yt-dlp's default, but is given in case a yt-dlp config file turns it off, as
+    // interrupted downloads are resumed from their .part files. The same goes for --no-mtime, as
+    // the modification time yt-dlp would set is when YouTube last changed the file, not when the
+    // video was uploaded, which yt-sync sets itself when asked to.
+    let mut args = vec!["-P", path, "-q", "--continue", "--no-mtime"];
+    let post_process = !options.separate_post_processing();
+    if post_process && format.embeds_thumbnail() {
+        args.push("--embed-thumbnail");
+    }
+    if post_process {
+        args.push("--embed-metadata");
+    }
+    let selection = format.video_selection();
+    match (format.is_audio(), post_process) {
+        (true, true) => args.extend(["-x", "--audio-format", &format.audio_format]),
+        (true, false) => args.extend(["-f", "bestaudio/best"]),
+        (false, _) => args.extend(["-f", &selection, "--merge-output-format", &format.container]),
+    }
+    if log_enabled!(Level::Trace) {
+        args.push("-v");
+    }
+    let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+    args.extend(format.cookies.args());
+    if post_process {
+        args.extend(chapter_args(format));
+        args.extend(format.tag_args());
+    }
+    if options.separate_post_processing() {
+        args.extend([
+            "--write-info-json".to_string(),
+            "-o".to_string(),
+            "infojson:%(id)s".to_string(),
+            "-P".to_string(),
+            format!("infojson:{}", options.post_process_dir().display()),
+        ]);
+    }
+    if options.timings {
+        args.push("--print".to_string());
+        args.push(format!("post_process:{} %(id)s", POST_PROCESS_MARKER));
+    }
+    // Post-processing runs aren't given the archive, as yt-dlp would skip the videos it recorded
+    // while downloading them.
+    if let Some(download_archive) = &format.download_archive {
+        args.push("--download-archive".to_string());
+        args.push(download_archive.clone());
+    }
+    args.extend(format.yt_dlp_args.iter().cloned());
+    args
+}
+
+// Create the yt-dlp arguments for post-processing a video that was downloaded already, which
+// yt-dlp finds again from the details saved while downloading it.
+pub fn post_process_args(
+    video_id: &str,
+    path: &str,
+    format: &MediaFormat,
+    options: &SyncOptions,
+) -> Vec<String> {
+    let info_path = options
+        .post_process_dir()
+        .join(format!("{}.info.json", video_id));
+    let mut args = vec![
+        "--load-info-json".to_string(),
+        info_path.to_string_lossy().into_owned(),
+    ];
+    let mut rest = vec!["-P", path, "-q", "--embed-metadata"];
+    if format.embeds_thumbnail() {
+        rest.push("--embed-thumbnail");
+    }
+    if format.is_audio() {
+        rest.extend(["-x", "--audio-format", &format.audio_format]);
+    } else {
+        rest.extend(["--merge-output-format", &format.container]);
+    }
+    if log_enabled!(Level::Trace) {
+        rest.push("-v");
+    }
+    args.extend(rest.into_iter().map(String::from));
+    args.extend(chapter_args(format));
+    args.extend(format.tag_args());
+    args.extend(format.yt_dlp_args.iter().cloned());
+    args
+}
+
+// The arguments having yt-dlp split videos into their chapters, named so they can be found next to
+// the full video.
+fn chapter_args(format: &MediaFormat) -> Vec<String> {
+    match format.split_chapters {
+        true => ["--split-chapters", "-o", chapters::TEMPLATE]
+            .map(String::from)
+            .to_vec(),
+        false => Vec::new(),
+    }
+}
+
+// Save the output of a failed yt-dlp run to <data_dir>/failures/<name>-<timestamp>.log.
+pub fn save_failure_log(
+    options: &SyncOptions,
+    name: &str,
+    args: &[String],
+    stdout: &str,
+    stderr: &str,
+) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let failures_dir = options.data_dir.join("failures");
+    options.storage.create_dir(&failures_dir)?;
+    let log_path = failures_dir.join(format!("{}-{}.log", name, timestamp));
+    let log = format!(
+        "args: {:?}\n\nstdout:\n{}\nstderr:\n{}",
+        args, stdout, stderr
+    );
+    options.storage.write(&log_path, log.as_bytes())?;
+    Ok(log_path)
+}
+
+// How many times to retry a download that failed for a reason that may go away, and how long to
+// wait before the first retry.
+pub const DEFAULT_RETRIES: u32 = 2;
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+// The format selections tried when a playlist doesn't set its own.
+pub const DEFAULT_FORMAT_FALLBACKS: [&str; 1] = ["best"];
+
+// Download a video from YouTube using yt-dlp, retrying transient failures and saving yt-dlp's
+// output if it still fails. When the format isn't available, or converting it fails, the
+// fallback format selections are tried in turn.
+pub fn download_video(
+    video_id: &str,
+    path: &str,
+    format: &MediaFormat,
+    format_fallbacks: &[String],
+    options: &SyncOptions,
+    observer: &dyn Observer,
+    timings: &mut Timings,
+) -> Result<(), Failure> {
+    let url = format!("https://www.youtube.com/watch?v={}", video_id);
+    let mut args = download_args(path, format, options);
+    args.push(url.clone());
+    let mut fallbacks = format_fallbacks.iter();
+
+    let mut attempt = 0;
+    loop {
+        options.throttle.wait(&options.cancel);
+        let mut stdout = String::new();
+        let mut clock = PhaseClock::start(timings);
+        let result = options.runner.run(&args, &options.cancel, &mut |line| {
+            clock.on_line(line);
+            stdout.push_str(line);
+            stdout.push('\n');
+            true
+        });
+        clock.finish();
+        let stderr = match result {
+            Ok(output) if output.success() || output.code == Some(100) => {
+                options.throttle.succeeded();
+                return Ok(());
+            }
+            Ok(output) => format!("{}\nerror code: {:?}", output.stderr, output.code),
+            Err(e) => tr!("yt-dlp-unrunnable", error = format!("{:?}", e)),
+        };
+
+        let kind = FailureKind::classify(&stderr);
+        if kind == FailureKind::Throttled {
+            let (cooldown, delay) = options.throttle.throttled();
+            observer.on_throttled(cooldown, delay);
+        }
+        if let (FailureKind::FormatUnavailable, Some(fallback)) = (kind, fallbacks.next()) {
+            if options.verbose {
+                observer.on_detail(&tr!(
+                    "trying-fallback-format",
+                    video = video_id,
+                    format = fallback.as_str()
+                ));
+            }
+            // The last -f given wins. Videos are remuxed, as the fallback may not be mergeable
+            // into the file name the download is looked for under.
+            args = download_args(path, format, options);
+            args.extend(["-f".to_string(), fallback.clone()]);
+            if !format.is_audio() {
+                args.extend(["--remux-video".to_string(), format.container.clone()]);
+            }
+            args.push(url.clone());
+            attempt = 0;
+            continue;
+        }
+        // Unknown errors are tried again too, as yt-dlp now and then fails for reasons that go
+        // away, such as a fragment it couldn't fetch.
+        let retryable = kind.is_transient() || kind == FailureKind::Unknown;
+        if retryable && attempt < options.retries && !options.cancel.is_cancelled() {
+            let delay = options.retry_delay.saturating_mul(1 << attempt.min(16));
+            attempt += 1;
+            if options.verbose {
+                observer.on_detail(&tr!(
+                    "retrying",
+                    video = video_id,
+                    reason = kind.to_string(),
+                    delay = format!("{:?}", delay),
+                    attempt = attempt,
+                    retries = options.retries
+                ));
+            }
+            options.cancel.sleep(delay);
+            continue;
+        }
+        return Err(Failure {
+            video_id: video_id.to_string(),
+            kind,
+            log: save_failure_log(options, video_id, &args, &stdout, &stderr).ok(),
+        });
+    }
+}
+
+// Download several videos with one yt-dlp process reading their URLs from a batch file, returning
+// the IDs of the videos that finished downloading and the ones that failed.
+pub fn download_batch(
+    video_ids: &[&str],
+    path: &str,
+    format: &MediaFormat,
+    options: &SyncOptions,
+    observer: &dyn Observer,
+    timings: &mut Timings,
+) -> io::Result<(HashSet<String>, Vec<Failure>)> {
+    let batch_path = env::temp_dir().join(format!("yt-sync-batch-{}.txt", std::process::id()));
+    let mut batch_file = BufWriter::new(File::create(&batch_path)?);
+    for video_id in video_ids {
+        writeln!(batch_file, "https://www.youtube.com/watch?v={}", video_id)?;
+    }
+    batch_file.flush()?;
+    drop(batch_file);
+
+    // Have yt-dlp print each video's ID once it has been fully processed, to track progress.
+    let mut args = download_args(path, format, options);
+    args.extend([
+        "--batch-file".to_string(),
+        batch_path.to_string_lossy().into_owned(),
+        "--print".to_string(),
+        "after_move:%(id)s".to_string(),
+    ]);
+    let result = run_batch(&args, video_ids, options, observer, timings);
+    let _ = fs::remove_file(&batch_path);
+    result
+}
+
+// Run a batch yt-dlp process, reporting each video as it finishes. The output of the whole batch
+// is saved for the videos that failed, as it can't be told apart per video.
+fn run_batch(
+    args: &[String],
+    video_ids: &[&str],
+    options: &SyncOptions,
+    observer: &dyn Observer,
+    timings: &mut Timings,
+) -> io::Result<(HashSet<String>, Vec<Failure>)> {
+    let expected: HashSet<&str> = video_ids.iter().copied().collect();
+    let mut finished = HashSet::with_capacity(video_ids.len());
+    let mut clock = PhaseClock::start(timings);
+    let output = options.runner.run(args, &options.cancel, &mut |line| {
+        clock.on_line(line);
+        let video_id = line.trim();
+        if expected.contains(video_id) && finished.insert(video_id.to_string()) {
+            observer.on_video_done(video_id, true);
+        }
+        true
+    });
+    clock.finish();
+    let output = output?;
+
+    for video_id in video_ids {
+        if !finished.contains(*video_id) {
+            observer.on_video_done(video_id, false);
+        }
+    }
+    if FailureKind::classify(&output.stderr) == FailureKind::Throttled {
+        let (cooldown, delay) = options.throttle.throttled();
+        observer.on_throttled(cooldown, delay);
+    }
+
+    let mut failures = Vec::new();
+    if finished.len() < video_ids.len() && !options.cancel.is_cancelled() {
+        let stderr = format!("{}\nerror code: {:?}", output.stderr, output.code);
+        let log = save_failure_log(options, "batch", args, "", &stderr).ok();
+        failures.extend(
+            video_ids
+                .iter()
+                .filter(|video_id| !finished.contains(**video_id))
+                .map(|video_id| Failure {
+                    video_id: video_id.to_string(),
+                    kind: find_batch_error(&output.stderr, video_id)
+                        .map_or(FailureKind::Unknown, FailureKind::classify),
+                    log: log.clone(),
+                }),
+        );
+    }
+    Ok((finished, failures))
+}
@@ -0,0 +1,137 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::catalog::location_files;
+use crate::config::format_size;
+use crate::i18n::tr;
+use crate::state::State;
+use crate::storage::Storage;
+
+// How much disk space a group of downloaded files takes up.
+#[derive(Debug, PartialEq)]
+pub struct Usage {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+// Add up the sizes of the downloaded files of each playlist, given as its ID and location, and of
+// each uploader, as remembered in the state when the videos were downloaded. Files in a location
+// shared by several playlists count towards the first. Both lists are sorted largest first.
+pub fn usage(
+    storage: &dyn Storage,
+    playlists: &[(&str, &str)],
+    state: &State,
+) -> io::Result<(Vec<Usage>, Vec<Usage>)> {
+    let unknown = tr!("unknown-uploader");
+    let mut by_playlist = Vec::new();
+    let mut by_uploader: BTreeMap<&str, Usage> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    for (id, location) in playlists {
+        let mut usage = Usage {
+            name: format!("{} ({})", id, location),
+            files: 0,
+            bytes: 0,
+        };
+        let files = match location_files(storage, location, state) {
+            Ok(files) => files,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for (video_id, _, path) in files {
+            let path = Path::new(location).join(path);
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let Ok(bytes) = storage.size(&path) else {
+                continue;
+            };
+            usage.files += 1;
+            usage.bytes += bytes;
+            let uploader = state
+                .uploaders
+                .get(&video_id)
+                .map_or(unknown.as_str(), |u| u);
+            let uploader = by_uploader.entry(uploader).or_insert_with(|| Usage {
+                name: uploader.to_string(),
+                files: 0,
+                bytes: 0,
+            });
+            uploader.files += 1;
+            uploader.bytes += bytes;
+        }
+        by_playlist.push(usage);
+    }
+    let mut by_uploader: Vec<Usage> = by_uploader.into_values().collect();
+    by_playlist.sort_by_key(|usage| Reverse(usage.bytes));
+    by_uploader.sort_by_key(|usage| Reverse(usage.bytes));
+    Ok((by_playlist, by_uploader))
+}
+
+// Print a list of usages under a heading, followed by their total.
+pub fn write(out: &mut dyn Write, heading: &str, usages: &[Usage]) -> io::Result<()> {
+    writeln!(out, "{}:", heading)?;
+    let line = |out: &mut dyn Write, bytes, files, name: &str| {
+        writeln!(
+            out,
+            "{:>10} {:>7} {}  {}",
+            format_size(bytes),
+            files,
+            tr!("files-unit", count = files),
+            name
+        )
+    };
+    for usage in usages {
+        line(out, usage.bytes, usage.files, &usage.name)?;
+    }
+    let bytes = usages.iter().map(|usage| usage.bytes).sum();
+    let files = usages.iter().map(|usage| usage.files).sum();
+    line(out, bytes, files, &tr!("summary-total"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn adds_up_usage_by_playlist_and_uploader() {
+        let storage = MemoryStorage::default();
+        for (path, size) in [
+            ("/a/One [x].opus", 100),
+            ("/a/Two [y].opus", 200),
+            ("/b/Three [z].opus", 1000),
+        ] {
+            storage.write(Path::new(path), &vec![0; size]).unwrap();
+        }
+        let mut state = State::default();
+        state.uploaders.insert("x".to_string(), "Chan".to_string());
+        state.uploaders.insert("z".to_string(), "Chan".to_string());
+
+        let playlists = [("PL1", "/a"), ("PL2", "/b"), ("PL3", "/a")];
+        let (by_playlist, by_uploader) = usage(&storage, &playlists, &state).unwrap();
+        let sizes = |usages: &[Usage]| -> Vec<(String, u64)> {
+            usages.iter().map(|u| (u.name.clone(), u.bytes)).collect()
+        };
+        assert_eq!(
+            sizes(&by_playlist),
+            [
+                ("PL2 (/b)".to_string(), 1000),
+                ("PL1 (/a)".to_string(), 300),
+                ("PL3 (/a)".to_string(), 0)
+            ]
+        );
+        assert_eq!(
+            sizes(&by_uploader),
+            [("Chan".to_string(), 1100), ("Unknown".to_string(), 200)]
+        );
+
+        let mut out = Vec::new();
+        write(&mut out, "By uploader", &by_uploader).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("    1.3 KB       3 files  Total\n"));
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::catalog::location_files;
+use crate::state::State;
+use crate::storage::Storage;
+
+// Files that are copies of one another, first the one found in the earliest location.
+pub struct Group {
+    // The video all the files are of, or None for identical files of different videos, such as
+    // a reupload.
+    pub video_id: Option<String>,
+    pub paths: Vec<PathBuf>,
+}
+
+// Find the videos downloaded more than once across locations, along with files that are
+// byte-for-byte identical whatever their video IDs. Files that are already symlinks, as left by
+// link, are skipped. Only files of the same size are hashed, as nothing else can be identical.
+pub fn find(storage: &dyn Storage, locations: &[&str], state: &State) -> io::Result<Vec<Group>> {
+    let mut paths = Vec::new();
+    for location in locations {
+        let files = match location_files(storage, location, state) {
+            Ok(files) => files,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for (video_id, _, path) in files {
+            let path = Path::new(location).join(path);
+            // Locations synced by several items are only looked at once.
+            if storage.read_link(&path).is_err() && !paths.iter().any(|(_, p)| *p == path) {
+                paths.push((video_id, path));
+            }
+        }
+    }
+
+    let mut by_id: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for (video_id, path) in &paths {
+        by_id.entry(video_id).or_default().push(path.clone());
+        by_size.entry(storage.size(path)?).or_default().push(path);
+    }
+    let mut by_hash: BTreeMap<Vec<u8>, Vec<PathBuf>> = BTreeMap::new();
+    for same_size in by_size.into_values().filter(|paths| paths.len() > 1) {
+        for path in same_size {
+            let hash = Sha256::digest(storage.read(path)?).to_vec();
+            by_hash.entry(hash).or_default().push(path.clone());
+        }
+    }
+
+    let mut groups: Vec<Group> = by_id
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(video_id, paths)| Group {
+            video_id: Some(video_id.to_string()),
+            paths,
+        })
+        .collect();
+    for mut identical in by_hash.into_values().filter(|paths| paths.len() > 1) {
+        // Copies of the same video are already reported above.
+        let is_one_video = groups
+            .iter()
+            .any(|group| identical.iter().all(|path| group.paths.contains(path)));
+        if !is_one_video {
+            identical.sort_by_key(|path| paths.iter().position(|(_, p)| p == path));
+            groups.push(Group {
+                video_id: None,
+                paths: identical,
+            });
+        }
+    }
+    Ok(groups)
+}
+
+// Replace copies with symlinks to the first file of their group, returning how many were
+// replaced. Copies of a video in another format than the first are left alone, as they aren't
+// interchangeable.
+pub fn link(storage: &dyn Storage, groups: &[Group]) -> io::Result<usize> {
+    let mut linked = 0;
+    for group in groups {
+        let Some((first, copies)) = group.paths.split_first() else {
+            continue;
+        };
+        let target = storage.canonicalize(first)?;
+        for copy in copies {
+            if copy.extension() != first.extension() {
+                continue;
+            }
+            storage.delete(copy)?;
+            storage.symlink(&target, copy)?;
+            linked += 1;
+        }
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn finds_and_links_duplicates() {
+        let storage = MemoryStorage::default();
+        for (path, content) in [
+            ("/a/One [x].opus", "one"),
+            ("/b/One [x].opus", "one"),
+            ("/b/One [x].mkv", "one video"),
+            ("/a/Two [y].opus", "two"),
+            ("/b/Two (reupload) [z].opus", "two"),
+            ("/b/Three [w].opus", "six"),
+        ] {
+            storage.write(Path::new(path), content.as_bytes()).unwrap();
+        }
+
+        let state = State::default();
+        let groups = find(&storage, &["/a", "/b"], &state).unwrap();
+        let summary: Vec<_> = groups
+            .iter()
+            .map(|group| (group.video_id.as_deref(), group.paths.len()))
+            .collect();
+        assert_eq!(summary, [(Some("x"), 3), (None, 2)]);
+        assert_eq!(groups[1].paths[0], Path::new("/a/Two [y].opus"));
+
+        assert_eq!(link(&storage, &groups).unwrap(), 2);
+        assert_eq!(
+            storage.read_link(Path::new("/b/One [x].opus")).unwrap(),
+            Path::new("/a/One [x].opus")
+        );
+        assert!(storage.read(Path::new("/b/One [x].mkv")).is_ok());
+        let groups = find(&storage, &["/a", "/b"], &state).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths[1], Path::new("/b/One [x].mkv"));
+    }
+}
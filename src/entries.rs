@@ -0,0 +1,194 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+// How many entries of a playlist are kept in memory before the rest go to disk.
+pub const SPILL_AFTER: usize = 10_000;
+
+// Numbers the spill files of the playlists synced by this process.
+static NEXT_SPILL: AtomicUsize = AtomicUsize::new(0);
+
+// A video as the playlist listed it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub video_id: String,
+    // Where the video was listed in the playlist, counting from 0.
+    pub index: usize,
+    // The name yt-dlp gives its file, going by its title, or the path of the file inside the
+    // location for pinned videos that weren't listed.
+    pub file_name: String,
+    pub title: String,
+    // In seconds since the Unix epoch, if the listing said.
+    pub uploaded_at: Option<i64>,
+    pub uploader: Option<String>,
+}
+
+// The videos of a playlist, in playlist order. The first entries are kept in memory, and any after
+// those are written to a temporary file, so memory use stays flat for playlists with tens of
+// thousands of videos.
+pub struct Entries {
+    memory: Vec<Entry>,
+    spill_after: usize,
+    spill: Option<(PathBuf, BufWriter<File>)>,
+    len: usize,
+}
+
+impl Entries {
+    pub fn new(spill_after: usize) -> Self {
+        Entries {
+            memory: Vec::new(),
+            spill_after,
+            spill: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn push(&mut self, entry: Entry) -> io::Result<()> {
+        self.len += 1;
+        if self.memory.len() < self.spill_after {
+            self.memory.push(entry);
+            return Ok(());
+        }
+        if self.spill.is_none() {
+            let path = env::temp_dir().join(format!(
+                "yt-sync-entries-{}-{}.jsonl",
+                std::process::id(),
+                NEXT_SPILL.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = BufWriter::new(File::create(&path)?);
+            self.spill = Some((path, file));
+        }
+        let (_, file) = self.spill.as_mut().unwrap();
+        serde_json::to_writer(&mut *file, &entry)?;
+        writeln!(file)
+    }
+
+    pub fn clear(&mut self) {
+        self.remove_spill();
+        self.memory.clear();
+        self.len = 0;
+    }
+
+    // Keep only the entries f returns true for, in the same order.
+    pub fn retain(&mut self, mut f: impl FnMut(&Entry) -> bool) -> io::Result<()> {
+        let mut kept = Entries::new(self.spill_after);
+        for entry in self.iter()? {
+            let entry = entry?;
+            if f(&entry) {
+                kept.push(entry)?;
+            }
+        }
+        *self = kept;
+        Ok(())
+    }
+
+    // Go through the entries in order.
+    pub fn for_each(&mut self, mut f: impl FnMut(&Entry)) -> io::Result<()> {
+        for entry in self.iter()? {
+            f(&entry?);
+        }
+        Ok(())
+    }
+
+    // The entries in order, reading back the ones that were written to disk as it goes.
+    pub fn iter(&mut self) -> io::Result<impl Iterator<Item = io::Result<Entry>> + '_> {
+        let spilled = match &mut self.spill {
+            Some((path, file)) => {
+                file.flush()?;
+                Some(BufReader::new(File::open(path)?).lines())
+            }
+            None => None,
+        };
+        let spilled = spilled.into_iter().flatten().map(|line| {
+            let entry = serde_json::from_str(&line?)?;
+            Ok(entry)
+        });
+        Ok(self.memory.iter().cloned().map(Ok).chain(spilled))
+    }
+
+    fn remove_spill(&mut self) {
+        if let Some((path, file)) = self.spill.take() {
+            drop(file);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for Entries {
+    fn drop(&mut self) {
+        self.remove_spill();
+    }
+}
+
+// The video IDs of a playlist that were seen, kept as hashes rather than the IDs themselves, to
+// take less memory. The hashes are keyed afresh each run, so in the unlikely case of two IDs
+// sharing one, a video left out because of it isn't left out again next run.
+#[derive(Default)]
+pub struct Seen {
+    hashes: HashSet<u64>,
+    keys: RandomState,
+}
+
+impl Seen {
+    // Whether a video is new, remembering it if so.
+    pub fn insert(&mut self, video_id: &str) -> bool {
+        self.hashes.insert(self.keys.hash_one(video_id))
+    }
+
+    pub fn contains(&self, video_id: &str) -> bool {
+        self.hashes.contains(&self.keys.hash_one(video_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(video_id: &str) -> Entry {
+        Entry {
+            video_id: video_id.to_string(),
+            index: 0,
+            file_name: format!("{}\n.opus", video_id),
+            title: video_id.to_string(),
+            uploaded_at: Some(1),
+            uploader: None,
+        }
+    }
+
+    #[test]
+    fn spills_entries_past_the_limit_in_order() {
+        let mut entries = Entries::new(2);
+        for video_id in ["a", "b", "c", "d"] {
+            entries.push(entry(video_id)).unwrap();
+        }
+        let spill_path = entries.spill.as_ref().unwrap().0.clone();
+        assert!(spill_path.exists());
+
+        let mut listed = Vec::new();
+        entries
+            .for_each(|entry| listed.push(entry.clone()))
+            .unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(listed, ["a", "b", "c", "d"].map(entry));
+
+        entries.retain(|entry| entry.video_id != "c").unwrap();
+        let listed: Vec<Entry> = entries.iter().unwrap().map(Result::unwrap).collect();
+        assert_eq!(listed, ["a", "b", "d"].map(entry));
+
+        let spill_path = entries.spill.as_ref().unwrap().0.clone();
+        entries.clear();
+        assert!(!spill_path.exists());
+        assert_eq!(entries.len(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::failure::Failure;
+use crate::i18n::tr;
+
+// Why a sync stopped, or finished without getting everything. Videos that fail to download don't
+// stop a sync, and are gathered up at the end of the run instead.
+#[derive(Debug)]
+pub enum SyncError {
+    // The item of a playlist in the config is invalid, such as a min_free_space that isn't a size.
+    InvalidItem {
+        playlist_id: String,
+        reason: String,
+    },
+    // Neither yt-dlp nor the fallback instance could list a playlist.
+    Listing {
+        playlist_id: String,
+        reason: String,
+    },
+    // Reading or writing a file failed, such as an m3u file or the state of yt-sync.
+    Io(io::Error),
+    // The sync was stopped, such as with Ctrl-C.
+    Cancelled,
+    // Downloading stopped at these locations, as they ran low on free space.
+    LowOnSpace(Vec<String>),
+    // The playlists that couldn't be synced, by ID with why, the videos that failed to download,
+    // with the ID of the playlist each is in, and why hooks failed.
+    Failed {
+        items: Vec<(String, String)>,
+        downloads: Vec<(String, Failure)>,
+        hooks: Vec<String>,
+    },
+}
+
+impl SyncError {
+    // The exit code of a run that ends with this error, so scripts can tell failed downloads
+    // apart from a sync that couldn't run at all.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SyncError::Failed { items, .. } if !items.is_empty() => 1,
+            SyncError::Failed { downloads, .. } if !downloads.is_empty() => 3,
+            SyncError::Failed { .. } => 5,
+            SyncError::LowOnSpace(_) => 4,
+            SyncError::Cancelled => 130,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncError::InvalidItem {
+                playlist_id,
+                reason,
+            } => f.write_str(&tr!(
+                "invalid-item",
+                playlist = playlist_id.as_str(),
+                reason = reason.as_str()
+            )),
+            SyncError::Listing {
+                playlist_id,
+                reason,
+            } => f.write_str(&tr!(
+                "listing-failed",
+                playlist = playlist_id.as_str(),
+                reason = reason.as_str()
+            )),
+            SyncError::Io(e) => e.fmt(f),
+            SyncError::Cancelled => f.write_str(&tr!("sync-cancelled")),
+            SyncError::LowOnSpace(locations) => f.write_str(&tr!(
+                "stopped-low-on-space",
+                locations = locations.join(" and ")
+            )),
+            SyncError::Failed {
+                items,
+                downloads,
+                hooks,
+            } => {
+                if !items.is_empty() {
+                    f.write_str(&tr!("playlists-not-synced", count = items.len()))?;
+                }
+                for (_, reason) in items {
+                    write!(f, "\n  {}", reason)?;
+                }
+                if !items.is_empty() && !downloads.is_empty() {
+                    f.write_str("\n")?;
+                }
+                if !downloads.is_empty() {
+                    f.write_str(&tr!("videos-failed", count = downloads.len()))?;
+                }
+                for (playlist_id, failure) in downloads {
+                    let (video, playlist) = (failure.video_id.as_str(), playlist_id.as_str());
+                    let reason = failure.kind.to_string();
+                    let line = match &failure.log {
+                        Some(log) => tr!(
+                            "video-failed-in-playlist-with-log",
+                            video = video,
+                            playlist = playlist,
+                            reason = reason,
+                            log = log.display().to_string()
+                        ),
+                        None => tr!(
+                            "video-failed-in-playlist",
+                            video = video,
+                            playlist = playlist,
+                            reason = reason
+                        ),
+                    };
+                    write!(f, "\n  {}", line)?;
+                }
+                if !hooks.is_empty() {
+                    if !items.is_empty() || !downloads.is_empty() {
+                        f.write_str("\n")?;
+                    }
+                    f.write_str(&tr!("hooks-failed", count = hooks.len()))?;
+                    for failure in hooks {
+                        write!(f, "\n  {}", failure)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SyncError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SyncError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SyncError {
+    fn from(e: io::Error) -> Self {
+        SyncError::Io(e)
+    }
+}
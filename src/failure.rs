@@ -0,0 +1,174 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::throttle::Throttle;
+
+// Why a download failed, worked out from what yt-dlp printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Network,
+    Throttled,
+    // The video is deleted, private, or otherwise gone for good.
+    Unavailable,
+    FormatUnavailable,
+    GeoBlocked,
+    AuthRequired,
+    Unknown,
+}
+
+// Messages yt-dlp prints for each kind of permanent failure, checked in order.
+const SIGNS: [(FailureKind, &[&str]); 4] = [
+    (
+        FailureKind::AuthRequired,
+        &[
+            // Age checks, and YouTube asking to confirm this isn't a bot.
+            "sign in to confirm",
+            "this playlist is private",
+            "playlist type is unviewable",
+            "members-only",
+            "join this channel",
+            "available to music premium",
+            "requires payment",
+            "use --cookies",
+        ],
+    ),
+    (
+        FailureKind::GeoBlocked,
+        &[
+            "available in your country",
+            "blocked it in your country",
+            "geo restriction",
+            "geo-restricted",
+        ],
+    ),
+    (
+        FailureKind::Unavailable,
+        &[
+            "video unavailable",
+            "private video",
+            "has been removed",
+            "no longer available",
+            "has been terminated",
+            "does not exist",
+        ],
+    ),
+    (
+        FailureKind::FormatUnavailable,
+        &[
+            "requested format is not available",
+            "no video formats found",
+            "postprocessing:",
+        ],
+    ),
+];
+
+// Messages yt-dlp prints when the connection to YouTube failed.
+const NETWORK_SIGNS: [&str; 10] = [
+    "unable to download webpage",
+    "unable to download video data",
+    "timed out",
+    "connection reset",
+    "temporary failure in name resolution",
+    "network is unreachable",
+    "http error 5",
+    "incompleteread",
+    "urlopen error",
+    "got error:",
+];
+
+impl FailureKind {
+    // Classify a failure from yt-dlp's output.
+    pub fn classify(output: &str) -> FailureKind {
+        let output = output.to_lowercase();
+        if let Some((kind, _)) = SIGNS
+            .iter()
+            .find(|(_, signs)| signs.iter().any(|sign| output.contains(sign)))
+        {
+            *kind
+        } else if Throttle::is_throttled(&output) {
+            FailureKind::Throttled
+        } else if NETWORK_SIGNS.iter().any(|sign| output.contains(sign)) {
+            FailureKind::Network
+        } else {
+            FailureKind::Unknown
+        }
+    }
+
+    // Whether trying again later in the same run might work.
+    pub fn is_transient(self) -> bool {
+        matches!(self, FailureKind::Network | FailureKind::Throttled)
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FailureKind::Network => "network error",
+            FailureKind::Throttled => "throttled by YouTube",
+            FailureKind::Unavailable => "video unavailable",
+            FailureKind::FormatUnavailable => "format unavailable",
+            FailureKind::GeoBlocked => "blocked in your country",
+            FailureKind::AuthRequired => "sign-in required",
+            FailureKind::Unknown => "unknown error",
+        })
+    }
+}
+
+// A video that failed to download, and where the output of yt-dlp was saved for diagnosis.
+#[derive(Debug)]
+pub struct Failure {
+    pub video_id: String,
+    pub kind: FailureKind,
+    pub log: Option<PathBuf>,
+}
+
+// Find the error yt-dlp printed for one video of a batch, which looks like
+// "ERROR: [youtube] <video_id>: <message>".
+pub fn find_batch_error<'a>(stderr: &'a str, video_id: &str) -> Option<&'a str> {
+    let marker = format!("] {}:", video_id);
+    stderr
+        .lines()
+        .find(|line| line.starts_with("ERROR:") && line.contains(&marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_yt_dlp_errors() {
+        let classify = FailureKind::classify;
+        assert_eq!(
+            classify("ERROR: [youtube] abc: Video unavailable. This video has been removed by the uploader"),
+            FailureKind::Unavailable
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] abc: Sign in to confirm your age. This video may be inappropriate for some users."),
+            FailureKind::AuthRequired
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] abc: The uploader has not made this video available in your country"),
+            FailureKind::GeoBlocked
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] abc: Requested format is not available. Use --list-formats for a list of available formats"),
+            FailureKind::FormatUnavailable
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] abc: Unable to download webpage: <urlopen error [Errno -3] Temporary failure in name resolution>"),
+            FailureKind::Network
+        );
+        assert_eq!(
+            classify("ERROR: unable to download video data: HTTP Error 429: Too Many Requests"),
+            FailureKind::Throttled
+        );
+        assert_eq!(classify("ERROR: something new"), FailureKind::Unknown);
+
+        let stderr = "WARNING: [youtube] a: skipping\nERROR: [youtube] b: Private video\n";
+        assert_eq!(
+            find_batch_error(stderr, "b"),
+            Some("ERROR: [youtube] b: Private video")
+        );
+        assert_eq!(find_batch_error(stderr, "a"), None);
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+
+// The API spoken by a fallback instance.
+#[derive(clap::ValueEnum, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackApi {
+    Invidious,
+    Piped,
+}
+
+// An Invidious or Piped instance used to list playlists when yt-dlp cannot.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Fallback {
+    pub api: FallbackApi,
+    pub url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct InvidiousPlaylist {
+    videos: Vec<InvidiousVideo>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct InvidiousVideo {
+    title: String,
+    video_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PipedPage {
+    related_streams: Vec<PipedStream>,
+    nextpage: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PipedStream {
+    url: String,
+    title: String,
+}
+
+// Stream the video IDs and titles of a playlist from the fallback instance, a page at a time.
+pub fn list_videos(
+    fallback: &Fallback,
+    playlist_id: &str,
+    on_video: &mut dyn FnMut(String, &str) -> io::Result<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build();
+    let base_url = fallback.url.trim_end_matches('/');
+
+    // Instances can repeat entries across page boundaries, so keep the first occurrence only.
+    let mut seen = HashSet::new();
+    let mut on_page = |entries: Vec<(String, String)>| {
+        for (video_id, title) in entries {
+            if seen.insert(video_id.clone()) {
+                on_video(video_id, &title)?;
+            }
+        }
+        Ok(())
+    };
+    match fallback.api {
+        FallbackApi::Invidious => list_invidious(&agent, base_url, playlist_id, &mut on_page)?,
+        FallbackApi::Piped => list_piped(&agent, base_url, playlist_id, &mut on_page)?,
+    }
+
+    if seen.is_empty() {
+        return Err(tr!(
+            "fallback-empty",
+            fallback = base_url,
+            playlist = playlist_id
+        )
+        .into());
+    }
+    Ok(())
+}
+
+type OnPage<'a> = dyn FnMut(Vec<(String, String)>) -> io::Result<()> + 'a;
+
+// List a playlist through the Invidious API, which pages by number until a page comes back empty.
+fn list_invidious(
+    agent: &ureq::Agent,
+    base_url: &str,
+    playlist_id: &str,
+    on_page: &mut OnPage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = format!("{}/api/v1/playlists/{}", base_url, playlist_id);
+    for page in 1.. {
+        let playlist: InvidiousPlaylist = agent
+            .get(&url)
+            .query("page", &page.to_string())
+            .call()?
+            .into_json()?;
+        if playlist.videos.is_empty() {
+            break;
+        }
+        on_page(
+            playlist
+                .videos
+                .into_iter()
+                .map(|video| (video.video_id, video.title))
+                .collect(),
+        )?;
+    }
+    Ok(())
+}
+
+// List a playlist through the Piped API, which pages with an opaque continuation token.
+fn list_piped(
+    agent: &ureq::Agent,
+    base_url: &str,
+    playlist_id: &str,
+    on_page: &mut OnPage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut page: PipedPage = agent
+        .get(&format!("{}/playlists/{}", base_url, playlist_id))
+        .call()?
+        .into_json()?;
+    loop {
+        on_page(
+            page.related_streams
+                .into_iter()
+                .filter_map(|stream| {
+                    let video_id = stream.url.strip_prefix("/watch?v=")?.to_string();
+                    Some((video_id, stream.title))
+                })
+                .collect(),
+        )?;
+        let Some(nextpage) = page.nextpage else {
+            break;
+        };
+        page = agent
+            .get(&format!("{}/nextpage/playlists/{}", base_url, playlist_id))
+            .query("nextpage", &nextpage)
+            .call()?
+            .into_json()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::io;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::i18n::tr;
+
+// Commands run at points of every sync, such as to tag or normalize downloads, from the [hooks]
+// section of the config.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    // Run after each video is downloaded, before the post_command of its playlist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_download: Option<String>,
+    // Run once every playlist has been synced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_sync: Option<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        *self == Hooks::default()
+    }
+}
+
+// Run a hook with the shell, telling it what it runs for through environment variables. All it
+// prints goes to the standard error of yt-sync, keeping standard output for --json, and it is
+// killed if the sync is cancelled.
+pub fn run(command: &str, env: &[(&str, &str)], cancel: &CancelToken) -> Result<(), String> {
+    let mut shell = match cfg!(windows) {
+        true => Command::new("cmd"),
+        false => Command::new("sh"),
+    };
+    shell
+        .arg(if cfg!(windows) { "/C" } else { "-c" })
+        .arg(command)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(io::stderr());
+    let status = cancel
+        .spawn(&mut shell)
+        .and_then(|process| process.wait())
+        .map_err(|e| tr!("hook-unrunnable", hook = command, error = e.to_string()))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(tr!(
+            "hook-status",
+            hook = command,
+            status = status.to_string()
+        )),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_hooks_with_their_environment() {
+        let cancel = CancelToken::default();
+        let env = [("YT_SYNC_VIDEO_ID", "a")];
+        assert!(run("test \"$YT_SYNC_VIDEO_ID\" = a", &env, &cancel).is_ok());
+        let failed = run("exit 3", &env, &cancel).unwrap_err();
+        assert!(failed.contains("exit status: 3"), "{}", failed);
+    }
+}
@@ -0,0 +1,110 @@
+use std::env;
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+// The translations built into the binary, by language, each a Fluent file in locales/. English
+// is the fallback for languages without one and for messages a translation hasn't caught up on.
+const LOCALES: [(&str, &str); 1] = [("en", include_str!("../locales/en.ftl"))];
+
+// Setting this picks the language of messages, overriding the usual locale variables.
+const LOCALE_ENV: &str = "YT_SYNC_LOCALE";
+
+// The bundles messages are looked up in, in order: the user's language, then English.
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+// The language asked for by the environment, such as de for LANG=de_DE.UTF-8.
+fn requested_language() -> Option<String> {
+    [LOCALE_ENV, "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(['_', '-', '.', '@'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+}
+
+fn bundle(language: &str, source: &str) -> FluentBundle<FluentResource> {
+    let language: LanguageIdentifier = language.parse().expect("Invalid language in LOCALES");
+    let resource = FluentResource::try_new(source.to_string())
+        .unwrap_or_else(|(_, errors)| panic!("Invalid {} translation: {:?}", language, errors));
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Messages go to a terminal, where Unicode isolation marks around arguments only get in the
+    // way.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("Duplicate message in a translation");
+    bundle
+}
+
+fn bundles() -> &'static [FluentBundle<FluentResource>] {
+    BUNDLES.get_or_init(|| {
+        let requested = requested_language();
+        LOCALES
+            .iter()
+            .filter(|(language, _)| {
+                Some(*language) == requested.as_deref() || *language == LOCALES[0].0
+            })
+            .rev()
+            .map(|(language, source)| bundle(language, source))
+            .collect()
+    })
+}
+
+// Format a message in the user's language. A message missing from every translation comes out as
+// its ID, so a typo shows up rather than an empty line.
+pub fn message(id: &str, args: &FluentArgs) -> String {
+    for bundle in bundles() {
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+            continue;
+        };
+        let mut errors = Vec::new();
+        return bundle
+            .format_pattern(pattern, Some(args), &mut errors)
+            .into_owned();
+    }
+    id.to_string()
+}
+
+// Format a message with named arguments, as in tr!("synced", count = 2, location = "/music").
+macro_rules! tr {
+    ($id:expr $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)*
+        $crate::i18n::message($id, &args)
+    }};
+}
+
+pub(crate) use tr;
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn formats_messages_with_plurals() {
+        assert_eq!(
+            tr!("synced", count = 1, location = "/music"),
+            "1 new song successfully synced to /music"
+        );
+        assert_eq!(
+            tr!("synced", count = 2, location = "/music"),
+            "2 new songs successfully synced to /music"
+        );
+        assert_eq!(
+            tr!("pruned", count = 1),
+            "1 video no longer in the playlist was pruned"
+        );
+        assert_eq!(
+            tr!("verified", checked = 3, broken = 1),
+            "Checked 3 files, found 1 problem"
+        );
+        assert_eq!(tr!("no-such-message"), "no-such-message");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table};
+
+use crate::config::Format;
+use crate::i18n::tr;
+
+// A playlist to add to the config, as given to the add command.
+pub struct NewItem<'a> {
+    pub id: &'a str,
+    pub name: Option<&'a str>,
+    pub location: &'a str,
+    pub format: Format,
+    pub save_playlist: bool,
+}
+
+// The name of an item in the config, which is its ID unless it has a name.
+fn name_of(table: &Table) -> Option<&str> {
+    table.get("name").or_else(|| table.get("id"))?.as_str()
+}
+
+fn items_of(document: &mut DocumentMut) -> Result<&mut ArrayOfTables, Box<dyn std::error::Error>> {
+    Ok(document
+        .entry("items")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| tr!("items-not-tables"))?)
+}
+
+// Add an item to the end of a config, keeping the rest of the file as it was written, comments
+// included.
+fn add_to_document(
+    content: &str,
+    new_item: &NewItem,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document: DocumentMut = content.parse()?;
+    let items = items_of(&mut document)?;
+    let name = new_item.name.unwrap_or(new_item.id);
+    if items.iter().any(|table| name_of(table) == Some(name)) {
+        return Err(tr!("item-exists", item = name).into());
+    }
+    let mut table = Table::new();
+    table["id"] = value(new_item.id);
+    if let Some(name) = new_item.name {
+        table["name"] = value(name);
+    }
+    table["location"] = value(new_item.location);
+    table["format"] = value(new_item.format.name());
+    table["save_playlist"] = value(new_item.save_playlist);
+    items.push(table);
+    Ok(document.to_string())
+}
+
+// Remove the item with a name from a config, keeping the rest of the file as it was written.
+fn remove_from_document(content: &str, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document: DocumentMut = content.parse()?;
+    let items = items_of(&mut document)?;
+    let positions: Vec<usize> = (0..items.len())
+        .filter(|&i| items.get(i).and_then(name_of) == Some(name))
+        .collect();
+    match positions[..] {
+        [position] => {
+            let decor = items.get(position).map(|removed| removed.decor().clone());
+            items.remove(position);
+            // The next item takes the place of the removed one, comments above it included.
+            if let (Some(next), Some(decor)) = (items.get_mut(position), decor) {
+                *next.decor_mut() = decor;
+            }
+        }
+        [] => return Err(tr!("no-such-item", item = name).into()),
+        _ => return Err(tr!("several-items-named", item = name).into()),
+    }
+    Ok(document.to_string())
+}
+
+// Rewrite the config file with an edit, starting from an empty one if there is none yet.
+fn edit(
+    config_path: &Path,
+    edit_document: impl FnOnce(&str) -> Result<String, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(tr!(
+                "config-unreadable",
+                path = config_path.display().to_string(),
+                error = e.to_string()
+            )
+            .into())
+        }
+    };
+    let content = edit_document(&content).map_err(|e| {
+        tr!(
+            "config-uneditable",
+            path = config_path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    if let Some(config_dir) = config_path.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+    fs::write(config_path, content).map_err(|e| {
+        tr!(
+            "config-unwritable",
+            path = config_path.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    Ok(())
+}
+
+// Add an item to the config file.
+pub fn add(config_path: &Path, new_item: &NewItem) -> Result<(), Box<dyn std::error::Error>> {
+    edit(config_path, |content| add_to_document(content, new_item))?;
+    println!(
+        "{}",
+        tr!(
+            "item-added",
+            item = new_item.name.unwrap_or(new_item.id),
+            location = new_item.location
+        )
+    );
+    Ok(())
+}
+
+// Remove an item from the config file. Its downloaded files are left where they are.
+pub fn remove(config_path: &Path, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    edit(config_path, |content| remove_from_document(content, name))?;
+    println!("{}", tr!("item-removed", item = name));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_and_removes_items_keeping_comments() {
+        let config =
+            "# My playlists\n[[items]]\nid = \"PL1\" # favourites\nlocation = \"/music/a\"\n";
+        let new_item = NewItem {
+            id: "PL2",
+            name: Some("mix"),
+            location: "/music/mix",
+            format: Format::Audio,
+            save_playlist: true,
+        };
+        let added = add_to_document(config, &new_item).unwrap();
+        assert_eq!(
+            added,
+            format!(
+                "{}\n[[items]]\nid = \"PL2\"\nname = \"mix\"\nlocation = \"/music/mix\"\n\
+                 format = \"audio\"\nsave_playlist = true\n",
+                config
+            )
+        );
+        assert!(add_to_document(&added, &new_item).is_err());
+
+        let removed = remove_from_document(&added, "PL1").unwrap();
+        assert!(removed.starts_with("# My playlists\n[[items]]\nid = \"PL2\""));
+        assert!(remove_from_document(&removed, "PL1").is_err());
+        assert_eq!(remove_from_document(&removed, "mix").unwrap(), "");
+    }
+}
@@ -0,0 +1,57 @@
+mod archive;
+mod blacklist;
+mod bundle;
+mod cancel;
+mod catalog;
+mod chapters;
+pub mod cli;
+mod concurrency;
+mod config;
+mod cookies;
+mod daemon;
+mod downloader;
+mod du;
+mod dupes;
+mod entries;
+mod error;
+mod failure;
+mod fallback;
+mod hooks;
+mod i18n;
+mod items;
+mod logging;
+mod m3u;
+mod manifest;
+mod media;
+mod mirror;
+mod observer;
+mod organize;
+mod permissions;
+mod playlist;
+mod postprocess;
+mod preflight;
+mod probe;
+mod runner;
+mod setup;
+mod sidecar;
+mod state;
+mod stats;
+mod status;
+mod storage;
+mod sync;
+mod throttle;
+mod update;
+mod validate;
+mod verify;
+mod views;
+
+pub use cancel::CancelToken;
+pub use config::{load_config, Config, Format, Item, Kind, Mtime, Order};
+pub use error::SyncError;
+pub use failure::{Failure, FailureKind};
+pub use fallback::{Fallback, FallbackApi};
+pub use hooks::Hooks;
+pub use observer::Observer;
+pub use runner::{CommandRunner, RunOutput};
+pub use storage::{LocalStorage, Storage};
+pub use sync::{SyncReport, Syncer, Timings};
@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use indicatif_log_bridge::LogWrapper;
+use log::{Level, LevelFilter};
+
+// Send log records to standard error, printing them above the progress bars of the sync so
+// neither mangles the other, and return the progress bars to draw into. Records of yt-sync are
+// shown down to level, and those of the libraries it uses only when they are warnings or worse,
+// unless RUST_LOG says otherwise. Progress bars are hidden when only errors are shown.
+pub fn init(level: LevelFilter) -> MultiProgress {
+    let progress = MultiProgress::new();
+    if level < LevelFilter::Info {
+        progress.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    let logger = env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module("yt_sync", level)
+        .parse_env("RUST_LOG")
+        .format(|buf, record| match record.level() {
+            Level::Debug | Level::Trace => writeln!(
+                buf,
+                "[{}] {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            ),
+            _ => writeln!(buf, "{}", record.args()),
+        })
+        .build();
+    let max_level = logger.filter();
+    if LogWrapper::new(progress.clone(), logger).try_init().is_ok() {
+        log::set_max_level(max_level);
+    }
+    progress
+}
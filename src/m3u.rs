@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::path;
+
+use clap::ValueEnum;
+use encoding_rs::WINDOWS_1252;
+
+// The kind of playlist file written next to a location.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaylistFormat {
+    /// A plain list of paths.
+    #[default]
+    M3u,
+    /// Extended M3U in UTF-8, with the title of each track.
+    M3u8,
+    /// XML Shareable Playlist Format, with the title of each track.
+    Xspf,
+    /// The playlists of Winamp and many internet radio players, with the title of each track.
+    Pls,
+}
+
+impl PlaylistFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Xspf => "xspf",
+            PlaylistFormat::Pls => "pls",
+        }
+    }
+}
+
+// Which character separates directories in the paths written to m3u files.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Separator {
+    /// Whatever this platform uses, a backslash on Windows and a slash elsewhere.
+    #[default]
+    Native,
+    /// Slashes, which most players on any platform understand.
+    Slash,
+    /// Backslashes, for players on Windows that don't understand anything else.
+    Backslash,
+}
+
+impl Separator {
+    fn char(self) -> char {
+        match self {
+            Separator::Native => path::MAIN_SEPARATOR,
+            Separator::Slash => '/',
+            Separator::Backslash => '\\',
+        }
+    }
+}
+
+// Which character set m3u files are written in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    /// Windows-1252, for players that can't read anything else. Characters it doesn't have are
+    /// transliterated, so entries with them might not be found by the player.
+    Windows1252,
+}
+
+// How m3u files are written, for players that ignore plain UTF-8 files with Unix line endings,
+// as many car head units and old MP3 players do.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Encoding {
+    pub charset: Charset,
+    // Start the file with a UTF-8 byte order mark.
+    pub bom: bool,
+    pub crlf: bool,
+}
+
+const BOM: &str = "\u{feff}";
+
+impl Encoding {
+    // Read an m3u file written with this encoding, or by hand in UTF-8 with or without a BOM.
+    pub fn decode(&self, content: &[u8]) -> String {
+        let content = match self.charset {
+            Charset::Utf8 => String::from_utf8_lossy(content).into_owned(),
+            Charset::Windows1252 => WINDOWS_1252
+                .decode_without_bom_handling(content)
+                .0
+                .into_owned(),
+        };
+        match content.strip_prefix(BOM) {
+            Some(content) => content.to_string(),
+            None => content,
+        }
+    }
+
+    // Write out the contents of an m3u file, given with Unix line endings.
+    pub fn encode(&self, m3u: &str) -> Vec<u8> {
+        let mut text = String::new();
+        if self.bom && self.charset == Charset::Utf8 {
+            text.push_str(BOM);
+        }
+        for line in m3u.lines() {
+            text.push_str(&self.representable(line));
+            text.push_str(if self.crlf { "\r\n" } else { "\n" });
+        }
+        match self.charset {
+            Charset::Utf8 => text.into_bytes(),
+            Charset::Windows1252 => WINDOWS_1252.encode(&text).0.into_owned(),
+        }
+    }
+
+    // What a line reads back as once written in this encoding, with any characters the charset
+    // doesn't have transliterated to ASCII.
+    pub fn representable(&self, line: &str) -> String {
+        if self.charset == Charset::Utf8 {
+            return line.to_string();
+        }
+        let mut representable = String::new();
+        let mut buffer = [0; 4];
+        for c in line.chars() {
+            let (_, _, unmappable) = WINDOWS_1252.encode(c.encode_utf8(&mut buffer));
+            if !unmappable {
+                representable.push(c);
+            } else {
+                representable.push_str(deunicode::deunicode_char(c).unwrap_or("?"));
+            }
+        }
+        representable
+    }
+}
+
+// The path an m3u file lists a file of the location by, which may be in a directory inside it,
+// with every separator in the chosen style. Drive letters and UNC shares (\\server\share) keep their meaning either way, but the
+// \\?\ prefix Windows puts on extended-length paths is dropped, as players don't understand it.
+pub fn entry_path(location: &str, file_name: &str, separator: Separator) -> String {
+    let location = match location.strip_prefix(r"\\?\") {
+        Some(unc) if unc.starts_with("UNC\\") => format!(r"\\{}", &unc[4..]),
+        Some(local) => local.to_string(),
+        None => location.to_string(),
+    };
+    let separator = separator.char();
+    let mut entry = location.trim_end_matches(path::is_separator).to_string();
+    entry.push('/');
+    entry.push_str(file_name);
+    entry
+        .chars()
+        .map(|c| if path::is_separator(c) { separator } else { c })
+        .collect()
+}
+
+// Whether an m3u line lists a file of the location, whichever separators it was written with.
+fn is_entry(line: &str, location: &str) -> bool {
+    let normalize = |path: &str| path.replace('\\', "/");
+    let (line, location) = (
+        normalize(line),
+        normalize(&entry_path(location, "", Separator::Slash)),
+    );
+    line.starts_with(&location)
+}
+
+// Work out the new contents of an m3u file from its current contents and the lines the playlist
+// should have, given in order. Lines pointing into the location are ours and follow the playlist,
+// so new entries are inserted where they belong and removed ones are dropped. Any other line,
+// such as a comment or a track added by hand, is unknown and is kept after the entry it followed
+// when preserve_unknown is set, or dropped otherwise.
+pub fn merge(existing: &str, location: &str, desired: &[String], preserve_unknown: bool) -> String {
+    // Unknown lines grouped by the entry they follow, with None for those before any entry.
+    let mut unknown: Vec<(Option<&str>, Vec<&str>)> = vec![(None, Vec::new())];
+    if preserve_unknown {
+        for line in existing.lines() {
+            if is_entry(line, location) {
+                // Lines after an entry that is gone stay with the last entry still in the playlist.
+                if desired.iter().any(|entry| entry == line) {
+                    unknown.push((Some(line), Vec::new()));
+                }
+            } else {
+                unknown.last_mut().unwrap().1.push(line);
+            }
+        }
+    }
+
+    let mut merged = String::new();
+    let mut push_unknown = |merged: &mut String, after: Option<&str>| {
+        for (_, lines) in unknown.iter_mut().filter(|(entry, _)| *entry == after) {
+            for line in lines.drain(..) {
+                merged.push_str(line);
+                merged.push('\n');
+            }
+        }
+    };
+    push_unknown(&mut merged, None);
+    for entry in desired {
+        merged.push_str(entry);
+        merged.push('\n');
+        push_unknown(&mut merged, Some(entry));
+    }
+    merged
+}
+
+// Drop the #EXTM3U header and the #EXTINF lines of entries into the location from m3u contents,
+// as extend writes them afresh, so titles follow videos that were retitled.
+pub fn strip_extended(existing: &str, location: &str) -> String {
+    let lines: Vec<&str> = existing.lines().collect();
+    let mut stripped = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let describes_entry = line.starts_with("#EXTINF")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| is_entry(next, location));
+        if *line != "#EXTM3U" && !describes_entry {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+    stripped
+}
+
+// Make merged m3u contents extended M3U, with a #EXTINF line giving the title of each entry that
+// is in tracks, by its entry and title.
+pub fn extend(merged: &str, tracks: &[(String, String)]) -> String {
+    let titles: HashMap<&str, &str> = tracks
+        .iter()
+        .map(|(entry, title)| (entry.as_str(), title.as_str()))
+        .collect();
+    let mut extended = String::from("#EXTM3U\n");
+    for line in merged.lines() {
+        if let Some(title) = titles.get(line) {
+            extended.push_str(&format!("#EXTINF:-1,{}\n", title));
+        }
+        extended.push_str(line);
+        extended.push('\n');
+    }
+    extended
+}
+
+// Write an XSPF playlist of tracks, given by their entry and title. Entries become file URIs, or
+// relative URIs when they are relative paths.
+pub fn xspf(tracks: &[(String, String)]) -> String {
+    let mut xspf = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+    for (entry, title) in tracks {
+        xspf.push_str(&format!(
+            "    <track>\n      <location>{}</location>\n      <title>{}</title>\n    </track>\n",
+            escape_xml(&uri(entry)),
+            escape_xml(title)
+        ));
+    }
+    xspf.push_str("  </trackList>\n</playlist>\n");
+    xspf
+}
+
+// Write a PLS playlist of tracks, given by their entry and title.
+pub fn pls(tracks: &[(String, String)]) -> String {
+    let mut pls = String::from("[playlist]\n");
+    for (i, (entry, title)) in tracks.iter().enumerate() {
+        pls.push_str(&format!(
+            "File{0}={1}\nTitle{0}={2}\nLength{0}=-1\n",
+            i + 1,
+            entry,
+            title
+        ));
+    }
+    pls.push_str(&format!("NumberOfEntries={}\nVersion=2\n", tracks.len()));
+    pls
+}
+
+// The URI of a path, percent-encoding anything but unreserved characters and separators.
+// Absolute paths, including Windows ones with a drive letter, become file URIs.
+fn uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    let has_drive = path.as_bytes().get(1) == Some(&b':');
+    if path.starts_with('/') {
+        format!("file://{}", encoded)
+    } else if has_drive {
+        format!("file:///{}", encoded)
+    } else {
+        encoded
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_new_entries_around_manual_edits() {
+        let existing = "#EXTM3U\n/music/mix/One [a].opus\n# a favourite\n/music/mix/Two [b].opus\n/elsewhere/Extra.mp3\n";
+        let desired = [
+            "/music/mix/Zero [z].opus".to_string(),
+            "/music/mix/One [a].opus".to_string(),
+            "/music/mix/Three [c].opus".to_string(),
+        ];
+
+        assert_eq!(
+            merge(existing, "/music/mix", &desired, true),
+            "#EXTM3U\n/music/mix/Zero [z].opus\n/music/mix/One [a].opus\n# a favourite\n/elsewhere/Extra.mp3\n/music/mix/Three [c].opus\n"
+        );
+        assert_eq!(
+            merge(existing, "/music/mix", &desired, false),
+            "/music/mix/Zero [z].opus\n/music/mix/One [a].opus\n/music/mix/Three [c].opus\n"
+        );
+    }
+
+    #[test]
+    fn writes_titles_in_every_format() {
+        let tracks = [
+            (
+                "/music/mix/One [a].opus".to_string(),
+                "One & Only".to_string(),
+            ),
+            ("mix/Two [b].opus".to_string(), "Two".to_string()),
+        ];
+        let existing = "#EXTM3U\n#EXTINF:-1,Old title\n/music/mix/One [a].opus\n#EXTINF:-1,Mine\n/elsewhere/Extra.mp3\n";
+        let stripped = strip_extended(existing, "/music/mix");
+        assert_eq!(
+            stripped,
+            "/music/mix/One [a].opus\n#EXTINF:-1,Mine\n/elsewhere/Extra.mp3\n"
+        );
+        assert_eq!(
+            extend(&stripped, &tracks),
+            "#EXTM3U\n#EXTINF:-1,One & Only\n/music/mix/One [a].opus\n#EXTINF:-1,Mine\n/elsewhere/Extra.mp3\n"
+        );
+
+        let xspf = xspf(&tracks);
+        assert!(xspf.contains("<location>file:///music/mix/One%20%5Ba%5D.opus</location>"));
+        assert!(xspf.contains("<title>One &amp; Only</title>"));
+        assert!(xspf.contains("<location>mix/Two%20%5Bb%5D.opus</location>"));
+        assert_eq!(uri(r"C:\Music\One.opus"), "file:///C:/Music/One.opus");
+
+        assert_eq!(
+            pls(&tracks[1..]),
+            "[playlist]\nFile1=mix/Two [b].opus\nTitle1=Two\nLength1=-1\nNumberOfEntries=1\nVersion=2\n"
+        );
+    }
+
+    #[test]
+    fn writes_entries_with_the_chosen_separator() {
+        assert_eq!(
+            entry_path("/music/mix/", "One [a].opus", Separator::Slash),
+            "/music/mix/One [a].opus"
+        );
+        assert_eq!(
+            entry_path("/music/mix", "One [a].opus", Separator::Backslash),
+            r"\music\mix\One [a].opus"
+        );
+        assert_eq!(
+            entry_path(r"\\?\UNC\nas\music", "One [a].opus", Separator::Backslash),
+            r"\\nas\music\One [a].opus"
+        );
+        // Entries written with other separators are still recognised as ours.
+        assert!(is_entry(r"\music\mix\One [a].opus", "/music/mix"));
+        assert!(!is_entry("/music/mixtape/One [a].opus", "/music/mix"));
+    }
+
+    #[test]
+    fn encodes_for_legacy_players() {
+        let encoding = Encoding {
+            charset: Charset::Windows1252,
+            bom: false,
+            crlf: true,
+        };
+        let encoded = encoding.encode("/music/mix/Café Łódź [a].opus\n");
+        assert_eq!(encoded, b"/music/mix/Caf\xe9 L\xf3dz [a].opus\r\n");
+        assert_eq!(
+            encoding.decode(&encoded),
+            "/music/mix/Café Lódz [a].opus\r\n"
+        );
+
+        let encoding = Encoding {
+            bom: true,
+            ..Encoding::default()
+        };
+        let encoded = encoding.encode("/music/mix/Łódź [a].opus\n");
+        assert!(encoded.starts_with(b"\xef\xbb\xbf/music"));
+        assert_eq!(encoding.decode(&encoded), "/music/mix/Łódź [a].opus\n");
+    }
+}
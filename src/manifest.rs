@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::location_files;
+use crate::i18n::tr;
+use crate::sidecar;
+use crate::state::{Change, State};
+use crate::storage::Storage;
+
+// The name of the manifest kept in each location.
+pub const FILE_NAME: &str = ".yt-sync.json";
+
+// The videos downloaded to a location, by video ID, kept in a .yt-sync.json file in it. This is
+// what tells which videos are downloaded, rather than file names, which change with yt-dlp's
+// naming and when videos are retitled.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+    pub videos: BTreeMap<String, Video>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Video {
+    pub title: String,
+    // The path of the file inside the location, with / between directories.
+    pub path: String,
+    // The format of the item the video was downloaded for, such as "audio".
+    pub format: String,
+    // In seconds since the Unix epoch.
+    pub downloaded_at: u64,
+    // The files yt-dlp split the video into, one for each chapter, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    // The path of the file inside the location, like the path of its video.
+    pub path: String,
+}
+
+impl Manifest {
+    fn path(location: &str) -> PathBuf {
+        Path::new(location).join(FILE_NAME)
+    }
+
+    // Load the manifest of a location, leaving out videos and chapters whose files are gone. A
+    // location synced before it had a manifest gets one listing the downloads yt-dlp named and the
+    // organized files, taking audio formats as downloaded for "audio" and others for "video".
+    pub fn load(storage: &dyn Storage, location: &str, state: &State) -> io::Result<Manifest> {
+        let mut manifest = match storage.read(&Manifest::path(location)) {
+            Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    tr!(
+                        "manifest-invalid",
+                        path = Manifest::path(location).display().to_string(),
+                        error = e.to_string()
+                    ),
+                )
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut manifest = Manifest::default();
+                let files = match location_files(storage, location, state) {
+                    Ok(files) => files,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+                    Err(e) => return Err(e),
+                };
+                for (video_id, title, path) in files {
+                    let modified = storage.modified(&Path::new(location).join(&path));
+                    let format = match Path::new(&path).extension() {
+                        Some(ext)
+                            if ["opus", "m4a", "mp3", "flac", "ogg", "wav"]
+                                .iter()
+                                .any(|audio| ext == *audio) =>
+                        {
+                            "audio"
+                        }
+                        _ => "video",
+                    };
+                    let video = Video {
+                        title,
+                        path,
+                        format: format.to_string(),
+                        downloaded_at: seconds(modified.unwrap_or_else(|_| SystemTime::now())),
+                        chapters: Vec::new(),
+                    };
+                    manifest.videos.insert(video_id, video);
+                }
+                manifest
+            }
+            Err(e) => return Err(e),
+        };
+        let exists = |path: &str| storage.size(&Path::new(location).join(path)).is_ok();
+        manifest.videos.retain(|_, video| {
+            video.chapters.retain(|chapter| exists(&chapter.path));
+            exists(&video.path)
+        });
+        Ok(manifest)
+    }
+
+    // Write the manifest under a temporary name and move it into place, so the old one stays
+    // intact until it is complete.
+    pub fn save(&self, storage: &dyn Storage, location: &str) -> io::Result<()> {
+        let path = Manifest::path(location);
+        let temporary = path.with_extension("json.tmp");
+        storage.write(&temporary, &serde_json::to_vec_pretty(self)?)?;
+        storage.rename(&temporary, &path)
+    }
+
+    // The path of a video downloaded in a format, if it is in the manifest.
+    pub fn find(&self, video_id: &str, format: &str) -> Option<&str> {
+        let video = self.videos.get(video_id)?;
+        (video.format == format).then_some(video.path.as_str())
+    }
+
+    // Record a video that was just downloaded.
+    pub fn insert(&mut self, video_id: &str, title: &str, path: &str, format: &str) {
+        let video = Video {
+            title: title.to_string(),
+            path: path.to_string(),
+            format: format.to_string(),
+            downloaded_at: seconds(SystemTime::now()),
+            chapters: Vec::new(),
+        };
+        self.videos.insert(video_id.to_string(), video);
+    }
+
+    // How much disk space the files of the videos take up in the location, chapters included.
+    pub fn size(&self, storage: &dyn Storage, location: &str) -> u64 {
+        let location = Path::new(location);
+        (self.videos.values())
+            .flat_map(|video| {
+                let chapters = video.chapters.iter().map(|chapter| &chapter.path);
+                std::iter::once(&video.path).chain(chapters)
+            })
+            .map(|path| storage.size(&location.join(path)).unwrap_or(0))
+            .sum()
+    }
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// The files yt-sync created in a location, as paths inside it with / between directories. For a
+// location synced before files were tracked, these are the downloads yt-dlp named and the
+// organized files, with their sidecars.
+pub fn managed(
+    storage: &dyn Storage,
+    location: &str,
+    state: &State,
+) -> io::Result<BTreeSet<String>> {
+    if let Some(managed) = state.managed.get(location) {
+        return Ok(managed.clone());
+    }
+    let mut managed = BTreeSet::new();
+    for (_, _, path) in location_files(storage, location, state)? {
+        let sidecar = sidecar::name(&path);
+        if storage.size(&Path::new(location).join(&sidecar)).is_ok() {
+            managed.insert(sidecar);
+        }
+        managed.insert(path);
+    }
+    Ok(managed)
+}
+
+// Start tracking the files of a location that was synced before files were tracked.
+pub fn track(
+    storage: &dyn Storage,
+    location: &str,
+    state: &mut State,
+    data_dir: &Path,
+) -> io::Result<()> {
+    if state.managed.contains_key(location) {
+        return Ok(());
+    }
+    let paths = match managed(storage, location, state) {
+        Ok(managed) => managed.into_iter().collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let location = location.to_string();
+    state.record(storage, data_dir, Change::Managed { location, paths })
+}
+
+// Collect the files under a directory by their path inside it. Hidden files, the partial files of
+// downloads still to finish, and symlinks, such as those of views, are left out.
+fn files(
+    storage: &dyn Storage,
+    dir: &Path,
+    inside: &str,
+    found: &mut Vec<String>,
+) -> io::Result<()> {
+    let mut names = storage.scan(&dir.join(inside))?;
+    names.sort();
+    for name in names {
+        let path = match inside.is_empty() {
+            true => name.clone(),
+            false => format!("{}/{}", inside, name),
+        };
+        let partial =
+            name.ends_with(".part") || name.contains(".part-Frag") || name.ends_with(".ytdl");
+        if name.starts_with('.') || partial || storage.read_link(&dir.join(&path)).is_ok() {
+            continue;
+        }
+        match storage.scan(&dir.join(&path)) {
+            Ok(_) => files(storage, dir, &path, found)?,
+            Err(_) => found.push(path),
+        }
+    }
+    Ok(())
+}
+
+// The files in a location that yt-sync didn't create, such as ones added by hand.
+pub fn orphans(storage: &dyn Storage, location: &str, state: &State) -> io::Result<Vec<String>> {
+    let managed = managed(storage, location, state)?;
+    let mut found = Vec::new();
+    files(storage, Path::new(location), "", &mut found)?;
+    found.retain(|path| !managed.contains(path));
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn tells_managed_files_from_orphans() {
+        let storage = MemoryStorage::default();
+        for path in [
+            "/music/mix/One [a].opus",
+            "/music/mix/One [a].opus.yt-sync.json",
+            "/music/mix/cover.jpg",
+            "/music/mix/Live/Bootleg.mp3",
+            "/music/mix/.m3u.tmp",
+            "/music/mix/Two [b].webm.part",
+        ] {
+            storage.write(Path::new(path), b"").unwrap();
+        }
+        let mut state = State::default();
+        assert_eq!(
+            orphans(&storage, "/music/mix", &state).unwrap(),
+            ["Live/Bootleg.mp3", "cover.jpg"]
+        );
+
+        let data_dir = Path::new("/data");
+        track(&storage, "/music/mix", &mut state, data_dir).unwrap();
+        let paths = vec!["Live/Bootleg.mp3".to_string()];
+        let location = "/music/mix".to_string();
+        let change = Change::Managed { location, paths };
+        state.record(&storage, data_dir, change).unwrap();
+        let state = State::load(&storage, data_dir).unwrap();
+        assert_eq!(
+            orphans(&storage, "/music/mix", &state).unwrap(),
+            ["cover.jpg"]
+        );
+    }
+
+    #[test]
+    fn finds_downloads_by_video_id() {
+        let storage = MemoryStorage::default();
+        for path in [
+            "/music/mix/One [a].opus",
+            "/music/mix/Two [b].mkv",
+            "/music/mix/cover.jpg",
+        ] {
+            storage.write(Path::new(path), b"").unwrap();
+        }
+        let state = State::default();
+        let mut manifest = Manifest::load(&storage, "/music/mix", &state).unwrap();
+        assert_eq!(manifest.find("a", "audio"), Some("One [a].opus"));
+        assert_eq!(manifest.find("b", "audio"), None);
+        assert_eq!(manifest.find("b", "video"), Some("Two [b].mkv"));
+        assert_eq!(manifest.videos.len(), 2);
+
+        manifest.insert("c", "Three", "Renamed [c].opus", "audio");
+        storage
+            .write(Path::new("/music/mix/Renamed [c].opus"), b"")
+            .unwrap();
+        manifest.save(&storage, "/music/mix").unwrap();
+        storage
+            .delete(Path::new("/music/mix/One [a].opus"))
+            .unwrap();
+        let manifest = Manifest::load(&storage, "/music/mix", &state).unwrap();
+        assert_eq!(manifest.find("a", "audio"), None);
+        assert_eq!(manifest.videos["c"].title, "Three");
+    }
+}
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use crate::config::Item;
+use crate::cookies::Cookies;
+use crate::i18n::tr;
+
+// The audio formats yt-dlp can extract, with the extension of the files it writes for each.
+const AUDIO_FORMATS: [(&str, &str); 6] = [
+    ("opus", "opus"),
+    ("mp3", "mp3"),
+    ("m4a", "m4a"),
+    ("flac", "flac"),
+    ("vorbis", "ogg"),
+    ("wav", "wav"),
+];
+
+// The containers yt-dlp can merge video and audio into.
+const CONTAINERS: [&str; 4] = ["mkv", "mp4", "webm", "mov"];
+
+// How the videos of a playlist are downloaded, from the format, audio_format, video_quality,
+// container and yt_dlp_args of its item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFormat {
+    // "audio" or "video", which the manifest records downloads under.
+    pub name: &'static str,
+    // The audio format extracted from videos, when only keeping the audio.
+    pub audio_format: String,
+    // The tallest video downloaded, in pixels.
+    pub max_height: Option<u32>,
+    // What video and audio are merged into, when keeping the video.
+    pub container: String,
+    // Further arguments given to yt-dlp as they are, after those of yt-sync.
+    pub yt_dlp_args: Vec<String>,
+    // The yt-dlp download archive that downloads are recorded in.
+    pub download_archive: Option<String>,
+    // Whether videos are split into a file for each chapter.
+    pub split_chapters: bool,
+    // The album downloads are tagged with, when they are tagged with track numbers.
+    pub album: Option<String>,
+    // The track number of the video being downloaded, its position in the playlist.
+    pub track: Option<usize>,
+    // The cookies yt-dlp signs in with. These are the item's own, which a sync falls back from to
+    // those of the config.
+    pub cookies: Cookies,
+}
+
+impl MediaFormat {
+    // The format of an item, which is opus audio or the best video in mkv unless it says
+    // otherwise.
+    pub fn of(item: &Item) -> Result<MediaFormat, String> {
+        let audio_format = item.audio_format.as_deref().unwrap_or("opus");
+        if !AUDIO_FORMATS.iter().any(|(name, _)| *name == audio_format) {
+            return Err(tr!(
+                "audio-format-unknown",
+                value = audio_format,
+                choices = AUDIO_FORMATS.map(|(name, _)| name).join(", ")
+            ));
+        }
+        let container = item.container.as_deref().unwrap_or("mkv");
+        if !CONTAINERS.contains(&container) {
+            return Err(tr!(
+                "container-unknown",
+                value = container,
+                choices = CONTAINERS.join(", ")
+            ));
+        }
+        let max_height = match &item.video_quality {
+            Some(quality) => Some(
+                quality
+                    .strip_suffix('p')
+                    .unwrap_or(quality)
+                    .parse()
+                    .map_err(|_| tr!("video-quality-invalid", value = quality.as_str()))?,
+            ),
+            None => None,
+        };
+        if item.split_chapters && item.organize.is_some() {
+            return Err(tr!("split-chapters-with-organize"));
+        }
+        // The album is the playlist's name, which the location is usually named after too.
+        let album = item.track_numbers.then(|| match &item.name {
+            Some(name) => name.clone(),
+            None => Path::new(&item.location)
+                .file_name()
+                .map_or(item.id.clone(), |name| name.to_string_lossy().into_owned()),
+        });
+        Ok(MediaFormat {
+            name: item.format.name(),
+            audio_format: audio_format.to_string(),
+            max_height,
+            container: container.to_string(),
+            yt_dlp_args: item.yt_dlp_args.clone(),
+            download_archive: item.download_archive.clone(),
+            split_chapters: item.split_chapters,
+            album,
+            track: None,
+            cookies: Cookies::default().for_item(item),
+        })
+    }
+
+    pub fn is_audio(&self) -> bool {
+        self.name == "audio"
+    }
+
+    // The extension of the files downloaded in this format.
+    pub fn extension(&self) -> &str {
+        match self.is_audio() {
+            true => AUDIO_FORMATS
+                .iter()
+                .find(|(name, _)| *name == self.audio_format)
+                .map_or("opus", |(_, extension)| extension),
+            false => &self.container,
+        }
+    }
+
+    // The format selection yt-dlp downloads video with.
+    pub fn video_selection(&self) -> String {
+        match self.max_height {
+            Some(height) => format!("bestvideo[height<={}]+bestaudio", height),
+            None => "bestvideo+bestaudio".to_string(),
+        }
+    }
+
+    // The arguments tagging a download with its track number and album. They are given to the
+    // ffmpeg run that embeds metadata, after the tags of yt-dlp, so they win.
+    pub fn tag_args(&self) -> Vec<String> {
+        let (Some(album), Some(track)) = (&self.album, self.track) else {
+            return Vec::new();
+        };
+        // yt-dlp splits these arguments like a POSIX shell.
+        let album = format!("'{}'", album.replace('\'', r"'\''"));
+        vec![
+            "--postprocessor-args".to_string(),
+            format!(
+                "Metadata:-metadata track={} -metadata album={}",
+                track, album
+            ),
+        ]
+    }
+
+    // Whether yt-dlp can embed thumbnails in files of this format.
+    pub fn embeds_thumbnail(&self) -> bool {
+        !["wav", "webm"].contains(&self.extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{create_default_config, Format};
+
+    #[test]
+    fn reads_formats_of_items() {
+        let mut item = create_default_config().items.remove(0);
+        let format = MediaFormat::of(&item).unwrap();
+        assert_eq!(format.extension(), "opus");
+
+        item.audio_format = Some("vorbis".to_string());
+        assert_eq!(MediaFormat::of(&item).unwrap().extension(), "ogg");
+        item.audio_format = Some("aiff".to_string());
+        assert!(MediaFormat::of(&item).is_err());
+
+        item.format = Format::Video;
+        item.audio_format = None;
+        item.container = Some("mp4".to_string());
+        item.video_quality = Some("1080p".to_string());
+        let format = MediaFormat::of(&item).unwrap();
+        assert_eq!(format.extension(), "mp4");
+        assert_eq!(
+            format.video_selection(),
+            "bestvideo[height<=1080]+bestaudio"
+        );
+        item.video_quality = Some("HD".to_string());
+        assert!(MediaFormat::of(&item).is_err());
+
+        item.video_quality = None;
+        item.track_numbers = true;
+        item.location = "/music/Rock 'n' Roll".to_string();
+        let mut format = MediaFormat::of(&item).unwrap();
+        assert!(format.tag_args().is_empty());
+        format.track = Some(3);
+        assert_eq!(
+            format.tag_args()[1],
+            r"Metadata:-metadata track=3 -metadata album='Rock '\''n'\'' Roll'"
+        );
+        item.split_chapters = true;
+        item.organize = Some("{artist}/{title}.{ext}".to_string());
+        assert!(MediaFormat::of(&item).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+
+use crate::storage::Storage;
+
+// What mirroring changed, as paths inside the mirror.
+#[derive(Debug, Default, PartialEq)]
+pub struct Mirrored {
+    pub copied: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+// Make a mirror identical to the location it mirrors, going by the files yt-sync created in each,
+// so files added to either by hand are left alone. Files the mirror is missing, or that differ in
+// size, are copied over, and files yt-sync copied to it that are gone from the location are
+// deleted. A dry run only finds out what would change.
+pub fn update(
+    storage: &dyn Storage,
+    location: &Path,
+    managed: &BTreeSet<String>,
+    mirror: &Path,
+    mirrored: &BTreeSet<String>,
+    dry_run: bool,
+) -> io::Result<Mirrored> {
+    if !dry_run {
+        storage.create_dir(mirror)?;
+    }
+    let mut changes = Mirrored::default();
+    for path in managed {
+        let (from, to) = (location.join(path), mirror.join(path));
+        let Ok(size) = storage.size(&from) else {
+            continue;
+        };
+        if storage.size(&to).ok() == Some(size) {
+            // Files that were already there, such as copies made by hand, are yt-sync's now.
+            if !mirrored.contains(path) {
+                changes.copied.push(path.clone());
+            }
+            continue;
+        }
+        if dry_run {
+            changes.copied.push(path.clone());
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            storage.create_dir(parent)?;
+        }
+        let _ = storage.delete(&to);
+        storage.copy(&from, &to)?;
+        changes.copied.push(path.clone());
+    }
+    for path in mirrored {
+        if dry_run && !managed.contains(path) {
+            changes.deleted.push(path.clone());
+        } else if !managed.contains(path) {
+            match storage.delete(&mirror.join(path)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => changes.deleted.push(path.clone()),
+            }
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn keeps_mirrors_identical() {
+        let storage = MemoryStorage::default();
+        for (path, content) in [
+            ("/music/mix/One [a].opus", "one"),
+            ("/music/mix/One [a].opus.yt-sync.json", "{}"),
+            ("/music/mix/Artist/Two.opus", "two"),
+            ("/music/mix/cover.jpg", "mine"),
+            ("/usb/mix/Two [b].opus", "old"),
+            ("/usb/mix/Artist/Two.opus", "tw"),
+            ("/usb/mix/Notes.txt", "mine too"),
+        ] {
+            storage.write(Path::new(path), content.as_bytes()).unwrap();
+        }
+        let set = |paths: &[&str]| paths.iter().map(|path| path.to_string()).collect();
+        let managed = set(&[
+            "One [a].opus",
+            "One [a].opus.yt-sync.json",
+            "Artist/Two.opus",
+        ]);
+        let mirrored = set(&["Two [b].opus", "Artist/Two.opus"]);
+
+        let (location, mirror) = (Path::new("/music/mix"), Path::new("/usb/mix"));
+        let planned = update(&storage, location, &managed, mirror, &mirrored, true).unwrap();
+        assert!(storage.read(Path::new("/usb/mix/Two [b].opus")).is_ok());
+        let changes = update(&storage, location, &managed, mirror, &mirrored, false).unwrap();
+        assert_eq!(planned, changes);
+        assert_eq!(changes.copied.len(), 3);
+        assert_eq!(changes.deleted, ["Two [b].opus"]);
+        assert_eq!(
+            storage.read(Path::new("/usb/mix/Artist/Two.opus")).unwrap(),
+            b"two"
+        );
+        assert!(storage.read(Path::new("/usb/mix/cover.jpg")).is_err());
+        assert!(storage.read(Path::new("/usb/mix/Notes.txt")).is_ok());
+        let changes = update(&storage, location, &managed, mirror, &managed, false).unwrap();
+        assert_eq!(changes, Mirrored::default());
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+
+use crate::config::format_size;
+use crate::error::SyncError;
+use crate::i18n::tr;
+use crate::stats::table;
+use crate::sync::SyncReport;
+
+// Receives events while playlists are synced, so embedders can render progress their own way.
+// Every method does nothing by default.
+pub trait Observer: Sync {
+    // A playlist is about to be synced.
+    fn on_playlist_start(&self, _playlist_id: &str) {}
+
+    // A playlist has been listed completely.
+    fn on_listing(&self, _playlist_id: &str, _video_count: usize) {}
+
+    // A video has started downloading. Its index is where it is in the playlist, counting from 0.
+    fn on_video_start(&self, _video_id: &str, _index: usize, _file_name: &str) {}
+
+    // A video has finished downloading, successfully or not.
+    fn on_video_done(&self, _video_id: &str, _success: bool) {}
+
+    // Something went wrong that doesn't stop the sync.
+    fn on_error(&self, _message: &str) {}
+
+    // Detail only worth showing in verbose output.
+    fn on_detail(&self, _message: &str) {}
+
+    // Something worth knowing about the run as a whole, such as that it stopped at its budget.
+    fn on_notice(&self, _message: &str) {}
+
+    // YouTube is throttling us, so requests are paused for a cooldown and then spaced out by delay.
+    fn on_throttled(&self, _cooldown: Duration, _delay: Duration) {}
+
+    // A playlist has been synced.
+    fn on_playlist_done(&self, _report: &SyncReport) {}
+
+    // Every playlist of the run has been synced, or as many as the run's limits allowed.
+    fn on_run_done(&self) {}
+}
+
+// Lines for finished videos, held back until every started video before them in the playlist has
+// finished too, so they come out in playlist order no matter which finishes first.
+#[derive(Default)]
+struct OrderedLines {
+    // The video IDs and file names of videos whose lines are still to be printed, by their index
+    // in the playlist, along with whether they succeeded once they finish.
+    started: BTreeMap<usize, (String, String, Option<bool>)>,
+}
+
+impl OrderedLines {
+    fn start(&mut self, video_id: &str, index: usize, file_name: &str) {
+        self.started
+            .insert(index, (video_id.to_string(), file_name.to_string(), None));
+    }
+
+    // Record that a video finished, returning the lines that are ready to print.
+    fn finish(&mut self, video_id: &str, success: bool) -> Vec<String> {
+        if let Some((_, _, outcome)) = self
+            .started
+            .values_mut()
+            .find(|(id, _, outcome)| id == video_id && outcome.is_none())
+        {
+            *outcome = Some(success);
+        }
+        let mut lines = Vec::new();
+        while let Some((_, (_, file_name, Some(success)))) = self.started.first_key_value() {
+            lines.push(match success {
+                true => tr!("video-downloaded", file = file_name.as_str()),
+                false => tr!("video-failed", file = file_name.as_str()),
+            });
+            self.started.pop_first();
+        }
+        lines
+    }
+}
+
+// Reports progress on the terminal, with a progress bar for each playlist. Everything else is
+// logged, which prints above the progress bars so they stay in place, along with a line for each
+// finished video in playlist order.
+pub struct ConsoleObserver {
+    bars: MultiProgress,
+    progress: Mutex<ProgressBar>,
+    lines: Mutex<OrderedLines>,
+    summary: Mutex<Vec<SummaryRow>>,
+}
+
+// What happened to a playlist, for the table printed at the end of a run.
+struct SummaryRow {
+    playlist_id: String,
+    location: String,
+    downloaded: usize,
+    // Videos left for later, for being unavailable or for the run reaching its deadline.
+    skipped: usize,
+    failed: usize,
+    // Files pruned or removed to keep only the newest videos.
+    pruned: usize,
+    bytes_downloaded: u64,
+    location_bytes: u64,
+    // Whether the playlist couldn't be synced at all.
+    error: bool,
+}
+
+impl SummaryRow {
+    fn failed(report: &SyncReport) -> SummaryRow {
+        SummaryRow {
+            playlist_id: report.playlist_id.clone(),
+            location: report.location.clone(),
+            downloaded: 0,
+            skipped: 0,
+            failed: 0,
+            pruned: 0,
+            bytes_downloaded: 0,
+            location_bytes: 0,
+            error: true,
+        }
+    }
+}
+
+impl ConsoleObserver {
+    // Draw progress bars into bars, which logging knows to print around.
+    pub fn new(bars: MultiProgress) -> Self {
+        ConsoleObserver {
+            bars,
+            progress: Mutex::new(ProgressBar::hidden()),
+            lines: Mutex::default(),
+            summary: Mutex::default(),
+        }
+    }
+}
+
+impl Observer for ConsoleObserver {
+    fn on_playlist_start(&self, playlist_id: &str) {
+        info!("{}", tr!("playlist-start", playlist = playlist_id));
+        *self.progress.lock().unwrap() = self.bars.add(ProgressBar::new(0));
+    }
+
+    fn on_video_start(&self, video_id: &str, index: usize, file_name: &str) {
+        self.progress.lock().unwrap().inc_length(1);
+        self.lines.lock().unwrap().start(video_id, index, file_name);
+    }
+
+    fn on_video_done(&self, video_id: &str, success: bool) {
+        for line in self.lines.lock().unwrap().finish(video_id, success) {
+            info!("{}", line);
+        }
+        self.progress.lock().unwrap().inc(1);
+    }
+
+    fn on_error(&self, message: &str) {
+        error!("{}", message);
+    }
+
+    fn on_detail(&self, message: &str) {
+        debug!("{}", message);
+    }
+
+    fn on_notice(&self, message: &str) {
+        info!("{}", message);
+    }
+
+    fn on_throttled(&self, cooldown: Duration, delay: Duration) {
+        warn!(
+            "{}",
+            tr!(
+                "throttled",
+                cooldown = cooldown.as_secs(),
+                delay = delay.as_secs()
+            )
+        );
+    }
+
+    fn on_playlist_done(&self, report: &SyncReport) {
+        let progress = self.progress.lock().unwrap();
+        progress.finish();
+        self.bars.remove(&progress);
+        self.lines.lock().unwrap().started.clear();
+        if let Some(error) = &report.error {
+            error!("{}", error);
+            self.summary
+                .lock()
+                .unwrap()
+                .push(SummaryRow::failed(report));
+            return;
+        }
+        info!(
+            "{}",
+            tr!(
+                "synced",
+                count = report.downloaded,
+                location = report.location.as_str()
+            )
+        );
+        if report.deferred > 0 {
+            info!("{}", tr!("deferred", count = report.deferred));
+        }
+        if report.resumed > 0 {
+            info!("{}", tr!("resumed", count = report.resumed));
+        }
+        if report.removed > 0 && !report.dry_run {
+            info!("{}", tr!("removed", count = report.removed));
+        }
+        if report.dry_run {
+            for path in &report.pruned {
+                info!("{}", tr!("would-prune", file = path.as_str()));
+            }
+        } else if !report.pruned.is_empty() {
+            info!("{}", tr!("pruned", count = report.pruned.len()));
+        }
+        if report.skipped_unavailable > 0 {
+            info!(
+                "{}",
+                tr!("skipped-unavailable", count = report.skipped_unavailable)
+            );
+        }
+        if let Some(timings) = &report.timings {
+            info!(
+                "{}",
+                tr!(
+                    "timings",
+                    listing = format!("{:.1?}", timings.listing),
+                    scanning = format!("{:.1?}", timings.scanning),
+                    downloading = format!("{:.1?}", timings.downloading),
+                    post_processing = format!("{:.1?}", timings.post_processing),
+                    writing_playlist = format!("{:.1?}", timings.writing_playlist)
+                )
+            );
+        }
+        for failure in &report.failures {
+            let video = failure.video_id.as_str();
+            let reason = failure.kind.to_string();
+            match &failure.log {
+                Some(log) => warn!(
+                    "{}",
+                    tr!(
+                        "failed-with-log",
+                        video = video,
+                        reason = reason,
+                        log = log.display().to_string()
+                    )
+                ),
+                None => warn!("{}", tr!("failed", video = video, reason = reason)),
+            }
+        }
+        self.summary.lock().unwrap().push(SummaryRow {
+            playlist_id: report.playlist_id.clone(),
+            location: report.location.clone(),
+            downloaded: report.downloaded,
+            skipped: report.skipped_unavailable + report.deferred,
+            failed: report.failures.len(),
+            pruned: report.pruned.len() + report.removed,
+            bytes_downloaded: report.bytes_downloaded,
+            location_bytes: report.location_bytes,
+            error: false,
+        });
+    }
+
+    // Print a table of what happened to every playlist, with totals. Playlists sharing a location
+    // count its disk usage once.
+    fn on_run_done(&self) {
+        let summary = std::mem::take(&mut *self.summary.lock().unwrap());
+        if summary.is_empty() {
+            return;
+        }
+        let headers = [
+            "summary-playlist",
+            "summary-new",
+            "summary-skipped",
+            "summary-failed",
+            "summary-pruned",
+            "summary-downloaded",
+            "summary-on-disk",
+        ];
+        let mut rows = vec![headers.iter().map(|id| tr!(id)).collect()];
+        for row in &summary {
+            if row.error {
+                let dash = || "-".to_string();
+                let failed = tr!("summary-error");
+                rows.push(vec![
+                    row.playlist_id.clone(),
+                    dash(),
+                    dash(),
+                    failed,
+                    dash(),
+                    dash(),
+                    dash(),
+                ]);
+                continue;
+            }
+            rows.push(vec![
+                row.playlist_id.clone(),
+                row.downloaded.to_string(),
+                row.skipped.to_string(),
+                row.failed.to_string(),
+                row.pruned.to_string(),
+                format_size(row.bytes_downloaded),
+                format_size(row.location_bytes),
+            ]);
+        }
+        let mut locations = HashSet::new();
+        let on_disk = (summary.iter())
+            .filter(|row| locations.insert(&row.location))
+            .map(|row| row.location_bytes)
+            .sum();
+        rows.push(vec![
+            tr!("summary-total"),
+            summary
+                .iter()
+                .map(|row| row.downloaded)
+                .sum::<usize>()
+                .to_string(),
+            summary
+                .iter()
+                .map(|row| row.skipped)
+                .sum::<usize>()
+                .to_string(),
+            summary
+                .iter()
+                .map(|row| row.failed)
+                .sum::<usize>()
+                .to_string(),
+            summary
+                .iter()
+                .map(|row| row.pruned)
+                .sum::<usize>()
+                .to_string(),
+            format_size(summary.iter().map(|row| row.bytes_downloaded).sum()),
+            format_size(on_disk),
+        ]);
+        for line in table(&rows) {
+            info!("{}", line);
+        }
+    }
+}
+
+// What happened to a playlist, as written by --json.
+#[derive(Serialize, Debug, PartialEq)]
+struct ItemSummary {
+    playlist_id: String,
+    location: String,
+    downloaded: usize,
+    // New videos not attempted, as they have been unavailable in several runs.
+    skipped_unavailable: usize,
+    deferred: usize,
+    resumed: usize,
+    removed: usize,
+    pruned: Vec<String>,
+    failed: Vec<FailedVideo>,
+    hook_failures: Vec<String>,
+    low_on_space: bool,
+    // Why the playlist couldn't be synced at all.
+    error: Option<String>,
+    bytes_downloaded: u64,
+    // How much disk space the videos in the location take up.
+    location_bytes: u64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct FailedVideo {
+    video_id: String,
+    reason: String,
+    log: Option<PathBuf>,
+}
+
+// The result of a whole run, as written by --json.
+#[derive(Serialize, Debug)]
+struct Summary<'a> {
+    items: &'a [ItemSummary],
+    dry_run: bool,
+    exit_code: u8,
+    error: Option<String>,
+}
+
+// Reports a sync for scripts and monitoring, with --json. Nothing is written to standard output
+// until the run ends with a JSON summary of every playlist synced, while errors and notices are
+// logged to standard error as they happen.
+pub struct JsonObserver {
+    items: Mutex<Vec<ItemSummary>>,
+    dry_run: bool,
+}
+
+impl JsonObserver {
+    pub fn new(dry_run: bool) -> Self {
+        JsonObserver {
+            items: Mutex::default(),
+            dry_run,
+        }
+    }
+
+    // The summary of the run, given how it ended.
+    pub fn summary(&self, error: Option<&SyncError>) -> String {
+        let items = self.items.lock().unwrap();
+        let summary = Summary {
+            items: &items,
+            dry_run: self.dry_run,
+            exit_code: error.map_or(0, SyncError::exit_code),
+            error: error.map(|e| e.to_string()),
+        };
+        serde_json::to_string(&summary).unwrap_or_default()
+    }
+}
+
+impl Observer for JsonObserver {
+    fn on_error(&self, message: &str) {
+        error!("{}", message);
+    }
+
+    fn on_detail(&self, message: &str) {
+        debug!("{}", message);
+    }
+
+    fn on_notice(&self, message: &str) {
+        info!("{}", message);
+    }
+
+    fn on_throttled(&self, cooldown: Duration, delay: Duration) {
+        warn!(
+            "{}",
+            tr!(
+                "throttled",
+                cooldown = cooldown.as_secs(),
+                delay = delay.as_secs()
+            )
+        );
+    }
+
+    fn on_playlist_done(&self, report: &SyncReport) {
+        self.items.lock().unwrap().push(ItemSummary {
+            playlist_id: report.playlist_id.clone(),
+            location: report.location.clone(),
+            downloaded: report.downloaded,
+            skipped_unavailable: report.skipped_unavailable,
+            deferred: report.deferred,
+            resumed: report.resumed,
+            removed: report.removed,
+            pruned: report.pruned.clone(),
+            failed: report
+                .failures
+                .iter()
+                .map(|failure| FailedVideo {
+                    video_id: failure.video_id.clone(),
+                    reason: failure.kind.to_string(),
+                    log: failure.log.clone(),
+                })
+                .collect(),
+            hook_failures: report.hook_failures.clone(),
+            low_on_space: report.low_on_space,
+            error: report.error.clone(),
+            bytes_downloaded: report.bytes_downloaded,
+            location_bytes: report.location_bytes,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_runs_as_json() {
+        let observer = JsonObserver::new(false);
+        observer.on_playlist_done(&SyncReport {
+            playlist_id: "PL".to_string(),
+            location: "/music/mix".to_string(),
+            downloaded: 2,
+            failures: vec![crate::failure::Failure {
+                video_id: "b".to_string(),
+                kind: crate::failure::FailureKind::Network,
+                log: None,
+            }],
+            deferred: 0,
+            skipped_unavailable: 1,
+            resumed: 0,
+            removed: 0,
+            pruned: Vec::new(),
+            dry_run: false,
+            low_on_space: false,
+            hook_failures: Vec::new(),
+            timings: None,
+            bytes_downloaded: 4_000_000,
+            location_bytes: 60_000_000,
+            error: None,
+        });
+
+        let summary: serde_json::Value = serde_json::from_str(&observer.summary(None)).unwrap();
+        assert_eq!(summary["exit_code"], 0);
+        assert_eq!(summary["items"][0]["downloaded"], 2);
+        assert_eq!(summary["items"][0]["skipped_unavailable"], 1);
+        assert_eq!(summary["items"][0]["bytes_downloaded"], 4_000_000);
+        assert_eq!(summary["items"][0]["failed"][0]["video_id"], "b");
+        assert_eq!(summary["items"][0]["failed"][0]["reason"], "network error");
+    }
+
+    #[test]
+    fn prints_finished_videos_in_playlist_order() {
+        let mut lines = OrderedLines::default();
+        lines.start("b", 1, "Two");
+        lines.start("a", 0, "One");
+        lines.start("c", 2, "Three");
+
+        assert!(lines.finish("b", true).is_empty());
+        assert!(lines.finish("c", false).is_empty());
+        assert_eq!(
+            lines.finish("a", true),
+            [
+                "Downloaded \"One\"",
+                "Downloaded \"Two\"",
+                "Failed to download \"Three\""
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path};
+
+use crate::i18n::tr;
+use crate::playlist::sanitize_filename;
+use crate::probe;
+
+// The fields an organize template can use, each written as {field}.
+const FIELDS: [&str; 6] = ["artist", "album", "title", "year", "id", "ext"];
+
+// Check that a template only uses known fields and stays inside the location.
+pub fn check(template: &str) -> Result<(), String> {
+    let path = render(template, &HashMap::new())?;
+    let path = Path::new(&path);
+    if path.file_name().is_none()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(tr!("organize-outside-location", template = template));
+    }
+    Ok(())
+}
+
+// Work out where a downloaded file goes inside its location, from the tags yt-dlp gave it.
+pub fn destination(template: &str, file: &Path, video_id: &str) -> io::Result<String> {
+    let mut fields = probe::probe(file)?.tags;
+    fields.insert("id".to_string(), video_id.to_string());
+    if let Some(extension) = file.extension() {
+        fields.insert("ext".to_string(), extension.to_string_lossy().into_owned());
+    }
+    if let Some(date) = fields.get("date") {
+        fields.insert("year".to_string(), date.chars().take(4).collect());
+    }
+    render(template, &fields).map_err(io::Error::other)
+}
+
+// Fill in a template, with fields that are missing or empty left as "Unknown". Values can't add
+// directories of their own, as their separators are replaced like in downloaded file names.
+fn render(template: &str, fields: &HashMap<String, String>) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| tr!("organize-unclosed", template = template))?;
+        let field = &rest[start + 1..start + end];
+        if !FIELDS.contains(&field) {
+            return Err(tr!(
+                "organize-unknown-field",
+                field = format!("{{{}}}", field),
+                template = template,
+                fields = FIELDS.map(|field| format!("{{{}}}", field)).join(", ")
+            ));
+        }
+        let value = fields
+            .get(field)
+            .map(|value| sanitize_filename(value.trim()))
+            .filter(|value| !value.is_empty() && value != "." && value != "..");
+        rendered.push_str(value.as_deref().unwrap_or("Unknown"));
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_templates_inside_the_location() {
+        let fields = HashMap::from([
+            ("artist".to_string(), "AC/DC".to_string()),
+            ("title".to_string(), "Thunderstruck".to_string()),
+            ("ext".to_string(), "opus".to_string()),
+        ]);
+        assert_eq!(
+            render("{artist}/{album}/{title}.{ext}", &fields).unwrap(),
+            "AC⧸DC/Unknown/Thunderstruck.opus"
+        );
+        assert!(render("{artist}/{genre}.{ext}", &fields).is_err());
+
+        assert!(check("{artist}/{album}/{title} [{id}].{ext}").is_ok());
+        assert!(check("../{title}.{ext}").is_err());
+        assert!(check("/music/{title}.{ext}").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::path::Path;
+
+use crate::i18n::tr;
+use crate::storage::Storage;
+
+// The permission bits and owner given to downloaded files, as set by the chmod and chown of an
+// item. Anything left out is kept as yt-dlp created it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Permissions {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Permissions {
+    // Parse a chmod in octal, such as "664", and a chown of a user, a group or both, such as
+    // "jellyfin:media", ":media" or "1000". Users and groups can be given by name or number.
+    pub fn parse(chmod: Option<&str>, chown: Option<&str>) -> Result<Self, String> {
+        let mut permissions = Permissions::default();
+        if let Some(chmod) = chmod {
+            let mode = u32::from_str_radix(chmod.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or_else(|| tr!("chmod-invalid", value = chmod))?;
+            permissions.mode = Some(mode);
+        }
+        if let Some(chown) = chown {
+            let (user, group) = chown.split_once(':').unwrap_or((chown, ""));
+            if !user.is_empty() {
+                let uid = user.parse().ok().or_else(|| user_id(user));
+                permissions.uid = Some(uid.ok_or_else(|| tr!("chown-unknown-user", user = user))?);
+            }
+            if !group.is_empty() {
+                let gid = group.parse().ok().or_else(|| group_id(group));
+                permissions.gid =
+                    Some(gid.ok_or_else(|| tr!("chown-unknown-group", group = group))?);
+            }
+        }
+        Ok(permissions)
+    }
+
+    // Give a file these permissions. Changing the owner needs root, or the user to be in the group.
+    pub fn apply(&self, storage: &dyn Storage, path: &Path) -> io::Result<()> {
+        if self.uid.is_some() || self.gid.is_some() {
+            storage.set_owner(path, self.uid, self.gid)?;
+        }
+        // The mode is set last, as changing the owner can clear the setuid and setgid bits.
+        if let Some(mode) = self.mode {
+            storage.set_mode(path, mode)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn user_id(name: &str) -> Option<u32> {
+    uzers::get_user_by_name(name).map(|user| user.uid())
+}
+
+#[cfg(unix)]
+fn group_id(name: &str) -> Option<u32> {
+    uzers::get_group_by_name(name).map(|group| group.gid())
+}
+
+// Other systems have no users and groups to look up.
+#[cfg(not(unix))]
+fn user_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn group_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_chmod_and_chown() {
+        assert_eq!(
+            Permissions::parse(Some("664"), Some("1000:1001")),
+            Ok(Permissions {
+                mode: Some(0o664),
+                uid: Some(1000),
+                gid: Some(1001),
+            })
+        );
+        let group_only = Permissions::parse(None, Some(":44")).unwrap();
+        assert_eq!((group_only.uid, group_only.gid), (None, Some(44)));
+        assert!(Permissions::parse(Some("rw-r--r--"), None).is_err());
+        assert!(Permissions::parse(Some("17777"), None).is_err());
+        assert!(Permissions::parse(None, Some("no-such-user-for-yt-sync")).is_err());
+    }
+}
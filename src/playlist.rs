@@ -0,0 +1,422 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::cancel::CancelToken;
+use crate::config::{Item, Kind};
+use crate::cookies::Cookies;
+use crate::i18n::tr;
+use crate::m3u::{self, PlaylistFormat};
+use crate::runner::CommandRunner;
+use crate::sync::SyncOptions;
+use crate::throttle::Throttle;
+
+#[derive(Deserialize, Debug)]
+pub struct VideoInfo {
+    pub id: String,
+    pub title: String,
+    // yt-dlp only lists these for some playlists.
+    pub timestamp: Option<i64>,
+    pub upload_date: Option<String>,
+    pub channel: Option<String>,
+    pub uploader: Option<String>,
+}
+
+impl VideoInfo {
+    // When the video was uploaded, in seconds since the Unix epoch, if yt-dlp listed it.
+    pub fn uploaded_at(&self) -> Option<i64> {
+        self.timestamp
+            .or_else(|| parse_upload_date(self.upload_date.as_deref()?))
+    }
+
+    // Who uploaded the video, if yt-dlp listed it.
+    pub fn uploader(&self) -> Option<String> {
+        self.channel.clone().or_else(|| self.uploader.clone())
+    }
+}
+
+// Parse a date in yt-dlp's YYYYMMDD format to seconds since the Unix epoch.
+pub fn parse_upload_date(date: &str) -> Option<i64> {
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (year, month, day): (i64, i64, i64) = (
+        date[..4].parse().ok()?,
+        date[4..6].parse().ok()?,
+        date[6..].parse().ok()?,
+    );
+    // Count days from 1970-01-01 with March as the first month, so leap days come last.
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some((era * 146097 + day_of_era - 719468) * 86400)
+}
+
+// The year, month and day of a time in seconds since the Unix epoch, the other way round from
+// parse_upload_date.
+pub fn civil_date(secs: i64) -> (i64, i64, i64) {
+    let days = secs.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let (year, month) = match month < 10 {
+        true => (era * 400 + year_of_era, month + 3),
+        false => (era * 400 + year_of_era + 1, month - 9),
+    };
+    (year, month, day)
+}
+
+// Get the path of the playlist file saved next to a location, named after its directory with the
+// extension of the playlist format.
+pub fn get_playlist_path(location: &str, format: PlaylistFormat) -> Result<PathBuf, String> {
+    let location_path = Path::new(location);
+    match (location_path.parent(), location_path.file_name()) {
+        (Some(parent_dir), Some(child_dir_name)) => {
+            let mut m3u_file_name = child_dir_name.to_os_string();
+            m3u_file_name.push(".");
+            m3u_file_name.push(format.extension());
+            Ok(parent_dir.join(m3u_file_name))
+        }
+        _ => Err(tr!("playlist-file-no-parent", location = location)),
+    }
+}
+
+// Update the playlist file of a location to list every available video in playlist order, given
+// by their paths inside the location and their titles, leaving it alone if nothing changed.
+// Paths are relative to the playlist file with playlist_relative, and absolute otherwise. It is
+// written under a temporary name and moved into place, so players never see a half-written
+// playlist.
+pub fn write_playlist(
+    m3u_file_path: &Path,
+    location: &str,
+    available: impl IntoIterator<Item = io::Result<(String, String)>>,
+    options: &SyncOptions,
+) -> io::Result<()> {
+    let format = options.playlist_format;
+    // Extended M3U files are always UTF-8, as their extension says.
+    let encoding = match format {
+        PlaylistFormat::M3u => options.playlist_encoding,
+        _ => m3u::Encoding {
+            charset: m3u::Charset::Utf8,
+            ..options.playlist_encoding
+        },
+    };
+    // The playlist file is saved next to the location, so relative paths start from its directory.
+    let base = match options.playlist_relative {
+        true => Path::new(location)
+            .file_name()
+            .map_or(location.to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            }),
+        false => location.to_string(),
+    };
+    let tracks = available
+        .into_iter()
+        .map(|file| {
+            let (path, title) = file?;
+            let entry = m3u::entry_path(&base, &path, options.playlist_separator);
+            Ok((encoding.representable(&entry), title))
+        })
+        .collect::<io::Result<Vec<(String, String)>>>()?;
+    let existing = match options.storage.read(m3u_file_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let merge = |existing: &str| {
+        let entries: Vec<String> = tracks.iter().map(|(entry, _)| entry.clone()).collect();
+        m3u::merge(
+            existing,
+            &encoding.representable(&base),
+            &entries,
+            options.preserve_unknown_lines,
+        )
+    };
+    let m3u = encoding.encode(&match format {
+        PlaylistFormat::M3u => merge(&encoding.decode(&existing)),
+        PlaylistFormat::M3u8 => {
+            let existing = m3u::strip_extended(&encoding.decode(&existing), &base);
+            m3u::extend(&merge(&existing), &tracks)
+        }
+        PlaylistFormat::Xspf => m3u::xspf(&tracks),
+        PlaylistFormat::Pls => m3u::pls(&tracks),
+    });
+    if m3u != existing {
+        let mut m3u_temp_name = OsString::from(".");
+        m3u_temp_name.push(m3u_file_path.file_name().unwrap_or_default());
+        m3u_temp_name.push(".tmp");
+        let m3u_temp_path = m3u_file_path.with_file_name(m3u_temp_name);
+        options.storage.write(&m3u_temp_path, &m3u)?;
+        if let Err(e) = options.storage.rename(&m3u_temp_path, m3u_file_path) {
+            let _ = options.storage.delete(&m3u_temp_path);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+// The URL yt-dlp lists a playlist from. Items can also be URLs themselves, such as of a single
+// video, which yt-dlp lists as a playlist of one.
+pub fn source_url(id: &str, kind: Kind) -> String {
+    match kind {
+        Kind::Url => id.to_string(),
+        Kind::Channel if id.starts_with('@') => format!("https://www.youtube.com/{}/videos", id),
+        Kind::Channel => format!("https://www.youtube.com/channel/{}/videos", id),
+        Kind::Video => format!("https://www.youtube.com/watch?v={}", id),
+        Kind::Playlist => format!("https://www.youtube.com/playlist?list={}", id),
+    }
+}
+
+// Read a list of URLs to sync, one per line, skipping blank lines and # comments.
+pub fn parse_url_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+// Stream the videos of a YouTube playlist, channel or video as yt-dlp lists each page of it.
+pub fn list_videos(
+    runner: &dyn CommandRunner,
+    playlist_id: &str,
+    kind: Kind,
+    cookies: &Cookies,
+    cancel: &CancelToken,
+    on_video: &mut dyn FnMut(VideoInfo),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = vec!["-j".to_string(), "--flat-playlist".to_string()];
+    args.extend(cookies.args());
+    args.push(source_url(playlist_id, kind));
+
+    let mut parse_error = None;
+    let output = runner.run(
+        &args,
+        cancel,
+        &mut |line| match serde_json::from_str(line) {
+            Ok(video_info) => {
+                on_video(video_info);
+                true
+            }
+            Err(e) => {
+                parse_error = Some(e);
+                false
+            }
+        },
+    )?;
+
+    if let Some(e) = parse_error {
+        return Err(e.into());
+    }
+    if !output.success() {
+        let code = format!("{:?}", output.code);
+        return Err(tr!("yt-dlp-failed", code = code, output = output.stderr).into());
+    }
+    Ok(())
+}
+
+// The names Windows keeps for devices, which no file can have whatever its extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Sanitize a whole filename so it is valid on Linux, macOS and Windows alike, the way yt-dlp does
+// for the files it saves. Invalid characters are replaced with their full-width lookalikes, apart
+// from the colons of timestamps, which become underscores, and control characters are dropped.
+// Leading dots that would hide the file are dropped too, as are trailing dots and spaces, which
+// Windows strips, and names Windows keeps for devices, such as "CON" or "nul.txt", get an
+// underscore. A name left empty becomes "_", as with yt-dlp.
+pub fn sanitize_filename(filename: &str) -> String {
+    let chars: Vec<char> = filename.chars().collect();
+    let mut sanitized = String::with_capacity(filename.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let in_timestamp = i > 0
+            && chars[i - 1].is_ascii_digit()
+            && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        match c {
+            ':' if in_timestamp => sanitized.push('_'),
+            '"' | '*' | ':' | '<' | '>' | '?' | '|' => {
+                sanitized.extend(char::from_u32(c as u32 + 0xfee0));
+            }
+            '/' => sanitized.push('⧸'),
+            '\\' => sanitized.push('⧹'),
+            '\n' => sanitized.push(' '),
+            c if c.is_control() => {}
+            c => sanitized.push(c),
+        }
+    }
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    sanitized.replace_range(
+        ..sanitized.len() - sanitized.trim_start_matches('.').len(),
+        "",
+    );
+    if sanitized.is_empty() {
+        return "_".to_string();
+    }
+    let stem = sanitized.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|name| stem.eq_ignore_ascii_case(name))
+    {
+        sanitized.insert(stem.len(), '_');
+    }
+    sanitized
+}
+
+// A message from a thread listing a playlist to the thread downloading it. The playlist's videos
+// are followed by a Done message carrying the outcome of its listing and how long it took.
+pub enum Listing {
+    Video(VideoInfo),
+    Done(Result<(), String>, Duration),
+}
+
+// Start listing playlists on a bounded pool of threads. Each playlist gets its own channel, so
+// they can be synced in order while later ones are listed in the background.
+pub fn spawn_listers(
+    playlists: &[Item],
+    jobs: usize,
+    options: &SyncOptions,
+) -> (Vec<Receiver<Listing>>, Vec<JoinHandle<()>>) {
+    let mut receivers = Vec::with_capacity(playlists.len());
+    let mut queue = VecDeque::with_capacity(playlists.len());
+    for playlist in playlists {
+        let (sender, receiver) = mpsc::channel();
+        receivers.push(receiver);
+        let cookies = options.cookies.for_item(playlist);
+        queue.push_back((playlist.id.clone(), playlist.kind(), cookies, sender));
+    }
+
+    let queue = Arc::new(Mutex::new(queue));
+    let listers = (0..jobs.clamp(1, playlists.len().max(1)))
+        .map(|_| {
+            let (queue, cancel) = (Arc::clone(&queue), options.cancel.clone());
+            let (runner, deadline) = (Arc::clone(&options.runner), options.deadline);
+            let throttle = Arc::clone(&options.throttle);
+            thread::spawn(move || loop {
+                let Some((playlist_id, kind, cookies, sender)) = queue.lock().unwrap().pop_front()
+                else {
+                    break;
+                };
+                // Playlists that won't be synced this run don't need listing.
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    let _ = sender.send(Listing::Done(Err(tr!("out-of-time")), Duration::ZERO));
+                    continue;
+                }
+                throttle.wait(&cancel);
+                let started = Instant::now();
+                let result = list_videos(
+                    &*runner,
+                    &playlist_id,
+                    kind,
+                    &cookies,
+                    &cancel,
+                    &mut |video| {
+                        let _ = sender.send(Listing::Video(video));
+                    },
+                );
+                match &result {
+                    Ok(()) => throttle.succeeded(),
+                    Err(e) if Throttle::is_throttled(&e.to_string()) => {
+                        throttle.throttled();
+                    }
+                    Err(_) => {}
+                }
+                let _ = sender.send(Listing::Done(
+                    result.map_err(|e| e.to_string()),
+                    started.elapsed(),
+                ));
+            })
+        })
+        .collect();
+    (receivers, listers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lists_of_urls() {
+        let list = "# To grab\nhttps://youtu.be/abc\n\n  PL123  \n";
+        let ids = parse_url_list(list);
+        assert_eq!(ids, ["https://youtu.be/abc", "PL123"]);
+        assert_eq!(Kind::of(&ids[0]), Kind::Url);
+        assert_eq!(
+            source_url(&ids[1], Kind::of(&ids[1])),
+            "https://www.youtube.com/playlist?list=PL123"
+        );
+    }
+
+    #[test]
+    fn parses_upload_dates() {
+        assert_eq!(parse_upload_date("19700102"), Some(86400));
+        assert_eq!(parse_upload_date("20240229"), Some(1709164800));
+        assert_eq!(parse_upload_date("2024-02-29"), None);
+        assert_eq!(parse_upload_date("NA"), None);
+    }
+
+    #[test]
+    fn sanitizes_filenames_for_every_platform() {
+        assert_eq!(
+            sanitize_filename("AC/DC: Live? [a].opus"),
+            "AC⧸DC： Live？ [a].opus"
+        );
+        assert_eq!(
+            sanitize_filename("Set 1:02:03 [a].opus"),
+            "Set 1_02_03 [a].opus"
+        );
+        assert_eq!(sanitize_filename("Tabs\tand\nlines"), "Tabsand lines");
+        assert_eq!(sanitize_filename("Vol. 2..  "), "Vol. 2");
+        assert_eq!(sanitize_filename("Vol. 2. [a].opus"), "Vol. 2. [a].opus");
+        assert_eq!(sanitize_filename(".hidden [a].opus"), "hidden [a].opus");
+        assert_eq!(sanitize_filename("con"), "con_");
+        assert_eq!(sanitize_filename("LPT1.txt"), "LPT1_.txt");
+        assert_eq!(sanitize_filename("Console"), "Console");
+        assert_eq!(sanitize_filename("CON .txt"), "CON_ .txt");
+        assert_eq!(sanitize_filename("..."), "_");
+    }
+
+    #[test]
+    fn tells_sources_apart() {
+        let url = |id| source_url(id, Kind::of(id));
+        assert_eq!(
+            url("@LinusTechTips"),
+            "https://www.youtube.com/@LinusTechTips/videos"
+        );
+        assert_eq!(
+            url("UCXuqSBlHAE6Xw-yeJA0Tunw"),
+            "https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw/videos"
+        );
+        assert_eq!(
+            url("dQw4w9WgXcQ"),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            url("PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf"),
+            "https://www.youtube.com/playlist?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf"
+        );
+        assert_eq!(
+            source_url("dQw4w9WgXcQ", Kind::Playlist),
+            "https://www.youtube.com/playlist?list=dQw4w9WgXcQ"
+        );
+    }
+}
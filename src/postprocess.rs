@@ -0,0 +1,92 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+use crate::runner::{CommandRunner, RunOutput};
+
+// A downloaded video waiting for yt-dlp to post-process it.
+pub struct Job {
+    pub video_id: String,
+    pub args: Vec<String>,
+}
+
+// How post-processing a video went.
+pub struct Finished {
+    pub video_id: String,
+    pub args: Vec<String>,
+    pub output: io::Result<RunOutput>,
+    pub stdout: String,
+    pub elapsed: Duration,
+}
+
+// Post-processes downloaded videos on a bounded pool of threads, so CPU-bound transcoding runs
+// alongside the network-bound downloads instead of holding them up. The queue is bounded too, so
+// downloads wait for post-processing when it falls behind rather than piling up files.
+pub struct PostProcessor {
+    jobs: Option<SyncSender<Job>>,
+    finished: Receiver<Finished>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl PostProcessor {
+    pub fn new(workers: usize, runner: Arc<dyn CommandRunner>, cancel: CancelToken) -> Self {
+        let workers = workers.max(1);
+        let (jobs, queue) = mpsc::sync_channel::<Job>(workers);
+        let queue = Arc::new(Mutex::new(queue));
+        let (sender, finished) = mpsc::channel();
+        let workers = (0..workers)
+            .map(|_| {
+                let (queue, sender) = (Arc::clone(&queue), sender.clone());
+                let (runner, cancel) = (Arc::clone(&runner), cancel.clone());
+                thread::spawn(move || loop {
+                    let Ok(job) = queue.lock().unwrap().recv() else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    let mut stdout = String::new();
+                    let output = runner.run(&job.args, &cancel, &mut |line| {
+                        stdout.push_str(line);
+                        stdout.push('\n');
+                        true
+                    });
+                    let _ = sender.send(Finished {
+                        video_id: job.video_id,
+                        args: job.args,
+                        output,
+                        stdout,
+                        elapsed: started.elapsed(),
+                    });
+                })
+            })
+            .collect();
+        PostProcessor {
+            jobs: Some(jobs),
+            finished,
+            workers,
+        }
+    }
+
+    // Queue a video, waiting while the queue is full.
+    pub fn submit(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(job);
+        }
+    }
+
+    // Take the videos that have finished post-processing so far.
+    pub fn take_finished(&self) -> Vec<Finished> {
+        self.finished.try_iter().collect()
+    }
+
+    // Wait for every queued video to be post-processed, and take the ones not taken yet.
+    pub fn finish(mut self) -> Vec<Finished> {
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        self.take_finished()
+    }
+}
@@ -0,0 +1,88 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::i18n::tr;
+use crate::update::is_newer;
+
+// The oldest yt-dlp release yt-sync works with. YouTube changes often enough that older releases
+// fail to list or download from it, with errors that don't say an update would fix them.
+pub const MIN_VERSION: &str = "2024.08.06";
+
+// Find out which version of yt-dlp a program is, making sure it can be run and is recent enough,
+// so a sync stops straight away rather than failing on every video.
+pub fn check(program: &Path) -> Result<String, String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                tr!("yt-dlp-not-found", path = program.display().to_string())
+            }
+            _ => tr!(
+                "yt-dlp-unrunnable-at",
+                path = program.display().to_string(),
+                error = e.to_string()
+            ),
+        })?;
+    if !output.status.success() {
+        return Err(tr!(
+            "yt-dlp-version-failed",
+            path = program.display().to_string(),
+            status = output.status.to_string(),
+            error = String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.lines().next().unwrap_or_default().trim().to_string();
+    check_version(&version)?;
+    Ok(version)
+}
+
+// Check a yt-dlp version is at least the minimum. Versions that aren't dates, such as those of
+// builds from source, are taken to be recent enough.
+fn check_version(version: &str) -> Result<(), String> {
+    match is_newer(MIN_VERSION, version) && version.starts_with(char::is_numeric) {
+        true => Err(tr!(
+            "yt-dlp-too-old",
+            version = version,
+            minimum = MIN_VERSION
+        )),
+        false => Ok(()),
+    }
+}
+
+// Have yt-dlp update itself to its latest release, showing its output as it goes.
+pub fn update(program: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let status = Command::new(program).arg("-U").status().map_err(|e| {
+        tr!(
+            "yt-dlp-unrunnable-at",
+            path = program.display().to_string(),
+            error = e.to_string()
+        )
+    })?;
+    if !status.success() {
+        return Err(tr!("yt-dlp-update-failed", status = status.to_string()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_yt_dlp_is_there_and_recent() {
+        assert!(check_version("2025.01.26").is_ok());
+        assert!(check_version(MIN_VERSION).is_ok());
+        assert!(check_version("2024.08.06.232621").is_ok());
+        assert!(check_version("2023.12.30")
+            .unwrap_err()
+            .contains("update-ytdlp"));
+        assert!(check_version("unknown").is_ok());
+
+        let missing = check(Path::new("/nonexistent/yt-dlp")).unwrap_err();
+        assert!(missing.contains("yt_dlp_path"), "{}", missing);
+    }
+}
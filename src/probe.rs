@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::i18n::tr;
+
+// What ffprobe says about a media file.
+pub struct MediaInfo {
+    // The tags yt-dlp embedded, by lowercase name.
+    pub tags: HashMap<String, String>,
+    // How long the file plays for, in seconds.
+    pub duration: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+struct Output {
+    #[serde(default)]
+    streams: Vec<Tagged>,
+    #[serde(default)]
+    format: Format,
+}
+
+#[derive(Deserialize, Default)]
+struct Tagged {
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Format {
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    // ffprobe gives numbers as strings.
+    duration: Option<String>,
+}
+
+// Read the tags and duration of a media file with ffprobe. Opus files keep their tags on the
+// audio stream and Matroska files on the container, so both are read.
+pub fn probe(path: &Path) -> io::Result<MediaInfo> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-of", "json", "-show_entries"])
+        .arg("format=duration:format_tags:stream_tags")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(e.kind(), tr!("ffprobe-not-found")),
+            _ => e,
+        })?;
+    if !output.status.success() {
+        return Err(io::Error::other(tr!(
+            "ffprobe-failed",
+            status = output.status.to_string(),
+            error = String::from_utf8_lossy(&output.stderr).trim().to_string()
+        )));
+    }
+    let output: Output = serde_json::from_slice(&output.stdout)?;
+    let mut tags = HashMap::new();
+    for stream in output.streams {
+        for (name, value) in stream.tags {
+            tags.insert(name.to_lowercase(), value);
+        }
+    }
+    for (name, value) in output.format.tags {
+        tags.insert(name.to_lowercase(), value);
+    }
+    Ok(MediaInfo {
+        tags,
+        duration: output.format.duration.and_then(|d| d.parse().ok()),
+    })
+}
@@ -0,0 +1,143 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use log::{debug, trace};
+
+use crate::cancel::CancelToken;
+
+// How a yt-dlp process finished.
+pub struct RunOutput {
+    pub code: Option<i32>,
+    pub stderr: String,
+}
+
+impl RunOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+// Runs yt-dlp. The sync logic only talks to yt-dlp through this, so tests can script its results.
+pub trait CommandRunner: Send + Sync {
+    // Run yt-dlp with the given arguments, passing each line it writes to stdout to on_line as it
+    // arrives. Returning false from on_line stops the process early.
+    fn run(
+        &self,
+        args: &[String],
+        cancel: &CancelToken,
+        on_line: &mut dyn FnMut(&str) -> bool,
+    ) -> io::Result<RunOutput>;
+}
+
+// Runs the real yt-dlp binary, which is "yt-dlp" on the PATH unless it is given a path.
+pub struct YtDlp {
+    pub program: PathBuf,
+}
+
+impl YtDlp {
+    pub fn new(program: Option<PathBuf>) -> YtDlp {
+        YtDlp {
+            program: program.unwrap_or_else(|| PathBuf::from("yt-dlp")),
+        }
+    }
+}
+
+impl CommandRunner for YtDlp {
+    fn run(
+        &self,
+        args: &[String],
+        cancel: &CancelToken,
+        on_line: &mut dyn FnMut(&str) -> bool,
+    ) -> io::Result<RunOutput> {
+        trace!("Running {} {}", self.program.display(), args.join(" "));
+        let process = cancel.spawn(
+            Command::new(&self.program)
+                .args(args)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )?;
+
+        // Drain stderr separately so a chatty yt-dlp can't block on a full pipe.
+        let mut stderr = process.take_stderr().unwrap();
+        let stderr_reader = thread::spawn(move || {
+            let mut content = String::new();
+            let _ = stderr.read_to_string(&mut content);
+            content
+        });
+
+        let mut read_result = Ok(());
+        for line in BufReader::new(process.take_stdout().unwrap()).lines() {
+            match line {
+                Ok(line) if on_line(&line) => {}
+                Ok(_) => {
+                    process.kill();
+                    break;
+                }
+                Err(e) => {
+                    process.kill();
+                    read_result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let status = process.wait()?;
+        let stderr = stderr_reader.join().unwrap_or_default();
+        for line in stderr.lines() {
+            debug!("yt-dlp: {}", line);
+        }
+        read_result?;
+        Ok(RunOutput {
+            code: status.code(),
+            stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+type Script = Box<dyn Fn(&[String]) -> (Vec<String>, i32) + Send + Sync>;
+
+// A scriptable stand-in for yt-dlp. The script gets the arguments of each call and returns the
+// lines to write to stdout and the exit code, doing whatever yt-dlp would have done on the way.
+#[cfg(test)]
+pub struct FakeRunner {
+    script: Script,
+    pub calls: std::sync::Mutex<Vec<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl FakeRunner {
+    pub fn new(script: impl Fn(&[String]) -> (Vec<String>, i32) + Send + Sync + 'static) -> Self {
+        FakeRunner {
+            script: Box::new(script),
+            calls: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for FakeRunner {
+    fn run(
+        &self,
+        args: &[String],
+        _cancel: &CancelToken,
+        on_line: &mut dyn FnMut(&str) -> bool,
+    ) -> io::Result<RunOutput> {
+        self.calls.lock().unwrap().push(args.to_vec());
+        let (lines, code) = (self.script)(args);
+        // Errors go to stderr, as yt-dlp prints them there.
+        let (errors, lines): (Vec<&String>, Vec<&String>) =
+            lines.iter().partition(|line| line.starts_with("ERROR:"));
+        for line in lines {
+            if !on_line(line) {
+                break;
+            }
+        }
+        Ok(RunOutput {
+            code: Some(code),
+            stderr: errors.iter().map(|line| format!("{}\n", line)).collect(),
+        })
+    }
+}
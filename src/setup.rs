@@ -0,0 +1,151 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::{Config, Format, Item};
+use crate::i18n::tr;
+
+// Ask a question on the terminal, returning the answer, or the default if it is left empty.
+fn ask(input: &mut dyn BufRead, question: &str, default: Option<&str>) -> io::Result<String> {
+    loop {
+        match default {
+            Some(default) => print!("{} [{}]: ", question, default),
+            None => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                tr!("setup-cancelled"),
+            ));
+        }
+        let answer = answer.trim();
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer.to_string()),
+            (true, Some(default)) => return Ok(default.to_string()),
+            (true, None) => {}
+        }
+    }
+}
+
+// The version a program prints, or None if it isn't installed.
+fn version(program: &str, flag: &str) -> Option<String> {
+    let output = Command::new(program)
+        .arg(flag)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+// Take the playlist ID out of a playlist URL, or a video URL that is playing from a playlist.
+// Anything that isn't a URL is taken to be an ID already.
+fn playlist_id(answer: &str) -> Option<String> {
+    if !answer.contains('/') && !answer.contains('?') {
+        return Some(answer.to_string());
+    }
+    let query = answer.split_once('?')?.1;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == "list")
+        .map(|(_, id)| id.split('#').next().unwrap_or_default().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+// Replace a leading ~ with the home directory, as a shell would.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+// Guide someone running yt-sync for the first time through setting up their first playlist,
+// checking that the programs it needs are installed along the way.
+pub fn run(input: &mut dyn BufRead) -> io::Result<Config> {
+    println!("{}\n", tr!("setup-intro"));
+    match version("yt-dlp", "--version") {
+        Some(version) => println!("{}", tr!("setup-found-yt-dlp", version = version)),
+        None => println!("{}", tr!("setup-missing-yt-dlp")),
+    }
+    match version("ffmpeg", "-version") {
+        Some(version) => println!("{}", tr!("setup-found-ffmpeg", version = version)),
+        None => println!("{}", tr!("setup-missing-ffmpeg")),
+    }
+    println!();
+
+    let id = loop {
+        let answer = ask(input, &tr!("setup-playlist"), None)?;
+        match playlist_id(&answer) {
+            Some(id) => break id,
+            None => println!("{}", tr!("setup-not-a-playlist")),
+        }
+    };
+    let format = loop {
+        match ask(input, &tr!("setup-format"), Some("audio"))?
+            .to_lowercase()
+            .as_str()
+        {
+            "audio" | "a" => break Format::Audio,
+            "video" | "v" => break Format::Video,
+            _ => println!("{}", tr!("setup-audio-or-video")),
+        }
+    };
+    let default_location = dirs::audio_dir()
+        .filter(|_| format == Format::Audio)
+        .or_else(dirs::video_dir)
+        .or_else(|| dirs::home_dir().map(|home| home.join("Music")))
+        .unwrap_or_else(|| PathBuf::from("yt-sync"))
+        .join(&id);
+    let location = ask(
+        input,
+        &tr!("setup-location"),
+        Some(&default_location.to_string_lossy()),
+    )?;
+    let save_playlist = !matches!(
+        ask(input, &tr!("setup-m3u"), Some("yes"))?
+            .to_lowercase()
+            .as_str(),
+        "n" | "no"
+    );
+    println!();
+
+    Ok(Config {
+        items: vec![Item {
+            id,
+            location: expand_home(&location),
+            format,
+            save_playlist,
+            ..Default::default()
+        }],
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asks_for_the_first_playlist() {
+        assert_eq!(
+            playlist_id("https://www.youtube.com/watch?v=abc&list=PL123&index=2").as_deref(),
+            Some("PL123")
+        );
+        assert_eq!(playlist_id("PL123").as_deref(), Some("PL123"));
+        assert_eq!(playlist_id("https://youtu.be/abc"), None);
+
+        let mut input = "\nhttps://youtube.com/playlist?list=PL123\nv\n/videos/mix\n\n".as_bytes();
+        let config = run(&mut input).unwrap();
+        let item = &config.items[0];
+        assert_eq!(
+            (item.id.as_str(), item.format, item.location.as_str()),
+            ("PL123", Format::Video, "/videos/mix")
+        );
+        assert!(item.save_playlist);
+    }
+}
@@ -0,0 +1,99 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::storage::Storage;
+
+// Where a downloaded file came from, kept next to it so the file describes itself even if the
+// state of yt-sync is lost.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Provenance {
+    pub video_id: String,
+    pub url: String,
+    // Of the file's contents, in hex.
+    pub sha256: String,
+    // When the file was synced, in seconds since the Unix epoch.
+    pub synced_at: u64,
+}
+
+// Added to the name of a file to name its sidecar.
+const SUFFIX: &str = ".yt-sync.json";
+
+// The sidecar of a file, named after it, such as "Song [id].opus.yt-sync.json".
+pub fn path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(SUFFIX);
+    file.with_file_name(name)
+}
+
+// The sidecar of a file given by its path inside a location, as a path inside the location too.
+pub fn name(path: &str) -> String {
+    format!("{}{}", path, SUFFIX)
+}
+
+// Write the sidecar of a file that was just downloaded.
+pub fn write(storage: &dyn Storage, file: &Path, video_id: &str) -> io::Result<()> {
+    let sha256 = Sha256::digest(storage.read(file)?)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let provenance = Provenance {
+        video_id: video_id.to_string(),
+        url: format!("https://www.youtube.com/watch?v={}", video_id),
+        sha256,
+        synced_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+    };
+    let json = serde_json::to_string_pretty(&provenance)?;
+    storage.write(&path(file), json.as_bytes())
+}
+
+// Move the sidecar of a file along with it, if it has one.
+pub fn rename(storage: &dyn Storage, from: &Path, to: &Path) -> io::Result<()> {
+    match storage.rename(&path(from), &path(to)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+// Delete the sidecar of a file, if it has one.
+pub fn delete(storage: &dyn Storage, file: &Path) -> io::Result<()> {
+    match storage.delete(&path(file)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn describes_files_next_to_them() {
+        let storage = MemoryStorage::default();
+        let file = Path::new("/music/Song [a].opus");
+        storage.write(file, b"abc").unwrap();
+        write(&storage, file, "a").unwrap();
+
+        let moved = Path::new("/music/Artist/Song.opus");
+        storage.rename(file, moved).unwrap();
+        rename(&storage, file, moved).unwrap();
+        let provenance: Provenance =
+            serde_json::from_slice(&storage.read(&path(moved)).unwrap()).unwrap();
+        assert_eq!(provenance.url, "https://www.youtube.com/watch?v=a");
+        assert_eq!(
+            provenance.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        delete(&storage, moved).unwrap();
+        assert!(storage
+            .read(Path::new("/music/Artist/Song.opus.yt-sync.json"))
+            .is_err());
+    }
+}
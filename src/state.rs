@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::Storage;
+
+// How many runs a video has to be unavailable in before it stops being attempted.
+pub const UNAVAILABLE_THRESHOLD: u32 = 3;
+
+// What yt-sync remembers between runs, kept in <data_dir>/state.json. Changes made during a run
+// are appended to <data_dir>/state.journal as they happen, and folded into state.json when it is
+// saved, so a crash or power loss never leaves a half-written state behind.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct State {
+    // How many runs in a row each video was found to be unavailable in, by video ID.
+    #[serde(default)]
+    pub unavailable: BTreeMap<String, u32>,
+    // Downloads that were started but haven't finished, by video ID. yt-dlp keeps their .part
+    // files, so they pick up where they left off when attempted again.
+    #[serde(default)]
+    pub in_progress: BTreeMap<String, InProgress>,
+    // Where downloads were moved to by an organize template, by video ID, as they no longer have
+    // the file name that shows they are downloaded.
+    #[serde(default)]
+    pub organized: BTreeMap<String, Organized>,
+    // The files yt-sync created in each location, as paths inside it with / between directories.
+    // Anything else in a location was put there by someone else, and is left alone.
+    #[serde(default)]
+    pub managed: BTreeMap<String, BTreeSet<String>>,
+    // Who uploaded each downloaded video, by video ID, when the listing said. Only used for
+    // reports, so they are saved with the state rather than journaled.
+    #[serde(default)]
+    pub uploaders: BTreeMap<String, String>,
+    // The playlist the last run used up its download budget in. The next run starts with it, so
+    // every playlist gets its turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_playlist: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InProgress {
+    pub location: String,
+    // When the download was first started, in seconds since the Unix epoch.
+    pub started: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Organized {
+    pub location: String,
+    // The path of the file inside the location, with / between directories.
+    pub path: String,
+}
+
+// A change to the state, as written to the journal. Each one sets a value rather than adjusting
+// it, so replaying the journal onto a state that already has some of it applied is harmless.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Unavailable {
+        video_id: String,
+        count: u32,
+    },
+    Available {
+        video_id: String,
+    },
+    Started {
+        video_id: String,
+        #[serde(flatten)]
+        download: InProgress,
+    },
+    Finished {
+        video_id: String,
+    },
+    Organized {
+        video_id: String,
+        #[serde(flatten)]
+        file: Organized,
+    },
+    // Also marks the location as tracked, even with no paths.
+    Managed {
+        location: String,
+        paths: Vec<String>,
+    },
+    Unmanaged {
+        location: String,
+        paths: Vec<String>,
+    },
+}
+
+impl State {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join("state.json")
+    }
+
+    fn journal_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("state.journal")
+    }
+
+    // Load the state, starting afresh if there is none yet, and replay any changes journaled
+    // since it was last saved. A change cut off by a crash while being written is dropped.
+    pub fn load(storage: &dyn Storage, data_dir: &Path) -> io::Result<State> {
+        let mut state: State = match storage.read(&State::path(data_dir)) {
+            Ok(content) => serde_json::from_slice(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(e),
+        };
+        match storage.read(&State::journal_path(data_dir)) {
+            Ok(journal) => {
+                for line in journal.split(|b| *b == b'\n') {
+                    if let Ok(change) = serde_json::from_slice(line) {
+                        state.apply(change);
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(state)
+    }
+
+    // Write the whole state out and clear the journal. The new state is written under a
+    // temporary name and moved into place, so the old one stays intact until it is complete.
+    pub fn save(&self, storage: &dyn Storage, data_dir: &Path) -> io::Result<()> {
+        storage.create_dir(data_dir)?;
+        let content = serde_json::to_vec_pretty(self)?;
+        let temp_path = data_dir.join(".state.json.tmp");
+        storage.write(&temp_path, &content)?;
+        storage.rename(&temp_path, &State::path(data_dir))?;
+        match storage.delete(&State::journal_path(data_dir)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // Make a change and append it to the journal straight away, so it survives the run being
+    // killed before the state is next saved.
+    pub fn record(
+        &mut self,
+        storage: &dyn Storage,
+        data_dir: &Path,
+        change: Change,
+    ) -> io::Result<()> {
+        storage.create_dir(data_dir)?;
+        let mut line = serde_json::to_vec(&change)?;
+        line.push(b'\n');
+        storage.append(&State::journal_path(data_dir), &line)?;
+        self.apply(change);
+        Ok(())
+    }
+
+    fn apply(&mut self, change: Change) {
+        match change {
+            Change::Unavailable { video_id, count } => {
+                self.unavailable.insert(video_id, count);
+            }
+            Change::Available { video_id } => {
+                self.unavailable.remove(&video_id);
+            }
+            Change::Started { video_id, download } => {
+                self.in_progress.insert(video_id, download);
+            }
+            Change::Finished { video_id } => {
+                self.in_progress.remove(&video_id);
+            }
+            Change::Organized { video_id, file } => {
+                self.organized.insert(video_id, file);
+            }
+            Change::Managed { location, paths } => {
+                self.managed.entry(location).or_default().extend(paths);
+            }
+            Change::Unmanaged { location, paths } => {
+                if let Some(managed) = self.managed.get_mut(&location) {
+                    for path in paths {
+                        managed.remove(&path);
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether a video has been unavailable often enough to stop trying it.
+    pub fn is_unavailable(&self, video_id: &str) -> bool {
+        self.unavailable
+            .get(video_id)
+            .is_some_and(|count| *count >= UNAVAILABLE_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn replays_the_journal_after_a_crash() {
+        let storage = MemoryStorage::default();
+        let data_dir = Path::new("/data");
+        let mut state = State::default();
+        let unavailable = |count| Change::Unavailable {
+            video_id: "a".to_string(),
+            count,
+        };
+        state.record(&storage, data_dir, unavailable(1)).unwrap();
+        state.save(&storage, data_dir).unwrap();
+        state.record(&storage, data_dir, unavailable(2)).unwrap();
+        let started = Change::Started {
+            video_id: "b".to_string(),
+            download: InProgress {
+                location: "/music/mix".to_string(),
+                started: 0,
+            },
+        };
+        state.record(&storage, data_dir, started).unwrap();
+        // The last change was cut off halfway through being written.
+        storage
+            .append(&data_dir.join("state.journal"), br#"{"change":"finis"#)
+            .unwrap();
+
+        let state = State::load(&storage, data_dir).unwrap();
+        assert_eq!(state.unavailable["a"], 2);
+        assert_eq!(state.in_progress["b"].location, "/music/mix");
+    }
+}
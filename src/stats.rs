@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::config::format_size;
+use crate::i18n::tr;
+use crate::manifest::Manifest;
+use crate::playlist::civil_date;
+use crate::state::State;
+use crate::storage::Storage;
+
+// What has been downloaded to a location, going by its manifest, for the playlists synced to it.
+#[derive(Debug, PartialEq)]
+pub struct Library {
+    pub playlists: Vec<String>,
+    pub location: String,
+    pub videos: usize,
+    pub chapters: usize,
+    pub bytes: u64,
+    // When the most recent download was, in seconds since the Unix epoch.
+    pub last_download: Option<u64>,
+}
+
+// Add up what each location holds from the manifests in them, without going online. Playlists,
+// given as their ID and location, that share a location are reported together.
+pub fn library(
+    storage: &dyn Storage,
+    playlists: &[(&str, &str)],
+    state: &State,
+) -> io::Result<Vec<Library>> {
+    let mut by_location: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (id, location) in playlists {
+        by_location
+            .entry(location)
+            .or_default()
+            .push(id.to_string());
+    }
+    let mut libraries = Vec::new();
+    for (location, playlists) in by_location {
+        let manifest = Manifest::load(storage, location, state)?;
+        libraries.push(Library {
+            playlists,
+            location: location.to_string(),
+            videos: manifest.videos.len(),
+            chapters: manifest.videos.values().map(|v| v.chapters.len()).sum(),
+            bytes: manifest.size(storage, location),
+            last_download: manifest.videos.values().map(|v| v.downloaded_at).max(),
+        });
+    }
+    Ok(libraries)
+}
+
+// Print the libraries as a table, followed by their total.
+pub fn write(out: &mut dyn Write, libraries: &[Library]) -> io::Result<()> {
+    let date = |secs: Option<u64>| match secs {
+        Some(secs) => {
+            let (year, month, day) = civil_date(secs as i64);
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        }
+        None => "-".to_string(),
+    };
+    let headers = [
+        "stats-location",
+        "stats-videos",
+        "stats-chapters",
+        "stats-size",
+        "stats-last-download",
+    ];
+    let mut rows = vec![headers.map(|id| tr!(id)).to_vec()];
+    for library in libraries {
+        rows.push(vec![
+            format!("{} ({})", library.location, library.playlists.join(", ")),
+            library.videos.to_string(),
+            library.chapters.to_string(),
+            format_size(library.bytes),
+            date(library.last_download),
+        ]);
+    }
+    rows.push(vec![
+        tr!("summary-total"),
+        libraries
+            .iter()
+            .map(|l| l.videos)
+            .sum::<usize>()
+            .to_string(),
+        libraries
+            .iter()
+            .map(|l| l.chapters)
+            .sum::<usize>()
+            .to_string(),
+        format_size(libraries.iter().map(|l| l.bytes).sum()),
+        date(libraries.iter().filter_map(|l| l.last_download).max()),
+    ]);
+    for line in table(&rows) {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Lay out rows of cells in columns as lines, the first column aligned left and the rest right.
+pub fn table(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (column, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(column) {
+                Some(widest) => *widest = (*widest).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = (row.iter().zip(&widths).enumerate())
+                .map(|(column, (cell, &width))| match column {
+                    0 => format!("{:<width$}", cell),
+                    _ => format!("{:>width$}", cell),
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use std::path::Path;
+
+    #[test]
+    fn reports_the_library_from_manifests() {
+        let storage = MemoryStorage::default();
+        for (path, size) in [("/a/One [x].opus", 100), ("/a/Two [y].opus", 200)] {
+            storage.write(Path::new(path), &vec![0; size]).unwrap();
+        }
+        let mut manifest = Manifest::default();
+        manifest.insert("x", "One", "One [x].opus", "audio");
+        manifest.insert("y", "Two", "Two [y].opus", "audio");
+        manifest.insert("z", "Gone", "Gone [z].opus", "audio");
+        manifest.save(&storage, "/a").unwrap();
+
+        let playlists = [("PL1", "/a"), ("PL2", "/b"), ("PL3", "/a")];
+        let libraries = library(&storage, &playlists, &State::default()).unwrap();
+        assert_eq!(libraries.len(), 2);
+        assert_eq!(libraries[0].playlists, ["PL1", "PL3"]);
+        assert_eq!((libraries[0].videos, libraries[0].bytes), (2, 300));
+        assert_eq!((libraries[1].videos, libraries[1].last_download), (0, None));
+
+        let mut out = Vec::new();
+        write(&mut out, &libraries).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Location       Videos  Chapters   Size  Last download"
+        );
+        assert!(lines[2] == "/b (PL2)            0         0    0 B              -");
+        assert!(lines[3].starts_with("Total               2         0  300 B  "));
+    }
+}
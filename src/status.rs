@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+
+use crate::archive;
+use crate::config::Item;
+use crate::i18n::tr;
+use crate::manifest::Manifest;
+use crate::playlist::{spawn_listers, Listing};
+use crate::sync::SyncOptions;
+
+// The videos of each playlist that the next sync would download, or why the playlist couldn't be
+// listed. Nothing is downloaded or changed.
+pub fn missing(
+    playlists: &[Item],
+    list_jobs: usize,
+    options: &SyncOptions,
+) -> Vec<Result<Vec<String>, String>> {
+    let (receivers, listers) = spawn_listers(playlists, list_jobs, options);
+    let missing = playlists
+        .iter()
+        .zip(&receivers)
+        .map(|(playlist, receiver)| missing_from(playlist, receiver, options))
+        .collect();
+    for lister in listers {
+        let _ = lister.join();
+    }
+    missing
+}
+
+// Compare the videos listed for a playlist with those in the manifest of its location, leaving
+// out the videos a sync would skip: blacklisted ones, ones in the download archive, ones that stay
+// unavailable, and with keep_last, all but the newest.
+fn missing_from(
+    playlist: &Item,
+    receiver: &Receiver<Listing>,
+    options: &SyncOptions,
+) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    let mut listed = Vec::new();
+    loop {
+        match receiver.recv() {
+            Ok(Listing::Video(video)) => {
+                let uploaded_at = video.uploaded_at();
+                if seen.insert(video.id.clone()) {
+                    listed.push((video.id, uploaded_at));
+                }
+            }
+            Ok(Listing::Done(result, _)) => {
+                result?;
+                break;
+            }
+            Err(_) => return Err(tr!("listing-stopped")),
+        }
+    }
+    listed.retain(|(video_id, _)| {
+        !options.blacklist.contains(video_id) && !playlist.blacklist.contains(video_id)
+    });
+    // The same as a sync, newest by upload date if every video has one, and otherwise by position.
+    if let Some(keep_last) = playlist.keep_last {
+        if listed.iter().all(|(_, uploaded_at)| uploaded_at.is_some()) {
+            listed.sort_by_key(|(_, uploaded_at)| *uploaded_at);
+        }
+        let older = listed.len().saturating_sub(keep_last);
+        let newest = listed.split_off(older);
+        listed.retain(|(video_id, _)| playlist.pinned.contains(video_id));
+        listed.extend(newest);
+    }
+
+    if let Some(path) = &playlist.download_archive {
+        let archived = archive::read(&*options.storage, Path::new(path))
+            .map_err(|e| tr!("read-failed", path = path.as_str(), error = e.to_string()))?;
+        listed.retain(|(video_id, _)| !archived.contains(video_id));
+    }
+
+    let state = options.state.lock().unwrap();
+    let manifest = Manifest::load(&*options.storage, &playlist.location, &state).map_err(|e| {
+        tr!(
+            "read-failed",
+            path = playlist.location.as_str(),
+            error = e.to_string()
+        )
+    })?;
+    Ok(listed
+        .into_iter()
+        .map(|(video_id, _)| video_id)
+        .filter(|video_id| {
+            manifest.find(video_id, playlist.format.name()).is_none()
+                && !state.is_unavailable(video_id)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::create_default_config;
+    use crate::runner::FakeRunner;
+    use crate::state::State;
+    use crate::storage::{MemoryStorage, Storage};
+
+    #[test]
+    fn finds_videos_the_next_sync_would_download() {
+        let storage = Arc::new(MemoryStorage::default());
+        storage
+            .write(Path::new("/music/mix/One [a].opus"), b"")
+            .unwrap();
+        let runner = Arc::new(FakeRunner::new(|args| {
+            match args.iter().any(|arg| arg.ends_with("list=PL")) {
+                true => (
+                    ["a", "b", "c", "d"]
+                        .iter()
+                        .map(|id| format!(r#"{{"id":"{}","title":"{}"}}"#, id, id))
+                        .collect(),
+                    0,
+                ),
+                false => (vec!["ERROR: not a playlist".to_string()], 1),
+            }
+        }));
+        let mut config = create_default_config();
+        config.blacklist.push("c".to_string());
+        let state = State::default();
+        let data_dir = PathBuf::from("/data");
+        let options = SyncOptions::new(&config, runner, storage, data_dir, state).unwrap();
+        let mut items = config.items;
+        items[0].id = "PL".to_string();
+        items[0].location = "/music/mix".to_string();
+
+        let results = missing(&items, 2, &options);
+        assert_eq!(results[0], Ok(vec!["b".to_string(), "d".to_string()]));
+        assert!(results[1].is_err());
+
+        items[0].keep_last = Some(1);
+        assert_eq!(
+            missing(&items[..1], 1, &options)[0],
+            Ok(vec!["d".to_string()])
+        );
+    }
+}
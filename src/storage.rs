@@ -0,0 +1,364 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// The file operations the sync logic needs, so locations can live somewhere other than a local
+// filesystem. yt-dlp itself still writes downloads through the normal filesystem.
+pub trait Storage: Send + Sync {
+    // Resolve a path to the form used to tell whether two locations are the same.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    // Create a directory and any missing parents.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    // List the names of the entries in a directory.
+    fn scan(&self, path: &Path) -> io::Result<Vec<String>>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    // Get the size of a file in bytes.
+    fn size(&self, path: &Path) -> io::Result<u64>;
+
+    // Get when a file was last written to.
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+
+    // Change when a file was last written to.
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()>;
+
+    // Create or replace a file with the given contents, which are on disk once this returns.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    // Add to the end of a file, creating it if needed. The contents are on disk once this returns.
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    // Copy a file to where there is none yet. Local files on the same filesystem are hard linked
+    // instead, so the copy takes no extra space.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    // Delete a file or symlink.
+    fn delete(&self, path: &Path) -> io::Result<()>;
+
+    // Delete a directory, which must be empty.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    // Create a symlink at link pointing to the file at target.
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    // Get how many bytes can still be written to the filesystem a path is on.
+    fn available_space(&self, path: &Path) -> io::Result<u64>;
+
+    // Set the permission bits of a file, as chmod does.
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    // Change the user and group owning a file, leaving either as it is if not given.
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()>;
+}
+
+// Stores everything on the local filesystem.
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn scan(&self, path: &Path) -> io::Result<Vec<String>> {
+        Ok(fs::read_dir(path)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_modified(time)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(contents)?;
+        file.sync_all()
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(contents)?;
+        file.sync_data()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to).or_else(|_| fs::copy(from, to).map(|_| ()))
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn available_space(&self, path: &Path) -> io::Result<u64> {
+        fs4::available_space(path)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(unix)]
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        std::os::unix::fs::chown(path, uid, gid)
+    }
+
+    #[cfg(windows)]
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(windows)]
+    fn set_owner(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+// The user and group IDs a file was given, for tests.
+#[cfg(test)]
+pub type Owner = (Option<u32>, Option<u32>);
+
+// Keeps files in memory, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage {
+    dirs: std::sync::Mutex<std::collections::BTreeSet<PathBuf>>,
+    files: std::sync::Mutex<std::collections::BTreeMap<PathBuf, Vec<u8>>>,
+    links: std::sync::Mutex<std::collections::BTreeMap<PathBuf, PathBuf>>,
+    // Unlimited if not set.
+    pub available_space: std::sync::Mutex<Option<u64>>,
+    // When files were last written to, by path, if set. Others count as the Unix epoch.
+    modified: std::sync::Mutex<std::collections::BTreeMap<PathBuf, SystemTime>>,
+    // The modes and owners files were given, by path.
+    pub modes: std::sync::Mutex<std::collections::BTreeMap<PathBuf, u32>>,
+    pub owners: std::sync::Mutex<std::collections::BTreeMap<PathBuf, Owner>>,
+    // How many times each file was read, by path, whether it was there or not.
+    pub reads: std::sync::Mutex<std::collections::BTreeMap<PathBuf, usize>>,
+}
+
+// Everything a memory storage holds, to check what a sync changed.
+#[cfg(test)]
+type Snapshot = (
+    std::collections::BTreeSet<PathBuf>,
+    std::collections::BTreeMap<PathBuf, Vec<u8>>,
+    std::collections::BTreeMap<PathBuf, PathBuf>,
+);
+
+#[cfg(test)]
+impl MemoryStorage {
+    pub fn snapshot(&self) -> Snapshot {
+        (
+            self.dirs.lock().unwrap().clone(),
+            self.files.lock().unwrap().clone(),
+            self.links.lock().unwrap().clone(),
+        )
+    }
+
+    // The contents of a file, without counting it as read.
+    fn file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files
+            .get(path)
+            .cloned()
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.components().collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.dirs
+            .lock()
+            .unwrap()
+            .extend(path.ancestors().map(Path::to_path_buf));
+        Ok(())
+    }
+
+    fn scan(&self, path: &Path) -> io::Result<Vec<String>> {
+        if !self.dirs.lock().unwrap().contains(path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let dirs = self.dirs.lock().unwrap();
+        let files = self.files.lock().unwrap();
+        let links = self.links.lock().unwrap();
+        Ok(dirs
+            .iter()
+            .chain(files.keys())
+            .chain(links.keys())
+            .filter(|entry| entry.parent() == Some(path))
+            .filter_map(|entry| Some(entry.file_name()?.to_str()?.to_string()))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        *self
+            .reads
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default() += 1;
+        self.file(path)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.file(path)?.len() as u64)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        self.file(path)?;
+        let modified = self.modified.lock().unwrap();
+        Ok(modified
+            .get(path)
+            .copied()
+            .unwrap_or(SystemTime::UNIX_EPOCH))
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        self.file(path)?;
+        self.modified
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), time);
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.create_dir(path.parent().unwrap())?;
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.create_dir(path.parent().unwrap())?;
+        self.files
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .extend_from_slice(contents);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let contents = files.remove(from).ok_or(io::ErrorKind::NotFound)?;
+        files.insert(to.to_path_buf(), contents);
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let contents = self.file(from)?;
+        self.write(to, &contents)
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let file = self.files.lock().unwrap().remove(path);
+        let link = self.links.lock().unwrap().remove(path);
+        match (file, link) {
+            (None, None) => Err(io::ErrorKind::NotFound.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        if !self.scan(path)?.is_empty() {
+            return Err(io::ErrorKind::DirectoryNotEmpty.into());
+        }
+        self.dirs.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.create_dir(link.parent().unwrap())?;
+        let mut links = self.links.lock().unwrap();
+        if links.contains_key(link) || self.files.lock().unwrap().contains_key(link) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        links.insert(link.to_path_buf(), target.to_path_buf());
+        Ok(())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let links = self.links.lock().unwrap();
+        links
+            .get(path)
+            .cloned()
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn available_space(&self, _path: &Path) -> io::Result<u64> {
+        Ok(self.available_space.lock().unwrap().unwrap_or(u64::MAX))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.file(path)?;
+        self.modes.lock().unwrap().insert(path.to_path_buf(), mode);
+        Ok(())
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        self.file(path)?;
+        self.owners
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (uid, gid));
+        Ok(())
+    }
+}
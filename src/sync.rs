@@ -0,0 +1,2617 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::io;
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Scope};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::archive;
+use crate::cancel::CancelToken;
+use crate::concurrency::Concurrency;
+use crate::config::{
+    format_size, parse_size, parse_size_setting, Config, Item, Kind, Mtime, Order,
+};
+use crate::cookies::Cookies;
+use crate::downloader::{
+    download_batch, download_video, post_process_args, save_failure_log, DEFAULT_FORMAT_FALLBACKS,
+    DEFAULT_RETRIES, DEFAULT_RETRY_DELAY,
+};
+use crate::entries::{self, Entries, Entry, Seen};
+use crate::error::SyncError;
+use crate::failure::{Failure, FailureKind};
+use crate::fallback::{self, Fallback};
+use crate::hooks::{self, Hooks};
+use crate::i18n::tr;
+use crate::manifest::{self, Manifest};
+use crate::media::MediaFormat;
+use crate::observer::Observer;
+use crate::permissions::Permissions;
+use crate::playlist::{
+    get_playlist_path, parse_upload_date, sanitize_filename, spawn_listers, write_playlist, Listing,
+};
+use crate::postprocess::{self, PostProcessor};
+use crate::runner::{CommandRunner, YtDlp};
+use crate::state::{Change, InProgress, Organized, State};
+use crate::storage::{LocalStorage, Storage};
+use crate::throttle::Throttle;
+use crate::{chapters, m3u, mirror, organize, probe, sidecar, update, views};
+
+// Options that apply to every playlist synced in a run.
+pub struct SyncOptions {
+    pub fallback: Option<Fallback>,
+    pub batch: bool,
+    pub verbose: bool,
+    pub cancel: CancelToken,
+    pub runner: Arc<dyn CommandRunner>,
+    pub storage: Arc<dyn Storage>,
+    pub data_dir: PathBuf,
+    pub check_for_updates: bool,
+    // Video IDs never downloaded from any playlist.
+    pub blacklist: HashSet<String>,
+    // In bytes, for playlists that don't set their own.
+    pub min_free_space: Option<u64>,
+    // How many bytes a run may download, and how many it has so far.
+    pub max_bytes: Option<u64>,
+    pub downloaded_bytes: AtomicU64,
+    // No new downloads are started after this point.
+    pub deadline: Option<Instant>,
+    pub throttle: Arc<Throttle>,
+    // How many times a download that failed is tried again, waiting retry_delay before the first
+    // retry and twice as long before each one after.
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub state: Mutex<State>,
+    pub preserve_unknown_lines: bool,
+    pub playlist_separator: m3u::Separator,
+    pub playlist_encoding: m3u::Encoding,
+    pub playlist_format: m3u::PlaylistFormat,
+    // Whether playlist files list paths relative to themselves rather than absolute ones.
+    pub playlist_relative: bool,
+    pub timings: bool,
+    pub post_jobs: usize,
+    pub jobs: usize,
+    // Prune every playlist, not only those that ask for it.
+    pub prune: bool,
+    // Where pruned files are moved to, for playlists that don't set their own.
+    pub trash: Option<PathBuf>,
+    pub dry_run: bool,
+    pub hooks: Hooks,
+    // The cookies yt-dlp signs in with, for playlists that don't set their own.
+    pub cookies: Cookies,
+    // The manifests of the locations synced so far, as saved, so playlists sharing a location
+    // don't each load it again.
+    pub manifests: Mutex<HashMap<String, Manifest>>,
+}
+
+impl SyncOptions {
+    // Options that sync with the settings of a config and the defaults of everything else, as a
+    // run without any command line options does.
+    pub fn new(
+        config: &Config,
+        runner: Arc<dyn CommandRunner>,
+        storage: Arc<dyn Storage>,
+        data_dir: PathBuf,
+        state: State,
+    ) -> Result<SyncOptions, String> {
+        Ok(SyncOptions {
+            fallback: config.fallback.clone(),
+            batch: false,
+            verbose: false,
+            cancel: CancelToken::default(),
+            runner,
+            storage,
+            data_dir,
+            check_for_updates: config.check_for_updates,
+            blacklist: config.blacklist.iter().cloned().collect(),
+            min_free_space: parse_size_setting("min_free_space", &config.min_free_space)?,
+            max_bytes: parse_size_setting("max_bytes_per_run", &config.max_bytes_per_run)?,
+            downloaded_bytes: AtomicU64::new(0),
+            deadline: None,
+            throttle: Arc::default(),
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            state: Mutex::new(state),
+            preserve_unknown_lines: false,
+            playlist_separator: m3u::Separator::default(),
+            playlist_encoding: m3u::Encoding::default(),
+            playlist_format: m3u::PlaylistFormat::default(),
+            playlist_relative: false,
+            timings: false,
+            post_jobs: 0,
+            jobs: 1,
+            prune: false,
+            trash: None,
+            dry_run: false,
+            hooks: config.hooks.clone(),
+            cookies: Cookies::of(config),
+            manifests: Mutex::default(),
+        })
+    }
+
+    // Whether new downloads may still be started, which stops when the sync is cancelled or runs
+    // past its deadline.
+    pub fn can_start_downloads(&self) -> bool {
+        !self.cancel.is_cancelled()
+            && self
+                .deadline
+                .is_none_or(|deadline| Instant::now() < deadline)
+            && !self.over_budget()
+    }
+
+    // Whether the run has downloaded as much as it may.
+    pub fn over_budget(&self) -> bool {
+        self.max_bytes
+            .is_some_and(|max_bytes| self.downloaded_bytes.load(Ordering::Relaxed) >= max_bytes)
+    }
+
+    // Whether videos are post-processed on their own pool of threads after downloading. Batches
+    // leave it to their one yt-dlp process.
+    pub fn separate_post_processing(&self) -> bool {
+        self.post_jobs > 0 && !self.batch
+    }
+
+    // Whether videos are downloaded several at a time, each by a yt-dlp process of its own.
+    pub fn parallel_downloads(&self) -> bool {
+        self.jobs > 1 && !self.batch
+    }
+
+    // Where yt-dlp keeps the details of downloaded videos waiting to be post-processed.
+    pub fn post_process_dir(&self) -> PathBuf {
+        self.data_dir.join("postprocess")
+    }
+}
+
+// What happened while syncing a playlist.
+pub struct SyncReport {
+    pub playlist_id: String,
+    pub location: String,
+    pub downloaded: usize,
+    pub failures: Vec<Failure>,
+    // New videos left for a later run because the run reached its deadline.
+    pub deferred: usize,
+    // New videos not attempted because they have been unavailable in several runs.
+    pub skipped_unavailable: usize,
+    // Downloads interrupted in an earlier run that were picked up again.
+    pub resumed: usize,
+    // Files of older videos removed to keep only the newest ones.
+    pub removed: usize,
+    // Files of videos no longer in the playlist that were pruned, or would be in a dry run, by
+    // their path inside the location.
+    pub pruned: Vec<String>,
+    pub dry_run: bool,
+    // Whether downloads stopped because the location ran low on free space.
+    pub low_on_space: bool,
+    // Why post-download hooks failed, for the report at the end of the run.
+    pub hook_failures: Vec<String>,
+    // Only measured when asked for with --timings.
+    pub timings: Option<Timings>,
+    // The size of the files downloaded for the playlist in this run.
+    pub bytes_downloaded: u64,
+    // How much disk space the videos in the playlist's location take up, going by its manifest.
+    pub location_bytes: u64,
+    // Why the playlist couldn't be synced, such as its listing failing, in which case nothing else
+    // in the report is filled in.
+    pub error: Option<String>,
+}
+
+impl SyncReport {
+    fn failed(playlist: &Item, error: &SyncError, dry_run: bool) -> SyncReport {
+        SyncReport {
+            playlist_id: playlist.id.clone(),
+            location: playlist.location.clone(),
+            downloaded: 0,
+            failures: Vec::new(),
+            deferred: 0,
+            skipped_unavailable: 0,
+            resumed: 0,
+            removed: 0,
+            pruned: Vec::new(),
+            dry_run,
+            low_on_space: false,
+            hook_failures: Vec::new(),
+            timings: None,
+            bytes_downloaded: 0,
+            location_bytes: 0,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+// How long each phase of syncing a playlist took. Listing runs in the background, so it overlaps
+// with the other phases.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Timings {
+    pub listing: Duration,
+    pub scanning: Duration,
+    pub downloading: Duration,
+    pub post_processing: Duration,
+    pub writing_playlist: Duration,
+}
+
+// Copy the files yt-sync created in a location to a mirror of it, journaling what changed there.
+// A dry run only finds out what would change.
+fn mirror_location(
+    location: &str,
+    mirror: &str,
+    options: &SyncOptions,
+) -> Result<mirror::Mirrored, Box<dyn std::error::Error>> {
+    let mut state = options.state.lock().unwrap();
+    let storage = &*options.storage;
+    // A dry run doesn't start tracking locations, so those that don't exist yet have no files.
+    let managed = |location| match manifest::managed(storage, location, &state) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeSet::new()),
+        managed => managed,
+    };
+    let (managed, mirrored) = (managed(location)?, managed(mirror)?);
+    let changes = mirror::update(
+        storage,
+        Path::new(location),
+        &managed,
+        Path::new(mirror),
+        &mirrored,
+        options.dry_run,
+    )?;
+    if options.dry_run {
+        return Ok(changes);
+    }
+    for (paths, created) in [(&changes.copied, true), (&changes.deleted, false)] {
+        let (location, paths) = (mirror.to_string(), paths.clone());
+        let change = match created {
+            true => Change::Managed { location, paths },
+            false => Change::Unmanaged { location, paths },
+        };
+        state.record(storage, &options.data_dir, change)?;
+    }
+    Ok(changes)
+}
+
+// A download that ran on a thread of its own.
+pub struct Downloaded {
+    video_id: String,
+    result: Result<(), Failure>,
+    timings: Timings,
+}
+
+// Downloads running on threads of their own, as many at once as the concurrency allows.
+struct ParallelDownloads<'scope, 'env> {
+    scope: &'scope Scope<'scope, 'env>,
+    concurrency: Concurrency,
+    running: usize,
+    sender: mpsc::Sender<Downloaded>,
+    finished: Receiver<Downloaded>,
+}
+
+// A playlist being synced while its listing is still arriving.
+struct PendingSync<'a, 'scope> {
+    playlist_id: &'a str,
+    location: &'a str,
+    format: &'a MediaFormat,
+    format_fallbacks: &'a [String],
+    options: &'a SyncOptions,
+    observer: &'a dyn Observer,
+    manifest: Manifest,
+    // The videos recorded in the playlist's download archive, if it has one.
+    archived: HashSet<String>,
+    // The hooks run after each download, the global one first.
+    post_commands: Vec<&'a str>,
+    hook_failures: Vec<String>,
+    entries: Entries,
+    seen: Seen,
+    order: Order,
+    // Videos to download once the listing is done.
+    queued: Vec<Entry>,
+    downloaded: HashSet<String>,
+    failures: Vec<Failure>,
+    deferred: usize,
+    skipped_unavailable: usize,
+    // Downloads started again after being interrupted in an earlier run.
+    resumed: usize,
+    // Video IDs never downloaded from this playlist, on top of the global blacklist.
+    blacklist: &'a [String],
+    keep_last: Option<usize>,
+    // In bytes.
+    min_free_space: Option<u64>,
+    low_on_space: bool,
+    permissions: Permissions,
+    mtime: Mtime,
+    sidecar: bool,
+    // The positions in the playlist of the videos being downloaded, by video ID. Only kept when
+    // downloads are tagged with track numbers.
+    tracks: HashMap<String, usize>,
+    // The videos being downloaded, by video ID, so their sizes count towards the download budget
+    // once they are done, and they can be given their permissions and recorded in the manifest.
+    downloading: HashMap<String, Entry>,
+    bytes_downloaded: u64,
+    removed: usize,
+    pruned: Vec<String>,
+    timings: Timings,
+    // Only set when post-processing separately.
+    post: Option<PostProcessor>,
+    // Only set when downloading in parallel.
+    parallel: Option<ParallelDownloads<'scope, 'a>>,
+}
+
+impl<'a> PendingSync<'a, '_> {
+    // Record a listed video, downloading it straight away unless downloads are batched, ordered,
+    // or can't be started any more.
+    fn add(
+        &mut self,
+        video_id: String,
+        title: &str,
+        uploaded_at: Option<i64>,
+        uploader: Option<String>,
+    ) -> io::Result<()> {
+        let file_name = sanitize_filename(&format!(
+            "{} [{}].{}",
+            title,
+            video_id,
+            self.format.extension()
+        ));
+        // Blacklisted videos are left out of the m3u file too.
+        if self.options.blacklist.contains(&video_id) || self.blacklist.contains(&video_id) {
+            if self.seen.insert(&video_id) && self.options.verbose {
+                self.observer
+                    .on_detail(&tr!("skipping-blacklisted", video = video_id.as_str()));
+            }
+            return Ok(());
+        }
+        let entry = Entry {
+            video_id,
+            index: self.entries.len(),
+            file_name,
+            title: title.to_string(),
+            uploaded_at,
+            uploader,
+        };
+        let video_id = entry.video_id.as_str();
+        let new = self.seen.insert(video_id);
+        if new && self.manifest.find(video_id, self.format.name).is_some() {
+            self.follow_retitle(video_id, title, &entry.file_name)?;
+        } else if new && !self.is_archived(video_id) {
+            if self.format.album.is_some() {
+                self.tracks.insert(video_id.to_string(), entry.index + 1);
+            }
+            if self.options.state.lock().unwrap().is_unavailable(video_id) {
+                self.skipped_unavailable += 1;
+            } else if self.options.batch
+                || self.order != Order::Playlist
+                || self.keep_last.is_some()
+            {
+                self.queued.push(entry.clone());
+            } else {
+                self.download(entry.clone());
+            }
+        }
+        while self.take_download(false) {}
+        if let Some(post) = &self.post {
+            for finished in post.take_finished() {
+                self.post_processed(finished);
+            }
+        }
+        self.entries.push(entry)
+    }
+
+    // Drop all but the newest videos from the playlist, apart from pinned ones, deleting the files
+    // of those downloaded already. Videos are newest by upload date if every one has one, and
+    // otherwise by position, taking later entries as newer.
+    fn drop_older_than(&mut self, keep_last: usize, pinned: &[String]) -> io::Result<()> {
+        // Videos listed more than once count where they were first listed.
+        let (mut listed, mut dated) = (Seen::default(), true);
+        self.entries.for_each(|entry| {
+            if listed.insert(&entry.video_id) {
+                dated &= entry.uploaded_at.is_some();
+            }
+        })?;
+        // Only the newest are held on to, with later entries winning ties.
+        let (mut listed, mut newest, mut position) = (Seen::default(), BinaryHeap::new(), 0);
+        self.entries.for_each(|entry| {
+            if !listed.insert(&entry.video_id) {
+                return;
+            }
+            position += 1;
+            let uploaded_at = entry.uploaded_at.filter(|_| dated);
+            newest.push(Reverse((uploaded_at, position, entry.video_id.clone())));
+            if newest.len() > keep_last {
+                newest.pop();
+            }
+        })?;
+        let kept: HashSet<String> = newest
+            .into_iter()
+            .map(|Reverse((_, _, video_id))| video_id)
+            .chain(pinned.iter().cloned())
+            .collect();
+
+        self.queued.retain(|entry| kept.contains(&entry.video_id));
+        let mut dropped = Vec::new();
+        self.entries.retain(|entry| {
+            if kept.contains(&entry.video_id) {
+                return true;
+            }
+            dropped.push(entry.video_id.clone());
+            false
+        })?;
+        for video_id in dropped {
+            let Some(path) = self.manifest.find(&video_id, self.format.name) else {
+                continue;
+            };
+            let path = path.to_string();
+            let chapters =
+                (self.manifest.videos.remove(&video_id)).map_or(Vec::new(), |video| video.chapters);
+            self.removed += 1;
+            if self.options.dry_run {
+                self.observer
+                    .on_notice(&tr!("would-remove", file = path.as_str()));
+                continue;
+            }
+            let mut removed = vec![sidecar::name(&path), path.clone()];
+            removed.extend(chapters.into_iter().map(|chapter| chapter.path));
+            for file in &removed[1..] {
+                discard(&*self.options.storage, self.location, file, None)?;
+            }
+            self.manage(removed, false);
+            if self.options.verbose {
+                self.observer
+                    .on_detail(&tr!("removed-older", file = path.as_str()));
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the location has at least the minimum free space, saying so the first time it
+    // doesn't. If the free space can't be found out, downloads go ahead.
+    fn has_free_space(&mut self) -> bool {
+        if self.low_on_space {
+            return false;
+        }
+        let Some(min_free_space) = self.min_free_space else {
+            return true;
+        };
+        match self
+            .options
+            .storage
+            .available_space(Path::new(self.location))
+        {
+            Ok(available) if available < min_free_space => {
+                self.observer.on_error(&tr!(
+                    "low-on-space",
+                    available = format_size(available),
+                    location = self.location,
+                    minimum = format_size(min_free_space)
+                ));
+                self.low_on_space = true;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    // Keep pinned videos that weren't listed in the m3u file, after the rest of the playlist, as
+    // long as their files are still there.
+    fn keep_pinned(&mut self, pinned: &[String]) -> io::Result<()> {
+        for video_id in pinned {
+            if self.seen.contains(video_id) {
+                continue;
+            }
+            let Some(path) = self.manifest.find(video_id, self.format.name) else {
+                continue;
+            };
+            let entry = Entry {
+                video_id: video_id.clone(),
+                index: self.entries.len(),
+                file_name: path.to_string(),
+                title: self.manifest.videos[video_id].title.clone(),
+                uploaded_at: None,
+                uploader: None,
+            };
+            self.seen.insert(video_id);
+            self.entries.push(entry)?;
+        }
+        Ok(())
+    }
+
+    // Remove the files yt-sync downloaded to the location whose videos are no longer in the
+    // playlist, moving them to the trash directory if there is one. Only run once the whole
+    // playlist is listed, after pinned videos are kept, so blacklisted and pinned videos count as
+    // seen. Files added by hand are left alone. A dry run only reports the files.
+    fn prune(&mut self, trash: Option<&Path>) -> io::Result<()> {
+        let storage = &*self.options.storage;
+        let managed = {
+            let state = self.options.state.lock().unwrap();
+            manifest::managed(storage, self.location, &state)?
+        };
+        let gone: Vec<(String, String, Vec<String>)> = (self.manifest.videos.iter())
+            .filter(|(video_id, video)| {
+                !self.seen.contains(video_id) && managed.contains(&video.path)
+            })
+            .map(|(video_id, video)| {
+                let chapters = video.chapters.iter().map(|chapter| chapter.path.clone());
+                (video_id.clone(), video.path.clone(), chapters.collect())
+            })
+            .collect();
+        for (video_id, path, chapters) in gone {
+            if !self.options.dry_run {
+                let mut removed = vec![sidecar::name(&path), path.clone()];
+                removed.extend(chapters);
+                for file in &removed[1..] {
+                    discard(storage, self.location, file, trash)?;
+                }
+                self.manifest.videos.remove(&video_id);
+                self.manage(removed, false);
+                if self.options.verbose {
+                    self.observer
+                        .on_detail(&tr!("pruned-file", file = path.as_str()));
+                }
+            }
+            self.pruned.push(path);
+        }
+        Ok(())
+    }
+
+    // Give a downloaded video that was retitled on YouTube its new title, renaming its file after
+    // it along with its sidecar and chapters, so it keeps the name yt-dlp would give it instead of
+    // being downloaded again. Files with other names, such as organized ones, keep them.
+    fn follow_retitle(&mut self, video_id: &str, title: &str, file_name: &str) -> io::Result<()> {
+        let Some(video) = self.manifest.videos.get_mut(video_id) else {
+            return Ok(());
+        };
+        if video.title == title || self.options.dry_run {
+            return Ok(());
+        }
+        video.title = title.to_string();
+        let named_by_yt_dlp = !video.path.contains('/')
+            && video
+                .path
+                .ends_with(&format!("[{}].{}", video_id, self.format.extension()));
+        if video.path == file_name || !named_by_yt_dlp {
+            return Ok(());
+        }
+        let storage = &*self.options.storage;
+        let location = Path::new(self.location);
+        if storage.size(&location.join(file_name)).is_ok() {
+            self.observer.on_error(&tr!(
+                "rename-exists",
+                file = video.path.as_str(),
+                new_file = file_name
+            ));
+            return Ok(());
+        }
+
+        let old_path = mem::replace(&mut video.path, file_name.to_string());
+        let (old_stem, new_stem) = (stem(&old_path), stem(file_name));
+        let mut renamed = vec![(old_path.clone(), file_name.to_string())];
+        for chapter in &mut video.chapters {
+            if let Some(rest) = chapter.path.strip_prefix(old_stem) {
+                let path = format!("{}{}", new_stem, rest);
+                renamed.push((mem::replace(&mut chapter.path, path.clone()), path));
+            }
+        }
+        let (mut unmanaged, mut managed) = (Vec::new(), Vec::new());
+        for (from, to) in renamed {
+            let (from_file, to_file) = (location.join(&from), location.join(&to));
+            storage.rename(&from_file, &to_file)?;
+            if storage.size(&sidecar::path(&from_file)).is_ok() {
+                sidecar::rename(storage, &from_file, &to_file)?;
+                unmanaged.push(sidecar::name(&from));
+                managed.push(sidecar::name(&to));
+            }
+            unmanaged.push(from);
+            managed.push(to);
+        }
+        self.manage(unmanaged, false);
+        self.manage(managed, true);
+        if self.options.verbose {
+            self.observer.on_detail(&tr!(
+                "renamed-retitled",
+                file = old_path,
+                new_file = file_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Whether a video is recorded in the download archive, which keeps it from being downloaded
+    // again even when its file isn't here, as yt-dlp does.
+    fn is_archived(&self, video_id: &str) -> bool {
+        let archived = self.archived.contains(video_id);
+        if archived && self.options.verbose {
+            self.observer
+                .on_detail(&tr!("skipping-archived", video = video_id));
+        }
+        archived
+    }
+
+    // Move the videos downloaded in this sync to where the organize template puts them, going by
+    // the tags yt-dlp gave them. A video that can't be moved stays where it was downloaded.
+    fn organize_downloads(&mut self, template: &str) -> io::Result<()> {
+        let mut downloaded = Vec::new();
+        self.entries.for_each(|entry| {
+            let path = self.manifest.find(&entry.video_id, self.format.name);
+            if let Some(file_name) = path.filter(|_| self.downloaded.contains(&entry.video_id)) {
+                downloaded.push((entry.video_id.clone(), file_name.to_string()));
+            }
+        })?;
+        let location = Path::new(self.location);
+        for (video_id, file_name) in downloaded {
+            let file = location.join(&file_name);
+            let path = match organize::destination(template, &file, &video_id) {
+                Ok(path) => path,
+                Err(e) => {
+                    self.observer.on_error(&tr!(
+                        "organize-failed",
+                        file = file_name,
+                        error = e.to_string()
+                    ));
+                    continue;
+                }
+            };
+            let destination = location.join(&path);
+            if self.options.storage.size(&destination).is_ok() {
+                self.observer.on_error(&tr!(
+                    "organize-exists",
+                    file = file_name,
+                    destination = destination.display().to_string()
+                ));
+                continue;
+            }
+            if let Some(parent) = destination.parent() {
+                self.options.storage.create_dir(parent)?;
+            }
+            self.options.storage.rename(&file, &destination)?;
+            sidecar::rename(&*self.options.storage, &file, &destination)?;
+            let mut moved = vec![path.clone()];
+            if self
+                .options
+                .storage
+                .size(&sidecar::path(&destination))
+                .is_ok()
+            {
+                moved.push(sidecar::name(&path));
+            }
+            self.manage(vec![sidecar::name(&file_name), file_name], false);
+            self.manage(moved, true);
+            let change = Change::Organized {
+                video_id: video_id.clone(),
+                file: Organized {
+                    location: self.location.to_string(),
+                    path: path.clone(),
+                },
+            };
+            let mut state = self.options.state.lock().unwrap();
+            state.record(&*self.options.storage, &self.options.data_dir, change)?;
+            if let Some(video) = self.manifest.videos.get_mut(&video_id) {
+                video.path = path;
+            }
+        }
+        Ok(())
+    }
+
+    // The files of the videos of the playlist that are available locally, in playlist order, by
+    // their path inside the location, with the title players show them by and the entry of their
+    // video. Videos split into chapters are there as their chapters instead.
+    fn available(
+        &mut self,
+    ) -> io::Result<impl Iterator<Item = io::Result<(String, String, Entry)>> + '_> {
+        let (manifest, format) = (&self.manifest, self.format);
+        let files = self.entries.iter()?.flat_map(move |entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => return vec![Err(e)],
+            };
+            let Some(video) = manifest.videos.get(&entry.video_id) else {
+                return Vec::new();
+            };
+            if format.split_chapters && !video.chapters.is_empty() {
+                return (video.chapters.iter())
+                    .map(|chapter| Ok((chapter.path.clone(), chapter.title.clone(), entry.clone())))
+                    .collect();
+            }
+            match video.format == format.name {
+                true => vec![Ok((video.path.clone(), video.title.clone(), entry))],
+                false => Vec::new(),
+            }
+        });
+        Ok(files)
+    }
+
+    // The available files for the playlist file, by their path and title.
+    fn tracks(&mut self) -> io::Result<impl Iterator<Item = io::Result<(String, String)>> + '_> {
+        let files = self.available()?;
+        Ok(files.map(|file| file.map(|(path, title, _)| (path, title))))
+    }
+
+    // Sort the queued videos into the order they should be downloaded in. Upload dates are only
+    // used if every video has one, as they can't be compared with playlist positions.
+    fn sort_queued(&mut self) {
+        if self.order == Order::Playlist {
+            return;
+        }
+        if self.queued.iter().all(|entry| entry.uploaded_at.is_some()) {
+            self.queued.sort_by_key(|entry| entry.uploaded_at);
+        }
+        if self.order == Order::NewestFirst {
+            self.queued.reverse();
+        }
+    }
+
+    // Download a single video, unless downloads can't be started any more. When downloading in
+    // parallel, it first waits for a free slot and then runs on a thread of its own. When
+    // post-processing separately, it is queued for that once downloaded. Downloads are tracked in
+    // the state while they run, so one that is interrupted is known to be resuming next time.
+    fn download(&mut self, entry: Entry) {
+        while self
+            .parallel
+            .as_ref()
+            .is_some_and(|parallel| parallel.running >= parallel.concurrency.limit())
+        {
+            self.take_download(true);
+        }
+        if !self.options.can_start_downloads() || self.options.dry_run || !self.has_free_space() {
+            self.deferred += 1;
+            return;
+        }
+        let video_id = entry.video_id.clone();
+        self.observer
+            .on_video_start(&video_id, entry.index, &entry.file_name);
+        self.track_download(&video_id, true);
+        self.downloading.insert(video_id.clone(), entry);
+        let format = self.format_of(&video_id);
+        let (location, format_fallbacks, options, observer) = (
+            self.location,
+            self.format_fallbacks,
+            self.options,
+            self.observer,
+        );
+        match &mut self.parallel {
+            Some(parallel) => {
+                let sender = parallel.sender.clone();
+                parallel.running += 1;
+                parallel.scope.spawn(move || {
+                    let mut timings = Timings::default();
+                    let result = download_video(
+                        &video_id,
+                        location,
+                        &format,
+                        format_fallbacks,
+                        options,
+                        observer,
+                        &mut timings,
+                    );
+                    let _ = sender.send(Downloaded {
+                        video_id,
+                        result,
+                        timings,
+                    });
+                });
+            }
+            None => {
+                let mut timings = Timings::default();
+                let result = download_video(
+                    &video_id,
+                    location,
+                    &format,
+                    format_fallbacks,
+                    options,
+                    observer,
+                    &mut timings,
+                );
+                self.download_finished(Downloaded {
+                    video_id,
+                    result,
+                    timings,
+                });
+            }
+        }
+    }
+
+    // The format a video is downloaded in, tagged with its track number if the playlist has them.
+    fn format_of(&self, video_id: &str) -> MediaFormat {
+        MediaFormat {
+            track: self.tracks.get(video_id).copied(),
+            ..self.format.clone()
+        }
+    }
+
+    // Handle a download that finished on another thread, waiting for one if asked to. Returns
+    // whether there was one.
+    fn take_download(&mut self, wait: bool) -> bool {
+        let Some(parallel) = &mut self.parallel else {
+            return false;
+        };
+        if parallel.running == 0 {
+            return false;
+        }
+        let downloaded = match wait {
+            true => parallel.finished.recv().ok(),
+            false => parallel.finished.try_recv().ok(),
+        };
+        let Some(downloaded) = downloaded else {
+            return false;
+        };
+        parallel.running -= 1;
+        let kind = downloaded.result.as_ref().map(|_| ()).map_err(|f| f.kind);
+        parallel.concurrency.finished(kind);
+        self.download_finished(downloaded);
+        true
+    }
+
+    // Wait for every download running on another thread to finish.
+    fn finish_downloads(&mut self) {
+        while self.take_download(true) {}
+    }
+
+    fn download_finished(&mut self, downloaded: Downloaded) {
+        let Downloaded {
+            video_id,
+            result,
+            timings,
+        } = downloaded;
+        self.timings.downloading += timings.downloading;
+        self.timings.post_processing += timings.post_processing;
+        // Downloads that were stopped or may work later keep their partial files to resume from.
+        let finished = match &result {
+            Ok(()) => true,
+            Err(failure) => !failure.kind.is_transient() && !self.options.cancel.is_cancelled(),
+        };
+        if finished {
+            self.track_download(&video_id, false);
+        }
+        match (result, &self.post) {
+            (Ok(()), Some(post)) => post.submit(postprocess::Job {
+                args: post_process_args(
+                    &video_id,
+                    self.location,
+                    &self.format_of(&video_id),
+                    self.options,
+                ),
+                video_id,
+            }),
+            (Ok(()), None) => {
+                self.observer.on_video_done(&video_id, true);
+                self.finish_download(video_id);
+            }
+            (Err(_), _) if self.options.cancel.is_cancelled() => {
+                self.observer.on_video_done(&video_id, false);
+            }
+            (Err(failure), _) => {
+                self.observer.on_video_done(&video_id, false);
+                self.record_failure(failure);
+            }
+        }
+    }
+
+    // Complete a download yt-dlp reported as finished, which only counts as downloaded once its
+    // file is found, as it is recorded in the manifest under the name it really has.
+    fn finish_download(&mut self, video_id: String) {
+        let Some(entry) = self.downloading.remove(&video_id) else {
+            return;
+        };
+        self.tracks.remove(&video_id);
+        let Some(file_name) = self.find_file(&video_id, &entry.file_name) else {
+            self.observer.on_error(&tr!(
+                "file-missing",
+                video = video_id.as_str(),
+                location = self.location
+            ));
+            self.record_failure(Failure {
+                video_id,
+                kind: FailureKind::Unknown,
+                log: None,
+            });
+            return;
+        };
+        self.finish_file(&entry, file_name);
+        if let Some(uploader) = entry.uploader {
+            let mut state = self.options.state.lock().unwrap();
+            state.uploaders.insert(video_id.clone(), uploader);
+        }
+        self.downloaded.insert(video_id);
+    }
+
+    // Find the file yt-dlp saved a video to. It is usually the name predicted from the title, but
+    // yt-dlp may have sanitized the title another way, so otherwise it is looked for by the ID and
+    // extension yt-dlp ends the name with.
+    fn find_file(&self, video_id: &str, predicted: &str) -> Option<String> {
+        let storage = &*self.options.storage;
+        let location = Path::new(self.location);
+        if storage.size(&location.join(predicted)).is_ok() {
+            return Some(predicted.to_string());
+        }
+        let suffix = format!(" [{}].{}", video_id, self.format.extension());
+        let names = storage.scan(location).ok()?;
+        names.into_iter().find(|name| name.ends_with(&suffix))
+    }
+
+    // Add the size of a finished download to the bytes downloaded in the run, write its sidecar,
+    // record its chapters, and give it and its chapters the permissions, owner and modification
+    // time of the playlist. Failing to is reported without failing the download.
+    fn finish_file(&mut self, entry: &Entry, file_name: String) {
+        let video_id = entry.video_id.as_str();
+        let path = Path::new(self.location).join(&file_name);
+        let size = self.options.storage.size(&path).unwrap_or(0);
+        self.options
+            .downloaded_bytes
+            .fetch_add(size, Ordering::Relaxed);
+        self.bytes_downloaded += size;
+        let mut created = vec![file_name.clone()];
+        if self.sidecar {
+            match sidecar::write(&*self.options.storage, &path, video_id) {
+                Ok(()) => created.push(sidecar::name(&file_name)),
+                Err(e) => self.observer.on_error(&tr!(
+                    "sidecar-failed",
+                    path = path.display().to_string(),
+                    error = e.to_string()
+                )),
+            }
+        }
+        let chapters = match self.format.split_chapters {
+            true => chapters::find(&*self.options.storage, self.location, &file_name)
+                .unwrap_or_else(|e| {
+                    self.observer.on_error(&tr!(
+                        "chapters-failed",
+                        path = path.display().to_string(),
+                        error = e.to_string()
+                    ));
+                    Vec::new()
+                }),
+            false => Vec::new(),
+        };
+        created.extend(chapters.iter().map(|chapter| chapter.path.clone()));
+        let files: Vec<PathBuf> = iter::once(path.clone())
+            .chain((chapters.iter()).map(|chapter| Path::new(self.location).join(&chapter.path)))
+            .collect();
+        self.manage(created, true);
+        self.manifest
+            .insert(video_id, &entry.title, &file_name, self.format.name);
+        if let Some(video) = self.manifest.videos.get_mut(video_id) {
+            video.chapters = chapters;
+        }
+        for file in &files {
+            if let Err(e) = self.permissions.apply(&*self.options.storage, file) {
+                self.observer.on_error(&tr!(
+                    "permissions-failed",
+                    path = file.display().to_string(),
+                    error = e.to_string()
+                ));
+            }
+        }
+        self.run_post_commands(video_id, &path, &entry.title);
+        if self.mtime == Mtime::Uploaded {
+            // Listings often leave out upload dates, but yt-dlp embeds them in the file.
+            let uploaded_at = entry.uploaded_at.or_else(|| {
+                let tags = probe::probe(&path).ok()?.tags;
+                parse_upload_date(tags.get("date")?)
+            });
+            let Some(uploaded_at) = uploaded_at else {
+                return;
+            };
+            let time = UNIX_EPOCH + Duration::from_secs(uploaded_at.max(0) as u64);
+            for file in &files {
+                if let Err(e) = self.options.storage.set_modified(file, time) {
+                    self.observer.on_error(&tr!(
+                        "mtime-failed",
+                        path = file.display().to_string(),
+                        error = e.to_string()
+                    ));
+                }
+            }
+        }
+    }
+
+    // Run the post-download hooks on a downloaded file, keeping their failures for the report.
+    // They run before its modification time is set, so tagging it doesn't change that.
+    fn run_post_commands(&mut self, video_id: &str, path: &Path, title: &str) {
+        let file = path.to_string_lossy();
+        let env = [
+            ("YT_SYNC_FILE", &*file),
+            ("YT_SYNC_VIDEO_ID", video_id),
+            ("YT_SYNC_TITLE", title),
+            ("YT_SYNC_PLAYLIST", self.playlist_id),
+            ("YT_SYNC_LOCATION", self.location),
+            ("YT_SYNC_FORMAT", self.format.name),
+        ];
+        for command in &self.post_commands {
+            if let Err(e) = hooks::run(command, &env, &self.options.cancel) {
+                let failure = tr!("hook-failed", error = e, video = video_id);
+                self.observer.on_error(&failure);
+                self.hook_failures.push(failure);
+            }
+        }
+    }
+
+    // Journal files yt-sync created in the location, or that are no longer there.
+    fn manage(&self, paths: Vec<String>, created: bool) {
+        let location = self.location.to_string();
+        let change = match created {
+            true => Change::Managed { location, paths },
+            false => Change::Unmanaged { location, paths },
+        };
+        let mut state = self.options.state.lock().unwrap();
+        if let Err(e) = state.record(&*self.options.storage, &self.options.data_dir, change) {
+            self.observer
+                .on_error(&tr!("files-state-failed", error = e.to_string()));
+        }
+    }
+
+    // Journal that a download started or finished, so it is known even if the run is killed.
+    fn track_download(&mut self, video_id: &str, started: bool) {
+        let mut state = self.options.state.lock().unwrap();
+        let video_id = video_id.to_string();
+        let change = if !started {
+            if !state.in_progress.contains_key(&video_id) {
+                return;
+            }
+            Change::Finished { video_id }
+        } else if state
+            .in_progress
+            .get(&video_id)
+            .is_some_and(|download| download.location == self.location)
+        {
+            self.resumed += 1;
+            return;
+        } else {
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            let location = self.location.to_string();
+            Change::Started {
+                video_id,
+                download: InProgress { location, started },
+            }
+        };
+        if let Err(e) = state.record(&*self.options.storage, &self.options.data_dir, change) {
+            self.observer
+                .on_error(&tr!("downloads-state-failed", error = e.to_string()));
+        }
+    }
+
+    // Record a video that finished post-processing, which completes its download.
+    fn post_processed(&mut self, finished: postprocess::Finished) {
+        self.timings.post_processing += finished.elapsed;
+        let video_id = finished.video_id;
+        let _ = self.options.storage.delete(
+            &self
+                .options
+                .post_process_dir()
+                .join(format!("{}.info.json", video_id)),
+        );
+        let stderr = match finished.output {
+            Ok(output) if output.success() => {
+                self.observer.on_video_done(&video_id, true);
+                self.finish_download(video_id);
+                return;
+            }
+            Ok(output) => format!("{}\nerror code: {:?}", output.stderr, output.code),
+            Err(e) => tr!("yt-dlp-unrunnable", error = format!("{:?}", e)),
+        };
+        self.observer.on_video_done(&video_id, false);
+        if !self.options.cancel.is_cancelled() {
+            let log = save_failure_log(
+                self.options,
+                &video_id,
+                &finished.args,
+                &finished.stdout,
+                &stderr,
+            );
+            self.record_failure(Failure {
+                video_id,
+                kind: FailureKind::classify(&stderr),
+                log: log.ok(),
+            });
+        }
+    }
+
+    // Keep a failed download for the report, alerting straight away when signing in is needed, as
+    // that won't fix itself.
+    fn record_failure(&mut self, failure: Failure) {
+        if failure.kind == FailureKind::AuthRequired {
+            self.observer.on_error(&tr!(
+                "video-needs-sign-in",
+                video = failure.video_id.as_str(),
+                playlist = self.playlist_id,
+                advice = self.format.cookies.advice()
+            ));
+        }
+        self.failures.push(failure);
+    }
+}
+
+// Sync a YouTube playlist to a local directory, ensuring no duplicates are downloaded. Videos are
+// downloaded as they arrive from the listing, so the first ones start before it is complete.
+fn sync_playlist(
+    playlist: &Item,
+    listing: &Receiver<Listing>,
+    options: &SyncOptions,
+    observer: &dyn Observer,
+) -> Result<SyncReport, SyncError> {
+    let (id, location) = (&playlist.id, &playlist.location);
+    let verbose = options.verbose;
+    observer.on_playlist_start(id);
+    let invalid = |reason| SyncError::InvalidItem {
+        playlist_id: id.clone(),
+        reason,
+    };
+    if let Some(template) = &playlist.organize {
+        organize::check(template).map_err(invalid)?;
+    }
+    let min_free_space = match &playlist.min_free_space {
+        Some(min_free_space) => Some(parse_size(min_free_space).map_err(|e| {
+            invalid(tr!(
+                "setting-invalid",
+                setting = "min_free_space",
+                error = e
+            ))
+        })?),
+        None => options.min_free_space,
+    };
+    let mut format = MediaFormat::of(playlist).map_err(invalid)?;
+    format.cookies = options.cookies.for_item(playlist);
+    let format_fallbacks = match &playlist.format_fallbacks {
+        Some(format_fallbacks) => format_fallbacks.clone(),
+        None => DEFAULT_FORMAT_FALLBACKS.map(String::from).to_vec(),
+    };
+    let permissions = Permissions::parse(playlist.chmod.as_deref(), playlist.chown.as_deref())
+        .map_err(invalid)?;
+    let m3u_file_path = match playlist.save_playlist {
+        true => Some(get_playlist_path(location, options.playlist_format).map_err(invalid)?),
+        false => None,
+    };
+    if !options.dry_run {
+        options.storage.create_dir(Path::new(location))?;
+    }
+    // Get the list of already downloaded videos.
+    let scan_started = Instant::now();
+    let manifest = {
+        let mut state = options.state.lock().unwrap();
+        let tracked = iter::once(location).chain(&playlist.locations);
+        for location in tracked.filter(|_| !options.dry_run) {
+            manifest::track(&*options.storage, location, &mut state, &options.data_dir)?;
+        }
+        // A playlist that fails partway leaves its location's manifest out, to be loaded afresh.
+        let cached = options.manifests.lock().unwrap().remove(location);
+        match cached {
+            Some(manifest) => manifest,
+            None => Manifest::load(&*options.storage, location, &state)?,
+        }
+    };
+    let archived = match &playlist.download_archive {
+        Some(path) => archive::read(&*options.storage, Path::new(path))?,
+        None => HashSet::new(),
+    };
+    let scanning = scan_started.elapsed();
+
+    if verbose {
+        let video_ids: Vec<&String> = manifest.videos.keys().collect();
+        observer.on_detail(&tr!("manifest-lists", videos = format!("{:?}", video_ids)));
+    }
+
+    // Downloads running in parallel borrow the sync, so they live in a scope that ends with it.
+    let report = thread::scope(|scope| -> Result<SyncReport, SyncError> {
+        let (sender, finished) = mpsc::channel();
+        let mut pending = PendingSync {
+            playlist_id: id,
+            location,
+            format: &format,
+            format_fallbacks: &format_fallbacks,
+            options,
+            observer,
+            manifest,
+            archived,
+            post_commands: [&options.hooks.post_download, &playlist.post_command]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect(),
+            hook_failures: Vec::new(),
+            entries: Entries::new(entries::SPILL_AFTER),
+            seen: Seen::default(),
+            order: playlist.order,
+            queued: Vec::new(),
+            downloaded: HashSet::new(),
+            failures: Vec::new(),
+            deferred: 0,
+            skipped_unavailable: 0,
+            resumed: 0,
+            blacklist: &playlist.blacklist,
+            keep_last: playlist.keep_last,
+            min_free_space,
+            low_on_space: false,
+            permissions,
+            mtime: playlist.mtime,
+            sidecar: playlist.sidecar,
+            tracks: HashMap::new(),
+            downloading: HashMap::new(),
+            bytes_downloaded: 0,
+            removed: 0,
+            pruned: Vec::new(),
+            timings: Timings {
+                scanning,
+                ..Timings::default()
+            },
+            post: options.separate_post_processing().then(|| {
+                PostProcessor::new(
+                    options.post_jobs,
+                    Arc::clone(&options.runner),
+                    options.cancel.clone(),
+                )
+            }),
+            parallel: options.parallel_downloads().then(|| ParallelDownloads {
+                scope,
+                concurrency: Concurrency::new(options.jobs),
+                running: 0,
+                sender,
+                finished,
+            }),
+        };
+
+        // Download videos as they are listed, until the listing of this playlist is done.
+        let listing_result = loop {
+            let received = listing.recv().map_err(|_| SyncError::Listing {
+                playlist_id: id.clone(),
+                reason: tr!("listing-stopped"),
+            })?;
+            match received {
+                Listing::Video(video_info) => {
+                    let uploaded_at = video_info.uploaded_at();
+                    let uploader = video_info.uploader();
+                    pending.add(video_info.id, &video_info.title, uploaded_at, uploader)?
+                }
+                Listing::Done(result, listing_time) => {
+                    pending.timings.listing = listing_time;
+                    break result;
+                }
+            }
+        };
+
+        // If YouTube refused the listing, list the whole playlist again through the fallback
+        // instance, which becomes the authoritative order. Videos handled already are not downloaded twice.
+        // A sync that is stopping keeps what was listed so far, so the m3u still covers it.
+        let listed_completely = match (listing_result, &options.fallback) {
+            (Ok(()), _) => true,
+            (Err(_), _) if !options.can_start_downloads() => false,
+            // The fallback instances only list playlists.
+            (Err(e), Some(fallback)) if playlist.kind() == Kind::Playlist => {
+                observer.on_error(&tr!(
+                    "listing-fallback",
+                    playlist = id.as_str(),
+                    fallback = fallback.url.as_str()
+                ));
+                if verbose {
+                    observer.on_error(&e);
+                }
+                let fallback_started = Instant::now();
+                pending.entries.clear();
+                fallback::list_videos(fallback, id, &mut |video_id, title| {
+                    pending.add(video_id, title, None, None)
+                })
+                .map_err(|e| SyncError::Listing {
+                    playlist_id: id.clone(),
+                    reason: e.to_string(),
+                })?;
+                pending.timings.listing += fallback_started.elapsed();
+                true
+            }
+            (Err(reason), _) => {
+                if FailureKind::classify(&reason) == FailureKind::AuthRequired {
+                    observer.on_error(&tr!(
+                        "playlist-needs-sign-in",
+                        playlist = id.as_str(),
+                        advice = format.cookies.advice()
+                    ));
+                }
+                return Err(SyncError::Listing {
+                    playlist_id: id.clone(),
+                    reason,
+                });
+            }
+        };
+        // Which videos are newest is only known once the whole playlist is listed.
+        if let (Some(keep_last), true) = (playlist.keep_last, listed_completely) {
+            pending.drop_older_than(keep_last, &playlist.pinned)?;
+        }
+        pending.keep_pinned(&playlist.pinned)?;
+        if listed_completely && (playlist.prune || options.prune) {
+            let trash = playlist.trash.as_deref().map(Path::new);
+            pending.prune(trash.or(options.trash.as_deref()))?;
+        }
+
+        observer.on_listing(id, pending.entries.len());
+
+        pending.sort_queued();
+        // A batch is one yt-dlp process for every video, which can't tag each with its own track
+        // number, so playlists with track numbers are downloaded one video at a time.
+        if !options.batch || playlist.track_numbers {
+            for entry in mem::take(&mut pending.queued) {
+                pending.download(entry);
+            }
+            pending.finish_downloads();
+        } else if !options.can_start_downloads() || options.dry_run || !pending.has_free_space() {
+            pending.deferred += pending.queued.len();
+        } else if !pending.queued.is_empty() {
+            for entry in &pending.queued {
+                observer.on_video_start(&entry.video_id, entry.index, &entry.file_name);
+            }
+            let queued: Vec<&str> = pending
+                .queued
+                .iter()
+                .map(|entry| entry.video_id.as_str())
+                .collect();
+            let (finished, failures) = download_batch(
+                &queued,
+                location,
+                &format,
+                options,
+                observer,
+                &mut pending.timings,
+            )?;
+            // A batch is one yt-dlp process, so the budget can only stop the batches after it.
+            for entry in mem::take(&mut pending.queued) {
+                if finished.contains(&entry.video_id) {
+                    let video_id = entry.video_id.clone();
+                    pending.downloading.insert(video_id.clone(), entry);
+                    pending.finish_download(video_id);
+                }
+            }
+            for failure in failures {
+                pending.record_failure(failure);
+            }
+        }
+
+        if let Some(post) = pending.post.take() {
+            for finished in post.finish() {
+                pending.post_processed(finished);
+            }
+        }
+
+        if let Some(template) = &playlist.organize {
+            pending.organize_downloads(template)?;
+        }
+
+        if verbose {
+            let mut file_names = Vec::new();
+            pending
+                .entries
+                .for_each(|entry| file_names.push(entry.file_name.clone()))?;
+            observer.on_detail(&tr!(
+                "playlist-contains",
+                files = format!("{:?}", file_names)
+            ));
+        }
+
+        if !options.dry_run {
+            pending.manifest.save(&*options.storage, location)?;
+            (options.manifests.lock().unwrap())
+                .insert(location.to_string(), pending.manifest.clone());
+        }
+        let location_bytes = pending.manifest.size(&*options.storage, location);
+
+        let playlist_started = Instant::now();
+        if let Some(m3u_file_path) = m3u_file_path.filter(|_| !options.dry_run) {
+            write_playlist(&m3u_file_path, location, pending.tracks()?, options)?;
+        }
+        pending.timings.writing_playlist = playlist_started.elapsed();
+
+        for mirror in &playlist.locations {
+            options.manifests.lock().unwrap().remove(mirror);
+            let mirrored = mirror_location(location, mirror, options).and_then(|changes| {
+                if options.dry_run {
+                    for path in &changes.copied {
+                        observer.on_notice(&tr!(
+                            "would-mirror",
+                            file = path.as_str(),
+                            mirror = mirror.as_str()
+                        ));
+                    }
+                    for path in &changes.deleted {
+                        observer.on_notice(&tr!(
+                            "would-unmirror",
+                            file = path.as_str(),
+                            mirror = mirror.as_str()
+                        ));
+                    }
+                    return Ok(());
+                }
+                if verbose {
+                    observer.on_detail(&tr!(
+                        "mirrored",
+                        mirror = mirror.as_str(),
+                        copied = changes.copied.len(),
+                        deleted = changes.deleted.len()
+                    ));
+                }
+                match playlist.save_playlist {
+                    true => {
+                        let m3u_file_path = get_playlist_path(mirror, options.playlist_format)?;
+                        let tracks = pending.tracks()?;
+                        Ok(write_playlist(&m3u_file_path, mirror, tracks, options)?)
+                    }
+                    false => Ok(()),
+                }
+            });
+            if let Err(e) = mirrored {
+                observer.on_error(&tr!(
+                    "mirror-failed",
+                    playlist = id.as_str(),
+                    mirror = mirror.as_str(),
+                    error = e.to_string()
+                ));
+            }
+        }
+
+        if let (Some(views_dir), false) = (&playlist.views, options.dry_run) {
+            let files = pending.available()?.map(|file| {
+                file.map(|(path, _, entry)| views::Viewed {
+                    path,
+                    uploader: entry.uploader,
+                    uploaded_at: entry.uploaded_at,
+                })
+            });
+            views::update(
+                &*options.storage,
+                Path::new(views_dir),
+                Path::new(location),
+                files,
+            )?;
+        }
+
+        // Remember which videos were unavailable, so ones that stay that way stop being attempted.
+        if !options.dry_run {
+            let mut state = options.state.lock().unwrap();
+            let (storage, data_dir) = (&*options.storage, &options.data_dir);
+            for video_id in &pending.downloaded {
+                if state.unavailable.contains_key(video_id) {
+                    let video_id = video_id.clone();
+                    state.record(storage, data_dir, Change::Available { video_id })?;
+                }
+            }
+            for failure in &pending.failures {
+                if failure.kind == FailureKind::Unavailable {
+                    let count = state
+                        .unavailable
+                        .get(&failure.video_id)
+                        .map_or(1, |n| n + 1);
+                    let video_id = failure.video_id.clone();
+                    state.record(storage, data_dir, Change::Unavailable { video_id, count })?;
+                }
+            }
+            state.save(storage, data_dir)?;
+        }
+
+        Ok(SyncReport {
+            playlist_id: id.clone(),
+            location: location.clone(),
+            downloaded: pending.downloaded.len(),
+            failures: pending.failures,
+            deferred: pending.deferred,
+            skipped_unavailable: pending.skipped_unavailable,
+            resumed: pending.resumed,
+            removed: pending.removed,
+            pruned: pending.pruned,
+            dry_run: options.dry_run,
+            low_on_space: pending.low_on_space,
+            hook_failures: pending.hook_failures,
+            timings: options.timings.then_some(pending.timings),
+            bytes_downloaded: pending.bytes_downloaded,
+            location_bytes,
+            error: None,
+        })
+    })?;
+    observer.on_playlist_done(&report);
+    Ok(report)
+}
+
+// The name of a file without its extension.
+fn stem(file_name: &str) -> &str {
+    file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem)
+}
+
+// Delete a file yt-sync downloaded to a location along with its sidecar, or move them to the same
+// path inside the trash directory if there is one.
+fn discard(
+    storage: &dyn Storage,
+    location: &str,
+    path: &str,
+    trash: Option<&Path>,
+) -> io::Result<()> {
+    let file = Path::new(location).join(path);
+    let Some(trash) = trash else {
+        storage.delete(&file)?;
+        return sidecar::delete(storage, &file);
+    };
+    let to = trash.join(path);
+    if let Some(parent) = to.parent() {
+        storage.create_dir(parent)?;
+    }
+    move_file(storage, &file, &to)?;
+    if storage.size(&sidecar::path(&file)).is_ok() {
+        move_file(storage, &sidecar::path(&file), &sidecar::path(&to))?;
+    }
+    Ok(())
+}
+
+// Move a file, copying it and deleting the original if it can't be renamed, such as to another
+// drive.
+fn move_file(storage: &dyn Storage, from: &Path, to: &Path) -> io::Result<()> {
+    if storage.rename(from, to).is_err() {
+        storage.copy(from, to)?;
+        storage.delete(from)?;
+    }
+    Ok(())
+}
+
+// Syncs playlists for programs that embed yt-sync, the way the yt-sync command does without any
+// command line options. Progress is reported to an observer, which reports nothing by default.
+pub struct Syncer {
+    options: SyncOptions,
+    observer: Box<dyn Observer>,
+}
+
+// Reports nothing.
+struct Silent;
+
+impl Observer for Silent {}
+
+impl Syncer {
+    // A syncer with the settings of a config, downloading with yt-dlp and keeping yt-sync's state
+    // in data_dir.
+    pub fn new(config: &Config, data_dir: &Path) -> Result<Syncer, Box<dyn std::error::Error>> {
+        Syncer::with_storage(config, data_dir, Arc::new(LocalStorage))
+    }
+
+    // A syncer like new's, with its locations and state kept in storage rather than on the local
+    // filesystem.
+    pub fn with_storage(
+        config: &Config,
+        data_dir: &Path,
+        storage: Arc<dyn Storage>,
+    ) -> Result<Syncer, Box<dyn std::error::Error>> {
+        let state = State::load(&*storage, data_dir)?;
+        let runner = Arc::new(YtDlp::new(config.yt_dlp_path.as_ref().map(PathBuf::from)));
+        let options = SyncOptions::new(config, runner, storage, data_dir.to_path_buf(), state)?;
+        Ok(Syncer {
+            options,
+            observer: Box::new(Silent),
+        })
+    }
+
+    pub fn with_observer(self, observer: Box<dyn Observer>) -> Syncer {
+        Syncer { observer, ..self }
+    }
+
+    // Run yt-dlp through runner instead, such as to run it somewhere else or script its results.
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Syncer {
+        self.options.runner = runner;
+        self
+    }
+
+    // A token that stops the syncs of this syncer when cancelled, such as from another thread.
+    // The playlist being synced keeps its m3u file up to date.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.options.cancel.clone()
+    }
+
+    // Sync a single playlist.
+    pub fn sync_item(&self, item: &Item) -> Result<SyncReport, SyncError> {
+        let (receivers, listers) = spawn_listers(slice::from_ref(item), 1, &self.options);
+        let result = sync_playlist(item, &receivers[0], &self.options, &*self.observer);
+        for lister in listers {
+            let _ = lister.join();
+        }
+        result
+    }
+
+    // Sync every playlist of a config, listing a few at a time ahead of the one being synced.
+    pub fn sync_all(&self, items: &[Item]) -> Result<(), SyncError> {
+        sync_all(items, 4, &self.options, &*self.observer)
+    }
+}
+
+// Sync every playlist in turn.
+pub fn sync_all(
+    playlists: &[Item],
+    list_jobs: usize,
+    options: &SyncOptions,
+    observer: &dyn Observer,
+) -> Result<(), SyncError> {
+    if options.check_for_updates {
+        if let Some(notice) = update::check_for_newer_release(&*options.storage, &options.data_dir)
+        {
+            observer.on_notice(&notice);
+        }
+    }
+
+    // Another playlist's videos would look like they left the playlist, so pruning needs a location
+    // of its own.
+    for playlist in playlists {
+        let shared = playlists
+            .iter()
+            .any(|other| other.id != playlist.id && other.location == playlist.location);
+        if shared && (playlist.prune || options.prune) {
+            return Err(SyncError::InvalidItem {
+                playlist_id: playlist.id.clone(),
+                reason: tr!(
+                    "shared-location-pruned",
+                    location = playlist.location.as_str()
+                ),
+            });
+        }
+    }
+
+    // A run with a download budget starts with the playlist the last one ran out of budget in.
+    let mut playlists = playlists.to_vec();
+    let resume_playlist = options.state.lock().unwrap().resume_playlist.clone();
+    if let Some(resume_playlist) = resume_playlist.filter(|_| options.max_bytes.is_some()) {
+        if let Some(start) = playlists.iter().position(|p| p.id == resume_playlist) {
+            playlists.rotate_left(start);
+        }
+    }
+
+    // List the playlists in the background, so later playlists are listed while earlier ones are
+    // still downloading.
+    let (receivers, listers) = spawn_listers(&playlists, list_jobs, options);
+
+    let mut low_on_space = Vec::new();
+    let mut failed_items = Vec::new();
+    let mut failures = Vec::new();
+    let mut hook_failures = Vec::new();
+    let mut downloaded = 0;
+    let mut out_of_budget_in = None;
+    for (playlist, receiver) in playlists.iter().zip(&receivers) {
+        // A playlist that can't be synced doesn't stop the others, and is reported at the end.
+        let report = match sync_playlist(playlist, receiver, options, observer) {
+            Ok(report) => report,
+            Err(_) if options.cancel.is_cancelled() => break,
+            Err(e) => {
+                let report = SyncReport::failed(playlist, &e, options.dry_run);
+                observer.on_playlist_done(&report);
+                failed_items.push((playlist.id.clone(), e.to_string()));
+                report
+            }
+        };
+        if report.low_on_space {
+            low_on_space.push(report.location);
+        }
+        for failure in report.failures {
+            failures.push((playlist.id.clone(), failure));
+        }
+        hook_failures.extend(report.hook_failures);
+        downloaded += report.downloaded;
+        if options.over_budget() {
+            out_of_budget_in = Some(playlist.id.clone());
+        }
+        if !options.can_start_downloads() {
+            break;
+        }
+    }
+    if options.max_bytes.is_some() && !options.dry_run {
+        let mut state = options.state.lock().unwrap();
+        if state.resume_playlist != out_of_budget_in {
+            state.resume_playlist = out_of_budget_in.clone();
+            state.save(&*options.storage, &options.data_dir)?;
+        }
+    }
+    for lister in listers {
+        let _ = lister.join();
+    }
+    if options.cancel.is_cancelled() {
+        return Err(SyncError::Cancelled);
+    }
+    if let Some(command) = options
+        .hooks
+        .post_sync
+        .as_deref()
+        .filter(|_| !options.dry_run)
+    {
+        let env = [
+            ("YT_SYNC_DOWNLOADED", &*downloaded.to_string()),
+            ("YT_SYNC_FAILED", &*failures.len().to_string()),
+            ("YT_SYNC_FAILED_ITEMS", &*failed_items.len().to_string()),
+        ];
+        if let Err(e) = hooks::run(command, &env, &options.cancel) {
+            observer.on_error(&e);
+            hook_failures.push(e);
+        }
+    }
+    if out_of_budget_in.is_some() {
+        observer.on_notice(&tr!(
+            "out-of-budget",
+            size = format_size(options.downloaded_bytes.load(Ordering::Relaxed))
+        ));
+    } else if !options.can_start_downloads() {
+        observer.on_notice(&tr!("out-of-runtime"));
+    }
+    observer.on_run_done();
+    if !low_on_space.is_empty() {
+        return Err(SyncError::LowOnSpace(low_on_space));
+    }
+    if !failed_items.is_empty() || !failures.is_empty() || !hook_failures.is_empty() {
+        return Err(SyncError::Failed {
+            items: failed_items,
+            downloads: failures,
+            hooks: hook_failures,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::runner::FakeRunner;
+    use crate::state;
+    use crate::storage::MemoryStorage;
+
+    // A fake yt-dlp serving a single playlist of (id, title) videos. Downloads create the file
+    // yt-dlp would in storage, except for the videos in failing. Downloads that are post-processed
+    // separately leave a .webm file for post-processing to turn into the .opus one. Videos split
+    // into chapters have two, Intro and Outro.
+    fn fake_yt_dlp(
+        storage: &Arc<MemoryStorage>,
+        videos: &[(&str, &str)],
+        failing: &[&str],
+    ) -> FakeRunner {
+        let storage = Arc::clone(storage);
+        let videos: Vec<(String, String)> = videos
+            .iter()
+            .map(|(id, title)| (id.to_string(), title.to_string()))
+            .collect();
+        let failing: Vec<String> = failing.iter().map(|id| id.to_string()).collect();
+        FakeRunner::new(move |args| {
+            if args.iter().any(|arg| arg == "--flat-playlist") {
+                let lines = videos
+                    .iter()
+                    .map(|(id, title)| format!(r#"{{"id":"{}","title":"{}"}}"#, id, title))
+                    .collect();
+                return (lines, 0);
+            }
+
+            let location = &args[args.iter().position(|arg| arg == "-P").unwrap() + 1];
+            if args[0] == "--load-info-json" {
+                let info_path = Path::new(&args[1]);
+                let video_id = info_path.file_name().unwrap().to_str().unwrap();
+                let video_id = video_id.trim_end_matches(".info.json");
+                storage.read(info_path).unwrap();
+                let (_, title) = videos.iter().find(|(id, _)| id == video_id).unwrap();
+                let downloaded = Path::new(location).join(format!("{} [{}].webm", title, video_id));
+                storage.delete(&downloaded).unwrap();
+                storage
+                    .write(&downloaded.with_extension("opus"), title.as_bytes())
+                    .unwrap();
+                return (Vec::new(), 0);
+            }
+            let extension = match args.iter().any(|arg| arg == "--write-info-json") {
+                true => "webm",
+                false => "opus",
+            };
+            let requested: Vec<String> = match args.iter().position(|arg| arg == "--batch-file") {
+                Some(i) => fs::read_to_string(&args[i + 1])
+                    .unwrap()
+                    .lines()
+                    .map(String::from)
+                    .collect(),
+                None => vec![args.last().unwrap().clone()],
+            };
+
+            let mut printed = Vec::new();
+            for url in &requested {
+                let video_id = url.rsplit('=').next().unwrap();
+                if failing.iter().any(|id| id == video_id) {
+                    continue;
+                }
+                let (_, title) = videos.iter().find(|(id, _)| id == video_id).unwrap();
+                let file_name = format!("{} [{}].{}", title, video_id, extension);
+                storage
+                    .write(&Path::new(location).join(file_name), title.as_bytes())
+                    .unwrap();
+                if args.iter().any(|arg| arg == "--split-chapters") {
+                    for chapter in ["001 Intro", "002 Outro"] {
+                        let file_name = format!("{} [{}] - {}.opus", title, video_id, chapter);
+                        storage
+                            .write(&Path::new(location).join(file_name), b"")
+                            .unwrap();
+                    }
+                }
+                if extension == "webm" {
+                    let info_path = format!("/data/postprocess/{}.info.json", video_id);
+                    storage.write(Path::new(&info_path), b"{}").unwrap();
+                }
+                printed.push(video_id.to_string());
+            }
+            let code = if printed.len() < requested.len() {
+                1
+            } else {
+                0
+            };
+            (printed, code)
+        })
+    }
+
+    // Sync one playlist into /music/mix, saving an m3u file next to it.
+    fn sync(
+        runner: &Arc<FakeRunner>,
+        storage: &Arc<MemoryStorage>,
+        batch: bool,
+    ) -> Result<SyncReport, SyncError> {
+        sync_with(runner, storage, |options, _| options.batch = batch)
+    }
+
+    fn options(runner: &Arc<FakeRunner>, storage: &Arc<MemoryStorage>) -> SyncOptions {
+        SyncOptions {
+            fallback: None,
+            batch: false,
+            verbose: false,
+            cancel: CancelToken::default(),
+            runner: runner.clone(),
+            storage: storage.clone(),
+            data_dir: PathBuf::from("/data"),
+            check_for_updates: false,
+            blacklist: HashSet::new(),
+            min_free_space: None,
+            max_bytes: None,
+            downloaded_bytes: AtomicU64::new(0),
+            deadline: None,
+            throttle: Arc::default(),
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            state: Mutex::new(State::load(&**storage, Path::new("/data")).unwrap()),
+            preserve_unknown_lines: false,
+            playlist_separator: m3u::Separator::Slash,
+            playlist_encoding: m3u::Encoding::default(),
+            playlist_format: m3u::PlaylistFormat::default(),
+            playlist_relative: false,
+            timings: false,
+            post_jobs: 0,
+            jobs: 1,
+            prune: false,
+            trash: None,
+            dry_run: false,
+            hooks: Hooks::default(),
+            cookies: Cookies::default(),
+            manifests: Mutex::default(),
+        }
+    }
+
+    fn item() -> Item {
+        Item {
+            id: "PL".to_string(),
+            location: "/music/mix".to_string(),
+            save_playlist: true,
+            ..Default::default()
+        }
+    }
+
+    fn sync_with(
+        runner: &Arc<FakeRunner>,
+        storage: &Arc<MemoryStorage>,
+        configure: impl FnOnce(&mut SyncOptions, &mut Item),
+    ) -> Result<SyncReport, SyncError> {
+        let (mut options, mut playlist) = (options(runner, storage), item());
+        configure(&mut options, &mut playlist);
+        let (receivers, listers) = spawn_listers(slice::from_ref(&playlist), 1, &options);
+        let result = sync_playlist(&playlist, &receivers[0], &options, &Silent);
+        for lister in listers {
+            lister.join().unwrap();
+        }
+        result
+    }
+
+    fn read_m3u(storage: &MemoryStorage) -> Vec<String> {
+        let m3u = storage.read(Path::new("/music/mix.m3u")).unwrap();
+        String::from_utf8(m3u)
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit('/').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn syncs_through_the_storage_and_runner_given() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+        let syncer = Syncer::with_storage(&Config::default(), Path::new("/data"), storage.clone())
+            .unwrap()
+            .with_runner(runner.clone());
+
+        let report = syncer.sync_item(&item()).unwrap();
+        assert_eq!(report.downloaded, 1);
+        assert_eq!(runner.calls.lock().unwrap().len(), 2);
+        assert_eq!(read_m3u(&storage), ["One [a].opus"]);
+    }
+
+    #[test]
+    fn downloads_only_missing_videos() {
+        let storage = Arc::new(MemoryStorage::default());
+        storage
+            .write(Path::new("/music/mix/Two [b].opus"), b"")
+            .unwrap();
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        sync(&runner, &storage, false).unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        let downloaded: Vec<&str> = calls[1..]
+            .iter()
+            .map(|args| args.last().unwrap().as_str())
+            .collect();
+        assert_eq!(
+            downloaded,
+            [
+                "https://www.youtube.com/watch?v=a",
+                "https://www.youtube.com/watch?v=c"
+            ]
+        );
+        assert_eq!(
+            read_m3u(&storage),
+            ["One [a].opus", "Two [b].opus", "Three [c].opus"]
+        );
+    }
+
+    #[test]
+    fn records_the_files_yt_dlp_saved() {
+        let storage = Arc::new(MemoryStorage::default());
+        // The fake yt-dlp doesn't sanitize titles, so it saves files under other names than the
+        // ones predicted.
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "Foo: Bar")], &[]));
+
+        let report = sync(&runner, &storage, false).unwrap();
+        assert_eq!(report.downloaded, 1);
+        assert_eq!(read_m3u(&storage), ["Foo: Bar [a].opus"]);
+
+        let report = sync(&runner, &storage, false).unwrap();
+        assert_eq!(report.downloaded, 0);
+        assert_eq!(runner.calls.lock().unwrap().len(), 3);
+        assert_eq!(read_m3u(&storage), ["Foo: Bar [a].opus"]);
+    }
+
+    #[test]
+    fn writes_m3u8_with_titles_and_relative_paths() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One"), ("b", "Two")], &[]));
+
+        sync_with(&runner, &storage, |options, _| {
+            options.playlist_format = m3u::PlaylistFormat::M3u8;
+            options.playlist_relative = true;
+        })
+        .unwrap();
+
+        let m3u8 = storage.read(Path::new("/music/mix.m3u8")).unwrap();
+        assert_eq!(
+            String::from_utf8(m3u8).unwrap(),
+            "#EXTM3U\n#EXTINF:-1,One\nmix/One [a].opus\n#EXTINF:-1,Two\nmix/Two [b].opus\n"
+        );
+        assert!(storage.read(Path::new("/music/mix.m3u")).is_err());
+    }
+
+    #[test]
+    fn failed_downloads_are_left_out_of_m3u() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &["b"],
+        ));
+
+        sync(&runner, &storage, false).unwrap();
+
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Three [c].opus"]);
+        let logs = storage.scan(Path::new("/data/failures")).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].starts_with("b-"));
+    }
+
+    #[test]
+    fn reports_failed_downloads_at_the_end() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &["b"],
+        ));
+
+        let error = sync_all(&[item()], 1, &options(&runner, &storage), &Silent).unwrap_err();
+
+        assert_eq!(error.exit_code(), 3);
+        match error {
+            SyncError::Failed {
+                items,
+                downloads,
+                hooks,
+            } => {
+                assert!(items.is_empty());
+                assert_eq!(downloads.len(), 1);
+                assert_eq!(downloads[0].0, "PL");
+                assert_eq!(downloads[0].1.video_id, "b");
+                assert!(hooks.is_empty());
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Three [c].opus"]);
+    }
+
+    #[test]
+    fn loads_a_shared_location_once() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+        let mut other = item();
+        other.id = "PL2".to_string();
+
+        sync_all(&[item(), other], 1, &options(&runner, &storage), &Silent).unwrap();
+
+        let reads = storage.reads.lock().unwrap();
+        assert_eq!(reads[Path::new("/music/mix/.yt-sync.json")], 1);
+    }
+
+    #[test]
+    fn carries_on_after_playlists_that_cannot_be_synced() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+        let mut invalid = item();
+        invalid.id = "PL0".to_string();
+        invalid.location = "/music/other".to_string();
+        invalid.min_free_space = Some("lots".to_string());
+
+        let playlists = [invalid, item()];
+        let error = sync_all(&playlists, 1, &options(&runner, &storage), &Silent).unwrap_err();
+
+        assert_eq!(error.exit_code(), 1);
+        match error {
+            SyncError::Failed {
+                items, downloads, ..
+            } => {
+                assert_eq!(items.len(), 1);
+                assert_eq!(items[0].0, "PL0");
+                assert!(items[0].1.contains("min_free_space"), "{}", items[0].1);
+                assert!(downloads.is_empty());
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        assert_eq!(read_m3u(&storage), ["One [a].opus"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_hooks_and_reports_their_failures() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One"), ("b", "Two")], &[]));
+        let mut options = options(&runner, &storage);
+        options.hooks.post_sync = Some("test \"$YT_SYNC_DOWNLOADED\" = 2".to_string());
+        let mut playlist = item();
+        playlist.post_command =
+            Some("test \"$YT_SYNC_FILE\" != \"/music/mix/Two [b].opus\"".to_string());
+
+        let error = sync_all(&[playlist], 1, &options, &Silent).unwrap_err();
+
+        assert_eq!(error.exit_code(), 5);
+        match error {
+            SyncError::Failed {
+                downloads, hooks, ..
+            } => {
+                assert!(downloads.is_empty());
+                assert_eq!(hooks.len(), 1);
+                assert!(hooks[0].ends_with("after downloading b"), "{}", hooks[0]);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Two [b].opus"]);
+    }
+
+    #[test]
+    fn signs_in_with_cookies() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+
+        sync_with(&runner, &storage, |options, playlist| {
+            options.cookies.file = Some("/home/user/cookies.txt".to_string());
+            playlist.cookies_from_browser = Some("firefox".to_string());
+        })
+        .unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        for args in calls.iter() {
+            assert!(args
+                .windows(2)
+                .any(|pair| pair == ["--cookies-from-browser", "firefox"]));
+            assert!(!args.contains(&"--cookies".to_string()));
+        }
+    }
+
+    #[test]
+    fn skips_videos_that_stay_unavailable() {
+        let storage = Arc::new(MemoryStorage::default());
+        let mut state = State::default();
+        state
+            .unavailable
+            .insert("b".to_string(), state::UNAVAILABLE_THRESHOLD);
+        state.unavailable.insert("c".to_string(), 1);
+        state.save(&*storage, Path::new("/data")).unwrap();
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        sync(&runner, &storage, false).unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 3);
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Three [c].opus"]);
+        let state = State::load(&*storage, Path::new("/data")).unwrap();
+        assert_eq!(state.unavailable.keys().collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn leaves_out_blacklisted_videos() {
+        let storage = Arc::new(MemoryStorage::default());
+        storage
+            .write(Path::new("/music/mix/One [a].opus"), b"")
+            .unwrap();
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        sync_with(&runner, &storage, |options, playlist| {
+            options.blacklist.insert("a".to_string());
+            playlist.blacklist.push("c".to_string());
+        })
+        .unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 2);
+        assert_eq!(read_m3u(&storage), ["Two [b].opus"]);
+    }
+
+    #[test]
+    fn skips_videos_in_the_download_archive() {
+        let storage = Arc::new(MemoryStorage::default());
+        storage
+            .write(
+                Path::new("/music/archive.txt"),
+                b"youtube b
+",
+            )
+            .unwrap();
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        sync_with(&runner, &storage, |_, playlist| {
+            playlist.download_archive = Some("/music/archive.txt".to_string());
+        })
+        .unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert!(calls[1..]
+            .iter()
+            .all(|args| args.contains(&"--download-archive".to_string())));
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Three [c].opus"]);
+    }
+
+    #[test]
+    fn keeps_pinned_videos_that_left_the_playlist() {
+        let storage = Arc::new(MemoryStorage::default());
+        for path in ["/music/mix/Gone [z].opus", "/music/mix/Also gone [y].opus"] {
+            storage.write(Path::new(path), b"").unwrap();
+        }
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+
+        sync_with(&runner, &storage, |_, playlist| {
+            playlist.pinned = vec!["z".to_string(), "x".to_string()];
+        })
+        .unwrap();
+
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Gone [z].opus"]);
+    }
+
+    #[test]
+    fn renames_videos_that_were_retitled() {
+        let storage = Arc::new(MemoryStorage::default());
+        for name in [
+            "Old title [a].opus",
+            "Old title [a].opus.yt-sync.json",
+            "Kept [b].opus",
+        ] {
+            storage
+                .write(&Path::new("/music/mix").join(name), b"")
+                .unwrap();
+        }
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "New title"), ("b", "Kept")],
+            &[],
+        ));
+
+        sync(&runner, &storage, false).unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+        assert_eq!(read_m3u(&storage), ["New title [a].opus", "Kept [b].opus"]);
+        assert!(storage
+            .read(Path::new("/music/mix/New title [a].opus.yt-sync.json"))
+            .is_ok());
+        assert!(storage
+            .size(Path::new("/music/mix/Old title [a].opus"))
+            .is_err());
+        let state = State::default();
+        let manifest = Manifest::load(&*storage, "/music/mix", &state).unwrap();
+        assert_eq!(manifest.videos["a"].title, "New title");
+    }
+
+    #[test]
+    fn prunes_videos_that_left_the_playlist() {
+        let storage = Arc::new(MemoryStorage::default());
+        for path in [
+            "/music/mix/Gone [z].opus",
+            "/music/mix/Gone [z].opus.yt-sync.json",
+            "/music/mix/Pinned [p].opus",
+        ] {
+            storage.write(Path::new(path), b"").unwrap();
+        }
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+        let prune = |options: &mut SyncOptions, playlist: &mut Item, dry_run| {
+            options.dry_run = dry_run;
+            playlist.prune = true;
+            playlist.trash = Some("/trash".to_string());
+            playlist.pinned = vec!["p".to_string()];
+        };
+
+        let report = sync_with(&runner, &storage, |o, p| prune(o, p, true)).unwrap();
+        assert_eq!(report.pruned, ["Gone [z].opus"]);
+        assert_eq!(report.deferred, 1);
+        assert!(storage.read(Path::new("/music/mix/Gone [z].opus")).is_ok());
+
+        let report = sync_with(&runner, &storage, |o, p| prune(o, p, false)).unwrap();
+        assert_eq!(report.pruned, ["Gone [z].opus"]);
+        assert!(storage.read(Path::new("/music/mix/Gone [z].opus")).is_err());
+        assert!(storage.read(Path::new("/trash/Gone [z].opus")).is_ok());
+        assert!(storage
+            .read(Path::new("/trash/Gone [z].opus.yt-sync.json"))
+            .is_ok());
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Pinned [p].opus"]);
+    }
+
+    #[test]
+    fn dry_runs_change_nothing() {
+        let storage = Arc::new(MemoryStorage::default());
+        let configure = |options: &mut SyncOptions, playlist: &mut Item, dry_run| {
+            options.dry_run = dry_run;
+            playlist.prune = true;
+            playlist.locations = vec!["/usb/mix".to_string()];
+            playlist.views = Some("/music/views".to_string());
+        };
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One")], &[]));
+        sync_with(&runner, &storage, |o, p| configure(o, p, false)).unwrap();
+        storage
+            .write(Path::new("/music/new/Two [b].opus"), b"two")
+            .unwrap();
+        let before = storage.snapshot();
+
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("b", "Two")], &[]));
+        sync_with(&runner, &storage, |o, p| configure(o, p, true)).unwrap();
+        sync_with(&runner, &storage, |o, p| {
+            configure(o, p, true);
+            p.location = "/music/new".to_string();
+        })
+        .unwrap();
+        assert!(storage.snapshot() == before);
+    }
+
+    #[test]
+    fn keeps_only_the_newest_videos() {
+        let storage = Arc::new(MemoryStorage::default());
+        for path in ["/music/mix/One [a].opus", "/music/mix/Two [b].opus"] {
+            storage.write(Path::new(path), b"").unwrap();
+        }
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three"), ("d", "Four")],
+            &[],
+        ));
+
+        let keep_last = |options: &mut SyncOptions, playlist: &mut Item, dry_run| {
+            options.dry_run = dry_run;
+            playlist.keep_last = Some(2);
+            playlist.pinned = vec!["b".to_string()];
+            playlist.locations = vec!["/usb/mix".to_string()];
+        };
+
+        let report = sync_with(&runner, &storage, |o, p| keep_last(o, p, true)).unwrap();
+        assert_eq!(report.removed, 1);
+        assert!(storage.read(Path::new("/music/mix/One [a].opus")).is_ok());
+        assert!(storage.read(Path::new("/usb/mix/Two [b].opus")).is_err());
+
+        sync_with(&runner, &storage, |o, p| keep_last(o, p, false)).unwrap();
+        assert!(storage.read(Path::new("/usb/mix/Two [b].opus")).is_ok());
+        assert_eq!(runner.calls.lock().unwrap().len(), 4);
+        assert_eq!(
+            read_m3u(&storage),
+            ["Two [b].opus", "Three [c].opus", "Four [d].opus"]
+        );
+        assert!(storage.read(Path::new("/music/mix/One [a].opus")).is_err());
+    }
+
+    #[test]
+    fn sets_modification_times_to_upload_dates() {
+        let storage = Arc::new(MemoryStorage::default());
+        let downloads = Arc::clone(&storage);
+        let runner = Arc::new(FakeRunner::new(move |args| {
+            if args.iter().any(|arg| arg == "--flat-playlist") {
+                let listed = r#"{"id":"a","title":"One","upload_date":"19700102"}"#;
+                return (vec![listed.to_string()], 0);
+            }
+            let path = Path::new("/music/mix/One [a].opus");
+            downloads.write(path, b"one").unwrap();
+            (Vec::new(), 0)
+        }));
+
+        sync_with(&runner, &storage, |_, playlist| {
+            playlist.mtime = Mtime::Uploaded
+        })
+        .unwrap();
+
+        assert_eq!(
+            storage
+                .modified(Path::new("/music/mix/One [a].opus"))
+                .unwrap(),
+            UNIX_EPOCH + Duration::from_secs(86400)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_other_formats() {
+        let storage = Arc::new(MemoryStorage::default());
+        let downloads = Arc::clone(&storage);
+        let runner = Arc::new(FakeRunner::new(move |args| {
+            if args.iter().any(|arg| arg == "--flat-playlist") {
+                return (vec![r#"{"id":"a","title":"One"}"#.to_string()], 0);
+            }
+            let format = args
+                .iter()
+                .position(|arg| arg == "-f")
+                .map(|i| &args[i + 1]);
+            if format.map(String::as_str) != Some("worst") {
+                let error = "ERROR: [youtube] a: Requested format is not available";
+                return (vec![error.to_string()], 1);
+            }
+            downloads
+                .write(Path::new("/music/mix/One [a].opus"), b"one")
+                .unwrap();
+            (Vec::new(), 0)
+        }));
+
+        let report = sync_with(&runner, &storage, |_, playlist| {
+            playlist.format_fallbacks = Some(vec!["best".to_string(), "worst".to_string()])
+        })
+        .unwrap();
+
+        assert_eq!(report.failures.len(), 0);
+        assert_eq!(runner.calls.lock().unwrap().len(), 4);
+        assert_eq!(read_m3u(&storage), ["One [a].opus"]);
+    }
+
+    #[test]
+    fn retries_failed_downloads() {
+        let storage = Arc::new(MemoryStorage::default());
+        let downloads = Arc::clone(&storage);
+        let attempts = AtomicU64::new(0);
+        let runner = Arc::new(FakeRunner::new(move |args| {
+            if args.iter().any(|arg| arg == "--flat-playlist") {
+                return (vec![r#"{"id":"a","title":"One"}"#.to_string()], 0);
+            }
+            if attempts.fetch_add(1, Ordering::Relaxed) < 2 {
+                return (vec!["ERROR: [youtube] a: Something odd".to_string()], 1);
+            }
+            downloads
+                .write(Path::new("/music/mix/One [a].opus"), b"one")
+                .unwrap();
+            (Vec::new(), 0)
+        }));
+
+        let report = sync_with(&runner, &storage, |options, _| options.retries = 1).unwrap();
+        assert_eq!(report.failures.len(), 1);
+        let report = sync_with(&runner, &storage, |options, _| options.retries = 1).unwrap();
+        assert_eq!(report.failures.len(), 0);
+        assert_eq!(runner.calls.lock().unwrap().len(), 5);
+        assert_eq!(read_m3u(&storage), ["One [a].opus"]);
+    }
+
+    #[test]
+    fn tracks_downloads_until_they_finish() {
+        let storage = Arc::new(MemoryStorage::default());
+        let mut state = State::default();
+        let interrupted = InProgress {
+            location: "/music/mix".to_string(),
+            started: 0,
+        };
+        state.in_progress.insert("a".to_string(), interrupted);
+        state.save(&*storage, Path::new("/data")).unwrap();
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One"), ("b", "Two")], &["b"]));
+
+        sync(&runner, &storage, false).unwrap();
+
+        assert!(runner.calls.lock().unwrap()[1].contains(&"--continue".to_string()));
+        let state = State::load(&*storage, Path::new("/data")).unwrap();
+        assert!(state.in_progress.is_empty());
+    }
+
+    #[test]
+    fn batch_mode_uses_one_process() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &["c"],
+        ));
+
+        sync(&runner, &storage, true).unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 2);
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Two [b].opus"]);
+    }
+
+    #[test]
+    fn splits_chapters_and_tags_track_numbers() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(&storage, &[("a", "One"), ("b", "Two")], &[]));
+
+        sync_with(&runner, &storage, |options, playlist| {
+            options.batch = true;
+            playlist.split_chapters = true;
+            playlist.track_numbers = true;
+        })
+        .unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert!(calls[2].contains(&"Metadata:-metadata track=2 -metadata album='mix'".to_string()));
+        assert_eq!(
+            read_m3u(&storage),
+            [
+                "One [a] - 001 Intro.opus",
+                "One [a] - 002 Outro.opus",
+                "Two [b] - 001 Intro.opus",
+                "Two [b] - 002 Outro.opus"
+            ]
+        );
+        let state = State::default();
+        let manifest = Manifest::load(&*storage, "/music/mix", &state).unwrap();
+        assert_eq!(manifest.videos["b"].chapters[1].title, "Outro");
+    }
+
+    #[test]
+    fn post_processes_separately() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &["b"],
+        ));
+
+        sync_with(&runner, &storage, |options, _| options.post_jobs = 2).unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        let post_processed = calls
+            .iter()
+            .filter(|args| args[0] == "--load-info-json")
+            .count();
+        assert_eq!(post_processed, 2);
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Three [c].opus"]);
+        assert_eq!(
+            storage.scan(Path::new("/music/mix")).unwrap(),
+            [manifest::FILE_NAME, "One [a].opus", "Three [c].opus"]
+        );
+        assert!(storage
+            .scan(Path::new("/data/postprocess"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn downloads_in_parallel() {
+        let storage = Arc::new(MemoryStorage::default());
+        let videos = [("a", "One"), ("b", "Two"), ("c", "Three"), ("d", "Four")];
+        let runner = Arc::new(fake_yt_dlp(&storage, &videos, &["c"]));
+
+        sync_with(&runner, &storage, |options, _| options.jobs = 3).unwrap();
+
+        assert_eq!(
+            read_m3u(&storage),
+            ["One [a].opus", "Two [b].opus", "Four [d].opus"]
+        );
+        let state = State::load(&*storage, Path::new("/data")).unwrap();
+        assert!(state.in_progress.is_empty());
+    }
+
+    #[test]
+    fn downloads_newest_videos_first() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        sync_with(&runner, &storage, |_, playlist| {
+            playlist.order = Order::NewestFirst
+        })
+        .unwrap();
+
+        let calls = runner.calls.lock().unwrap();
+        let downloaded: Vec<&str> = calls[1..]
+            .iter()
+            .map(|args| args.last().unwrap().rsplit('=').next().unwrap())
+            .collect();
+        assert_eq!(downloaded, ["c", "b", "a"]);
+        assert_eq!(
+            read_m3u(&storage),
+            ["One [a].opus", "Two [b].opus", "Three [c].opus"]
+        );
+    }
+
+    #[test]
+    fn stops_downloading_at_the_budget() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        let report = sync_with(&runner, &storage, |options, _| {
+            options.max_bytes = Some(5);
+        })
+        .unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 3);
+        assert_eq!(report.deferred, 1);
+        assert_eq!(read_m3u(&storage), ["One [a].opus", "Two [b].opus"]);
+    }
+
+    #[test]
+    fn stops_downloading_when_low_on_space() {
+        let storage = Arc::new(MemoryStorage::default());
+        *storage.available_space.lock().unwrap() = Some(1_000);
+        let runner = Arc::new(fake_yt_dlp(
+            &storage,
+            &[("a", "One"), ("b", "Two"), ("c", "Three")],
+            &[],
+        ));
+
+        let report = sync_with(&runner, &storage, |options, _| {
+            options.min_free_space = Some(1_000_000);
+        })
+        .unwrap();
+
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+        assert!(report.low_on_space);
+        assert_eq!(report.deferred, 3);
+    }
+
+    #[test]
+    fn listing_failures_stop_the_sync() {
+        let storage = Arc::new(MemoryStorage::default());
+        let runner = Arc::new(FakeRunner::new(|_| (Vec::new(), 1)));
+
+        assert!(matches!(
+            sync(&runner, &storage, false),
+            Err(SyncError::Listing { .. })
+        ));
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+    }
+}
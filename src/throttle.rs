@@ -0,0 +1,131 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+
+// Messages in yt-dlp's output that mean YouTube is throttling or blocking us.
+const THROTTLE_SIGNS: [&str; 5] = [
+    "HTTP Error 429",
+    "Too Many Requests",
+    // YouTube's "Sign in to confirm you’re not a bot".
+    "not a bot",
+    "rate-limited",
+    "try again later",
+];
+
+const MIN_DELAY: Duration = Duration::from_secs(5);
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+const BASE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+// Spaces out requests to YouTube, backing off further each time it throttles us and easing off
+// again as requests go through.
+#[derive(Default)]
+pub struct Throttle {
+    state: Mutex<ThrottleState>,
+    // The least time between requests, however well they go.
+    min_delay: Duration,
+}
+
+#[derive(Default)]
+struct ThrottleState {
+    // How long to wait between requests.
+    delay: Duration,
+    // When to resume after a cooldown.
+    resume_at: Option<Instant>,
+    last_request: Option<Instant>,
+    // How many times in a row we have been throttled without things improving.
+    strikes: u32,
+}
+
+impl Throttle {
+    // A throttle that always leaves at least min_delay between requests.
+    pub fn with_min_delay(min_delay: Duration) -> Throttle {
+        Throttle {
+            min_delay,
+            ..Throttle::default()
+        }
+    }
+
+    // Whether yt-dlp's output shows that YouTube is throttling it.
+    pub fn is_throttled(output: &str) -> bool {
+        let output = output.to_lowercase();
+        THROTTLE_SIGNS
+            .iter()
+            .any(|sign| output.contains(&sign.to_lowercase()))
+    }
+
+    // Back off after being throttled, returning how long requests are paused for and the new delay
+    // between them.
+    pub fn throttled(&self) -> (Duration, Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.strikes += 1;
+        state.delay = (state.delay * 2).clamp(MIN_DELAY, MAX_DELAY);
+        let cooldown = BASE_COOLDOWN
+            .saturating_mul(1 << (state.strikes - 1).min(8))
+            .min(MAX_COOLDOWN);
+        state.resume_at = Some(Instant::now() + cooldown);
+        (cooldown, state.delay)
+    }
+
+    // Ease off the delay after a request went through.
+    pub fn succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.strikes = state.strikes.saturating_sub(1);
+        state.delay /= 2;
+        if state.delay < Duration::from_secs(1) {
+            state.delay = Duration::ZERO;
+        }
+    }
+
+    // Block until the next request may be made, or the sync is cancelled.
+    pub fn wait(&self, cancel: &CancelToken) {
+        loop {
+            let ready_at = {
+                let mut state = self.state.lock().unwrap();
+                let delay = state.delay.max(self.min_delay);
+                let after_delay = state.last_request.map(|last| last + delay);
+                let ready_at = after_delay.max(state.resume_at);
+                if ready_at.is_none_or(|ready_at| Instant::now() >= ready_at) {
+                    state.last_request = Some(Instant::now());
+                    return;
+                }
+                ready_at.unwrap()
+            };
+            if cancel.is_cancelled() {
+                return;
+            }
+            let remaining = ready_at.saturating_duration_since(Instant::now());
+            thread::sleep(remaining.min(Duration::from_millis(200)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_while_throttled() {
+        assert!(Throttle::is_throttled(
+            "ERROR: [youtube] abc: Sign in to confirm you’re not a bot"
+        ));
+        assert!(Throttle::is_throttled(
+            "ERROR: HTTP Error 429: Too Many Requests"
+        ));
+        assert!(!Throttle::is_throttled(
+            "ERROR: [youtube] abc: Video unavailable"
+        ));
+
+        let throttle = Throttle::default();
+        assert_eq!(throttle.throttled(), (BASE_COOLDOWN, MIN_DELAY));
+        assert_eq!(throttle.throttled(), (BASE_COOLDOWN * 2, MIN_DELAY * 2));
+        throttle.succeeded();
+        throttle.succeeded();
+        assert_eq!(
+            throttle.state.lock().unwrap().delay,
+            Duration::from_millis(2500)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::i18n::tr;
+use crate::storage::Storage;
+
+// Where releases are published.
+const REPO: &str = "ethan-hawksley/yt-sync";
+
+// The release asset listing the SHA-256 checksum of every other asset, as sha256sum prints them.
+const CHECKSUMS: &str = "SHA256SUMS";
+
+// Binaries are far smaller than this, so anything bigger is not what we asked for.
+const MAX_BINARY_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    // The version of the release, without the v its tag starts with.
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Result<&Asset, String> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| {
+                tr!(
+                    "release-missing-asset",
+                    release = self.tag_name.as_str(),
+                    file = name
+                )
+            })
+    }
+}
+
+fn agent(timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(timeout)
+        .user_agent(concat!("yt-sync/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+// Look up the latest release on GitHub.
+fn latest_release(agent: &ureq::Agent) -> Result<Release, Box<dyn std::error::Error>> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", REPO);
+    let response = agent
+        .get(&url)
+        .call()
+        .map_err(|e| tr!("release-check-failed", error = e.to_string()))?;
+    Ok(response.into_json()?)
+}
+
+// Whether one version is newer than another, comparing their dot-separated numbers in turn.
+pub fn is_newer(version: &str, than: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parse(version) > parse(than)
+}
+
+// The name of the release asset built for this platform, such as yt-sync-linux-x86_64.
+fn asset_name() -> String {
+    format!(
+        "yt-sync-{}-{}{}",
+        env::consts::OS,
+        env::consts::ARCH,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+// Setting this turns the check for newer releases off, whatever the config says.
+const NO_UPDATE_CHECK_ENV: &str = "YT_SYNC_NO_UPDATE_CHECK";
+
+// How often GitHub is asked about newer releases.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// The last check for a newer release, kept in <data_dir>/update-check.json.
+#[derive(Deserialize, Serialize, Debug)]
+struct LastCheck {
+    // In seconds since the Unix epoch.
+    checked: u64,
+    // The latest version found, if the check worked.
+    latest: Option<String>,
+}
+
+// A message saying there is a newer release than this one, if there is. GitHub is asked at most
+// once a day, and the answer is remembered in between. Failing to check is not worth interrupting
+// a sync over, so it is ignored, and not retried until the next day either.
+pub fn check_for_newer_release(storage: &dyn Storage, data_dir: &Path) -> Option<String> {
+    if env::var_os(NO_UPDATE_CHECK_ENV).is_some() {
+        return None;
+    }
+    let path = data_dir.join("update-check.json");
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let last_check = storage
+        .read(&path)
+        .ok()
+        .and_then(|content| serde_json::from_slice::<LastCheck>(&content).ok())
+        .filter(|last_check| now.saturating_sub(last_check.checked) < CHECK_INTERVAL.as_secs());
+    let latest = match last_check {
+        Some(last_check) => last_check.latest,
+        None => {
+            let latest = latest_release(&agent(Duration::from_secs(5)))
+                .ok()
+                .map(|release| release.version().to_string());
+            let last_check = LastCheck {
+                checked: now,
+                latest: latest.clone(),
+            };
+            if let Ok(content) = serde_json::to_vec(&last_check) {
+                let _ = storage
+                    .create_dir(data_dir)
+                    .and_then(|()| storage.write(&path, &content));
+            }
+            latest
+        }
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = latest.filter(|latest| is_newer(latest, current))?;
+    Some(tr!("update-available", version = latest, current = current))
+}
+
+// Replace the running binary with the one from the latest release if that is newer, after
+// checking it against the release's checksums. With check_only, just say whether there is one.
+pub fn self_update(check_only: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let current = env!("CARGO_PKG_VERSION");
+    let agent = agent(Duration::from_secs(30));
+    let release = latest_release(&agent)?;
+    if !is_newer(release.version(), current) {
+        println!("{}", tr!("latest-version", version = current));
+        return Ok(());
+    }
+    if check_only {
+        println!(
+            "{}",
+            tr!(
+                "update-available",
+                version = release.version(),
+                current = current
+            )
+        );
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let binary_url = &release.asset(&name)?.browser_download_url;
+    let checksums_url = &release.asset(CHECKSUMS)?.browser_download_url;
+    let checksums = agent.get(checksums_url).call()?.into_string()?;
+    let expected = checksums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim().trim_start_matches('*') == name)
+        .map(|(checksum, _)| checksum.to_lowercase())
+        .ok_or_else(|| {
+            tr!(
+                "checksum-missing",
+                checksums = CHECKSUMS,
+                file = name.as_str()
+            )
+        })?;
+
+    println!("{}", tr!("downloading-update", version = release.version()));
+    let mut binary = Vec::new();
+    agent
+        .get(binary_url)
+        .call()?
+        .into_reader()
+        .take(MAX_BINARY_SIZE)
+        .read_to_end(&mut binary)?;
+    let checksum: String = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if checksum != expected {
+        return Err(tr!("checksum-mismatch", file = name).into());
+    }
+
+    let exe = env::current_exe()?;
+    replace_binary(&exe, &binary).map_err(|e| {
+        let path = exe.display().to_string();
+        tr!("replace-failed", path = path, error = e.to_string())
+    })?;
+    println!(
+        "{}",
+        tr!("updated", current = current, version = release.version())
+    );
+    Ok(())
+}
+
+// Write the new binary next to the running one and move it into place. Windows won't replace a
+// running binary, but will rename it, so there the old one is moved aside first, to be removed by
+// the next update.
+fn replace_binary(exe: &Path, binary: &[u8]) -> std::io::Result<()> {
+    let new = exe.with_extension("new");
+    fs::write(&new, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+    fs::rename(&new, exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert!(is_newer("2.10.0", "2.4.2"));
+        assert!(is_newer("3.0.0", "2.4.2"));
+        assert!(!is_newer("2.4.2", "2.4.2"));
+        assert!(!is_newer("2.4.1", "2.4.2"));
+    }
+}
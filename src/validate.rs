@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use toml_edit::ImDocument;
+
+use crate::config::{parse_size, Config, Item};
+use crate::cookies;
+use crate::i18n::tr;
+use crate::m3u::PlaylistFormat;
+use crate::media::MediaFormat;
+use crate::organize;
+use crate::permissions::Permissions;
+use crate::playlist::get_playlist_path;
+
+// The settings of an item that parse but couldn't be synced, each with the field it is in.
+fn item_problems(item: &Item) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    if item.id.trim().is_empty() {
+        problems.push(("id", tr!("id-empty")));
+    }
+    // Without a location, the first of locations is where the playlist is downloaded to.
+    let location = match item.location.is_empty() {
+        true => item.locations.first(),
+        false => Some(&item.location),
+    };
+    match location {
+        None => problems.push(("location", tr!("no-location"))),
+        Some(location) if item.save_playlist => {
+            if let Err(e) = get_playlist_path(location, PlaylistFormat::M3u) {
+                problems.push(("location", e));
+            }
+        }
+        Some(_) => {}
+    }
+    if let Err(e) = MediaFormat::of(item) {
+        let field = [
+            "audio_format",
+            "video_quality",
+            "container",
+            "split_chapters",
+        ]
+        .into_iter()
+        .find(|field| e.starts_with(field))
+        .unwrap_or("format");
+        problems.push((field, e));
+    }
+    if let Some(Err(e)) = item.organize.as_deref().map(organize::check) {
+        problems.push(("organize", e));
+    }
+    if let Some(Err(e)) = item.min_free_space.as_deref().map(parse_size) {
+        let problem = tr!("setting-invalid", setting = "min_free_space", error = e);
+        problems.push(("min_free_space", problem));
+    }
+    if let Err(e) = Permissions::parse(item.chmod.as_deref(), None) {
+        problems.push(("chmod", e));
+    }
+    if let Err(e) = Permissions::parse(None, item.chown.as_deref()) {
+        problems.push(("chown", e));
+    }
+    if let Some(Err(e)) = item
+        .cookies_from_browser
+        .as_deref()
+        .map(cookies::check_browser)
+    {
+        problems.push(("cookies_from_browser", e));
+    }
+    problems
+}
+
+// Check a config read from content for settings that parse but wouldn't work, such as a
+// container yt-dlp can't merge into, which would otherwise only come up once a sync reaches
+// them. Each problem names the setting, and the line it is on.
+pub fn check(config: &Config, content: &str) -> Vec<String> {
+    let document = ImDocument::parse(content).ok();
+    let at = |span: Option<Range<usize>>, problem: String| match span {
+        Some(span) => tr!(
+            "problem-on-line",
+            line = content[..span.start].matches('\n').count() + 1,
+            problem = problem
+        ),
+        None => problem,
+    };
+
+    let mut problems = Vec::new();
+    for (field, value) in [
+        ("min_free_space", &config.min_free_space),
+        ("max_bytes_per_run", &config.max_bytes_per_run),
+    ] {
+        if let Some(Err(e)) = value.as_deref().map(parse_size) {
+            let span = document
+                .as_ref()
+                .and_then(|document| document.get(field)?.span());
+            problems.push(at(span, tr!("setting-invalid", setting = field, error = e)));
+        }
+    }
+    if let Some(Err(e)) = config
+        .cookies_from_browser
+        .as_deref()
+        .map(cookies::check_browser)
+    {
+        let span = document
+            .as_ref()
+            .and_then(|document| document.get("cookies_from_browser")?.span());
+        problems.push(at(span, e));
+    }
+    let tables = document
+        .as_ref()
+        .and_then(|document| document.get("items")?.as_array_of_tables());
+    for (i, item) in config.items.iter().enumerate() {
+        let table = tables.and_then(|tables| tables.get(i));
+        for (field, problem) in item_problems(item) {
+            let span = table.and_then(|table| {
+                let value = table.get(field).and_then(|value| value.span());
+                value.or_else(|| table.span())
+            });
+            let problem = tr!("item-problem", playlist = item.name(), problem = problem);
+            problems.push(at(span, problem));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_problems_with_their_lines() {
+        let content = "min_free_space = \"lots\"\n\n\
+                       [[items]]\nid = \"PL1\"\nlocation = \"/music/a\"\nformat = \"video\"\n\
+                       container = \"avi\"\nsave_playlist = true\n\n\
+                       [[items]]\nid = \"PL2\"\nformat = \"audio\"\nsave_playlist = false\n\
+                       chmod = \"rw\"\n";
+        let config: Config = toml::from_str(content).unwrap();
+        let problems = check(&config, content);
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[0].starts_with("line 1: min_free_space is invalid"));
+        assert!(problems[1].starts_with("line 7: playlist PL1: container \"avi\""));
+        assert!(problems[2].starts_with("line 10: playlist PL2: it has no location"));
+        assert!(problems[3].starts_with("line 14: playlist PL2: chmod \"rw\""));
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::cancel::CancelToken;
+use crate::i18n::tr;
+use crate::storage::Storage;
+
+// Extensions of the media files yt-dlp produces.
+const MEDIA_EXTENSIONS: [&str; 10] = [
+    "opus", "m4a", "mp3", "ogg", "flac", "wav", "webm", "mkv", "mp4", "mov",
+];
+
+// A file in a location that looks broken.
+pub struct Problem {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+// Check the files in a location for signs of a download that never finished. A deep check also
+// decodes every media file with ffmpeg, which catches truncated or corrupt files that look fine
+// from the outside. Only the media files yt-sync created, as given by managed, are checked.
+pub fn verify_location(
+    storage: &dyn Storage,
+    location: &Path,
+    managed: &BTreeSet<String>,
+    deep: bool,
+    cancel: &CancelToken,
+) -> io::Result<(usize, Vec<Problem>)> {
+    let mut names = storage.scan(location)?;
+    names.sort();
+
+    let mut checked = 0;
+    let mut problems = Vec::new();
+    for name in names {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let path = location.join(&name);
+        if name.ends_with(".part") || name.contains(".part-Frag") || name.ends_with(".ytdl") {
+            problems.push(Problem {
+                path,
+                reason: tr!("unfinished-download"),
+            });
+            continue;
+        }
+        let is_media = Path::new(&name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| MEDIA_EXTENSIONS.contains(&extension));
+        if !is_media || !managed.contains(&name) {
+            continue;
+        }
+
+        checked += 1;
+        let reason = if storage.size(&path)? == 0 {
+            Some(tr!("empty-file"))
+        } else if deep {
+            decode_error(&path, cancel)?
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            problems.push(Problem { path, reason });
+        }
+    }
+    Ok((checked, problems))
+}
+
+// Decode a file with ffmpeg without writing the result anywhere, returning the first error it
+// reports, if any.
+fn decode_error(path: &Path, cancel: &CancelToken) -> io::Result<Option<String>> {
+    let process = cancel
+        .spawn(
+            Command::new("ffmpeg")
+                .args(["-nostdin", "-v", "error", "-i"])
+                .arg(path)
+                .args(["-f", "null", "-"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped()),
+        )
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::other(tr!("ffmpeg-not-found")),
+            _ => e,
+        })?;
+
+    let mut stderr = process.take_stderr().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut content = String::new();
+        let _ = stderr.read_to_string(&mut content);
+        content
+    });
+    let status = process.wait()?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if cancel.is_cancelled() {
+        return Ok(None);
+    }
+    Ok(match stderr.lines().find(|line| !line.trim().is_empty()) {
+        Some(line) => Some(line.trim().to_string()),
+        None if !status.success() => Some(tr!("ffmpeg-failed", status = status.to_string())),
+        None => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn finds_unfinished_downloads() {
+        let storage = MemoryStorage::default();
+        for (name, contents) in [
+            ("One [a].opus", &b"audio"[..]),
+            ("Two [b].opus", b""),
+            ("Three [c].webm.part", b"audio"),
+            ("cover.txt", b""),
+            ("Mine.mp3", b""),
+        ] {
+            storage
+                .write(&Path::new("/music/mix").join(name), contents)
+                .unwrap();
+        }
+
+        let managed = ["One [a].opus", "Two [b].opus", "cover.txt"]
+            .map(String::from)
+            .into();
+        let (checked, problems) = verify_location(
+            &storage,
+            Path::new("/music/mix"),
+            &managed,
+            false,
+            &CancelToken::default(),
+        )
+        .unwrap();
+
+        assert_eq!(checked, 2);
+        let reasons: Vec<(&Path, &str)> = problems
+            .iter()
+            .map(|problem| (problem.path.as_path(), problem.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (
+                    Path::new("/music/mix/Three [c].webm.part"),
+                    "left over from an unfinished download"
+                ),
+                (Path::new("/music/mix/Two [b].opus"), "empty file"),
+            ]
+        );
+    }
+}
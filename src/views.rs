@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::playlist::{civil_date, sanitize_filename};
+use crate::storage::Storage;
+
+// The view directories kept inside a views directory.
+const BY_UPLOADER: &str = "by-uploader";
+const BY_YEAR: &str = "by-year";
+
+// A file of a location, with what the views list it under.
+pub struct Viewed {
+    // The path of the file inside the location, with / between directories.
+    pub path: String,
+    pub uploader: Option<String>,
+    // When the video was uploaded, in seconds since the Unix epoch.
+    pub uploaded_at: Option<i64>,
+}
+
+// Bring the views of a location up to date, so by-uploader/<uploader>/ and by-year/<year>/ in
+// the views directory hold a symlink to each of the files given. Links into the location that
+// are no longer wanted are removed, along with directories left empty, while links into other
+// locations are left alone, so several locations can share a views directory.
+pub fn update(
+    storage: &dyn Storage,
+    views_dir: &Path,
+    location: &Path,
+    files: impl IntoIterator<Item = io::Result<Viewed>>,
+) -> io::Result<()> {
+    // Links point at absolute paths, so they work wherever the views directory is.
+    let location = storage.canonicalize(location)?;
+    let mut wanted = BTreeMap::new();
+    for file in files {
+        let file = file?;
+        let target = location.join(&file.path);
+        let Some(link_name) = target.file_name() else {
+            continue;
+        };
+        if let Some(uploader) = &file.uploader {
+            let dir = views_dir
+                .join(BY_UPLOADER)
+                .join(sanitize_filename(uploader));
+            wanted.insert(dir.join(link_name), target.clone());
+        }
+        if let Some(uploaded_at) = file.uploaded_at {
+            let dir = views_dir
+                .join(BY_YEAR)
+                .join(civil_date(uploaded_at).0.to_string());
+            wanted.insert(dir.join(link_name), target.clone());
+        }
+    }
+
+    for view in [BY_UPLOADER, BY_YEAR] {
+        let view_dir = views_dir.join(view);
+        for group in scan(storage, &view_dir)? {
+            let group_dir = view_dir.join(group);
+            for link_name in scan(storage, &group_dir)? {
+                let link = group_dir.join(link_name);
+                let Ok(target) = storage.read_link(&link) else {
+                    continue;
+                };
+                if !target.starts_with(&location) {
+                    continue;
+                }
+                if wanted.get(&link) == Some(&target) {
+                    wanted.remove(&link);
+                } else {
+                    storage.delete(&link)?;
+                }
+            }
+            if scan(storage, &group_dir)?.is_empty() {
+                storage.remove_dir(&group_dir)?;
+            }
+        }
+    }
+
+    for (link, target) in wanted {
+        storage.create_dir(link.parent().unwrap_or(views_dir))?;
+        match storage.symlink(&target, &link) {
+            // Something else already has this name, such as a link into another location.
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+fn scan(storage: &dyn Storage, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    match storage.scan(dir) {
+        Ok(names) => Ok(names.into_iter().map(PathBuf::from).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn links_files_by_uploader_and_year() {
+        let storage = MemoryStorage::default();
+        let (views_dir, location) = (Path::new("/views"), Path::new("/music/mix"));
+        let viewed = |path: &str, uploader: &str, uploaded_at| Viewed {
+            path: path.to_string(),
+            uploader: Some(uploader.to_string()),
+            uploaded_at: Some(uploaded_at),
+        };
+        storage
+            .symlink(
+                Path::new("/music/other/Old.opus"),
+                Path::new("/views/by-year/1999/Old.opus"),
+            )
+            .unwrap();
+
+        let files = || {
+            [
+                viewed("One [a].opus", "Band", 1_704_067_200),
+                viewed("Two [b].opus", "AC/DC", 1_577_836_799),
+            ]
+            .map(Ok)
+        };
+        update(&storage, views_dir, location, files()).unwrap();
+        assert_eq!(
+            storage
+                .read_link(Path::new("/views/by-uploader/AC⧸DC/Two [b].opus"))
+                .unwrap(),
+            Path::new("/music/mix/Two [b].opus")
+        );
+        assert_eq!(
+            storage.scan(Path::new("/views/by-year")).unwrap(),
+            ["1999", "2019", "2024"]
+        );
+
+        update(&storage, views_dir, location, files().into_iter().take(1)).unwrap();
+        assert_eq!(
+            storage.scan(Path::new("/views/by-uploader")).unwrap(),
+            ["Band"]
+        );
+        assert_eq!(
+            storage.scan(Path::new("/views/by-year")).unwrap(),
+            ["1999", "2024"]
+        );
+    }
+}